@@ -0,0 +1,60 @@
+use std::{hint::black_box, num::NonZeroU16};
+
+use blerp::{
+    buffer::{AudioBuffer, Layout},
+    Block,
+};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+const TRACKS: usize = 16;
+
+fn tracks(frames: usize) -> Vec<Vec<Block<f32, 2>>> {
+    (0..TRACKS)
+        .map(|track| {
+            (0..frames)
+                .map(|frame| Block::from([((track * frame) % 97) as f32 / 97., ((track + frame) % 89) as f32 / -89.]))
+                .collect()
+        })
+        .collect()
+}
+
+/// Mix 16 stereo tracks into one, by summing `Block`s frame by frame and by accumulating whole buffers.
+fn mix(c: &mut Criterion) {
+    let mut group = c.benchmark_group("mix");
+    for frames in [256, 4096] {
+        let blocks = tracks(frames);
+        let buffers: Vec<AudioBuffer<f32>> = blocks.iter().map(|track| AudioBuffer::from_blocks(track.iter().copied(), Layout::Interleaved)).collect();
+        group.bench_with_input(BenchmarkId::new("Block::sum", frames), &blocks, |b, blocks| {
+            b.iter(|| (0..frames).map(|frame| blocks.iter().map(|track| track[frame]).sum()).collect::<Vec<Block<f32, 2>>>());
+        });
+        group.bench_with_input(BenchmarkId::new("AudioBuffer::mix", frames), &buffers, |b, buffers| {
+            b.iter(|| {
+                let mut bus = AudioBuffer::new(NonZeroU16::new(2).unwrap(), frames, Layout::Interleaved);
+                for track in buffers {
+                    bus.mix(track, black_box(0.5));
+                }
+                bus
+            });
+        });
+    }
+    group.finish();
+}
+
+/// Apply a gain to a stereo track, with `Block`'s `Mul` and with `AudioBuffer`'s.
+fn gain(c: &mut Criterion) {
+    let mut group = c.benchmark_group("gain");
+    let blocks = tracks(4096).swap_remove(1);
+    let buffer = AudioBuffer::from_blocks(blocks.iter().copied(), Layout::Interleaved);
+    group.bench_function("Block::mul", |b| b.iter(|| blocks.iter().map(|&block| block * black_box(0.5)).collect::<Vec<_>>()));
+    group.bench_function("AudioBuffer::mul_assign", |b| {
+        b.iter(|| {
+            let mut buffer = buffer.clone();
+            buffer *= black_box(0.5);
+            buffer
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(benches, mix, gain);
+criterion_main!(benches);
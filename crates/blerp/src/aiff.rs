@@ -0,0 +1,408 @@
+use std::{
+    io::{self, Read, Write},
+    num::NonZeroU16,
+};
+
+use thiserror::Error;
+
+use crate::wavefile::{ChannelMask, CuePoint, Format, Metadata, WaveFile};
+
+/// The timestamp in the `FVER` chunk of an AIFF-C file, which identifies the only version of the format (May 23, 1990).
+const AIFC_VERSION_1: u32 = 0xA280_5140;
+
+/// The size of each block of frames that is converted to big-endian before it is written.
+const WRITE_BUFFER_LEN: usize = 1 << 16;
+
+/// How the samples in the `SSND` chunk of an AIFF-C file are stored, from the compression type in its `COMM` chunk.
+///
+/// A plain AIFF file always stores big-endian integer samples, i.e. [`Compression::None`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// Big-endian integer samples (`NONE`, and the equivalent `twos`, `in24` and `in32`).
+    None,
+    /// Little-endian integer samples (`sowt`), which some macOS software writes.
+    Sowt,
+    /// Big-endian 32-bit floating point samples (`fl32`).
+    Float32,
+    /// Big-endian 64-bit floating point samples (`fl64`).
+    Float64,
+}
+
+impl Compression {
+    const fn from_id(id: [u8; 4]) -> Option<Self> {
+        match &id {
+            b"NONE" | b"twos" | b"in24" | b"in32" => Some(Self::None),
+            b"sowt" => Some(Self::Sowt),
+            b"fl32" | b"FL32" => Some(Self::Float32),
+            b"fl64" | b"FL64" => Some(Self::Float64),
+            _ => None,
+        }
+    }
+
+    const fn id(self) -> [u8; 4] {
+        match self {
+            Self::None => *b"NONE",
+            Self::Sowt => *b"sowt",
+            Self::Float32 => *b"fl32",
+            Self::Float64 => *b"fl64",
+        }
+    }
+
+    /// The human-readable name that is written after the compression type.
+    const fn name(self) -> &'static str {
+        match self {
+            Self::None => "not compressed",
+            Self::Sowt => "little endian",
+            Self::Float32 => "32-bit floating point",
+            Self::Float64 => "64-bit floating point",
+        }
+    }
+
+    const fn format(self) -> Format {
+        match self {
+            Self::None | Self::Sowt => Format::PulseCodeModulation,
+            Self::Float32 | Self::Float64 => Format::FloatingPoint,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum AiffReadError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("not an AIFF or AIFF-C file")]
+    BadMagic,
+    #[error("`{}` chunk is truncated", .0.escape_ascii())]
+    TruncatedChunk([u8; 4]),
+    #[error("missing `{}` chunk", .0.escape_ascii())]
+    MissingChunk([u8; 4]),
+    #[error("unsupported compression type `{}`", .0.escape_ascii())]
+    UnsupportedCompression([u8; 4]),
+    #[error("invalid `COMM` chunk")]
+    InvalidCommonChunk,
+}
+
+#[derive(Error, Debug)]
+pub enum AiffWriteError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("data too long")]
+    DataTooLong,
+    #[error("unsupported sample format: {bytes_per_sample}-byte {format:?} with {compression:?} compression")]
+    UnsupportedSampleFormat { format: Format, bytes_per_sample: u16, compression: Compression },
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+}
+
+/// Convert an 80-bit extended precision number (which AIFF uses for sample rates) to the nearest integer, or [`None`] if it is negative or too big for a [`u32`].
+fn from_extended(bytes: &[u8]) -> Option<u32> {
+    let sign_exponent = u16_at(bytes, 0);
+    let mantissa = u64::from_be_bytes(bytes[2..10].try_into().expect("slice is 8 bytes long"));
+    if mantissa == 0 {
+        return Some(0);
+    }
+    if sign_exponent & 0x8000 != 0 {
+        return None;
+    }
+    // The mantissa has an explicit integer bit, so the binary point is after its first bit.
+    let exponent = i32::from(sign_exponent) - 16383;
+    match exponent {
+        ..-1 => Some(0),
+        -1..=31 => {
+            // Keep one bit after the binary point, to round to the nearest integer.
+            let doubled = u128::from(mantissa) << 1 >> (63 - exponent);
+            u32::try_from((doubled + 1) >> 1).ok()
+        }
+        _ => None,
+    }
+}
+
+/// Convert an integer to an 80-bit extended precision number.
+fn to_extended(value: u32) -> [u8; 10] {
+    if value == 0 {
+        return [0; 10];
+    }
+    let exponent = value.ilog2();
+    let [_, _, high, low] = (16383 + exponent).to_be_bytes();
+    let mut bytes = [high, low, 0, 0, 0, 0, 0, 0, 0, 0];
+    bytes[2..].copy_from_slice(&(u64::from(value) << (63 - exponent)).to_be_bytes());
+    bytes
+}
+
+/// Read a Pascal string (a length byte followed by that many bytes of text) at the start of `bytes`, returning it and the number of bytes it takes up including padding.
+fn pascal_string(bytes: &[u8]) -> Option<(String, usize)> {
+    let len = usize::from(*bytes.first()?);
+    let text = bytes.get(1..1 + len)?;
+    // The length byte and the text are padded to an even number of bytes.
+    Some((String::from_utf8_lossy(text).into_owned(), 1 + len + (len + 1) % 2))
+}
+
+fn push_pascal_string(out: &mut Vec<u8>, text: &str) {
+    let bytes = &text.as_bytes()[..text.len().min(usize::from(u8::MAX))];
+    out.push(u8::try_from(bytes.len()).expect("string was truncated to 255 bytes"));
+    out.extend_from_slice(bytes);
+    if bytes.len().is_multiple_of(2) {
+        out.push(0);
+    }
+}
+
+/// Append a chunk with the given id and body to `out`, followed by a padding byte if the body has an odd length.
+fn push_chunk(out: &mut Vec<u8>, id: [u8; 4], body: &[u8]) -> Result<(), AiffWriteError> {
+    out.extend_from_slice(&id);
+    out.extend_from_slice(&u32::try_from(body.len()).map_err(|_| AiffWriteError::DataTooLong)?.to_be_bytes());
+    out.extend_from_slice(body);
+    if body.len() % 2 == 1 {
+        out.push(0);
+    }
+    Ok(())
+}
+
+/// The contents of a `COMM` chunk.
+struct Common {
+    channels: NonZeroU16,
+    frames: u32,
+    sample_size: u16,
+    sample_rate: u32,
+    compression: Compression,
+}
+
+fn parse_common(chunk: &[u8], aifc: bool) -> Result<Common, AiffReadError> {
+    if chunk.len() < if aifc { 22 } else { 18 } {
+        return Err(AiffReadError::TruncatedChunk(*b"COMM"));
+    }
+    let compression = if aifc {
+        let id = chunk[18..22].try_into().expect("slice is 4 bytes long");
+        Compression::from_id(id).ok_or(AiffReadError::UnsupportedCompression(id))?
+    } else {
+        Compression::None
+    };
+    let sample_size = u16_at(chunk, 6);
+    let valid_size = match compression {
+        Compression::None | Compression::Sowt => (1..=32).contains(&sample_size),
+        Compression::Float32 => sample_size == 32,
+        Compression::Float64 => sample_size == 64,
+    };
+    if !valid_size {
+        return Err(AiffReadError::InvalidCommonChunk);
+    }
+    Ok(Common {
+        channels: NonZeroU16::new(u16_at(chunk, 0)).ok_or(AiffReadError::InvalidCommonChunk)?,
+        frames: u32_at(chunk, 2),
+        sample_size,
+        sample_rate: from_extended(&chunk[8..18]).ok_or(AiffReadError::InvalidCommonChunk)?,
+        compression,
+    })
+}
+
+fn parse_markers(chunk: &[u8], cue_points: &mut Vec<CuePoint>) -> Result<(), AiffReadError> {
+    let truncated = || AiffReadError::TruncatedChunk(*b"MARK");
+    let count = chunk.get(0..2).map(|count| u16_at(count, 0)).ok_or_else(truncated)?;
+    let mut offset = 2;
+    for _ in 0..count {
+        let header = chunk.get(offset..offset + 6).ok_or_else(truncated)?;
+        let (name, name_len) = pascal_string(&chunk[offset + 6..]).ok_or_else(truncated)?;
+        cue_points.push(CuePoint {
+            id: u32::from(u16_at(header, 0)),
+            position: u32_at(header, 2),
+            label: (!name.is_empty()).then_some(name),
+            ..CuePoint::default()
+        });
+        offset += 6 + name_len;
+    }
+    Ok(())
+}
+
+/// Parse an AIFF or AIFF-C file into a [`WaveFile`], converting the samples to the little-endian layout that [`WaveFile`] uses so that they can be decoded with
+/// [`WaveFile::frames`] like any other file.
+///
+/// The `NAME`, `AUTH`, `(c) ` and `ANNO` chunks become the title, artist, copyright (`ICOP`) and comment tags in [`Metadata::info`], and the markers in the `MARK` chunk become cue
+/// points. Other chunks are skipped.
+/// # Errors
+/// Returns an [`AiffReadError`] if the file is not an AIFF or AIFF-C file, is truncated, is missing its `COMM` chunk (or its `SSND` chunk, when it has any frames), or uses a
+/// compression type other than those in [`Compression`].
+pub fn parse(bytes: &[u8]) -> Result<WaveFile<'static>, AiffReadError> {
+    if bytes.len() < 12 || &bytes[0..4] != b"FORM" || !matches!(&bytes[8..12], b"AIFF" | b"AIFC") {
+        return Err(AiffReadError::BadMagic);
+    }
+    let aifc = &bytes[8..12] == b"AIFC";
+    let end = (u32_at(bytes, 4) as usize).saturating_add(8).min(bytes.len());
+    let mut common = None;
+    let mut sound = None;
+    let mut metadata = Metadata::default();
+    let mut offset = 12;
+    while offset + 8 <= end {
+        let id = [bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]];
+        let size = u32_at(bytes, offset + 4) as usize;
+        let start = offset + 8;
+        let chunk = bytes.get(start..start.saturating_add(size)).filter(|_| start + size <= end).ok_or(AiffReadError::TruncatedChunk(id))?;
+        // Chunks are word-aligned, so odd-sized chunks are followed by a padding byte.
+        offset = start + size + size % 2;
+        let text = || String::from_utf8_lossy(chunk).trim_end_matches('\0').to_owned();
+        match &id {
+            b"COMM" => common = Some(parse_common(chunk, aifc)?),
+            b"SSND" => {
+                let data_offset = chunk.get(0..4).map(|data_offset| u32_at(data_offset, 0) as usize).ok_or(AiffReadError::TruncatedChunk(id))?;
+                sound = Some(chunk.get(data_offset.saturating_add(8)..).ok_or(AiffReadError::TruncatedChunk(id))?);
+            }
+            b"MARK" => parse_markers(chunk, &mut metadata.cue_points)?,
+            b"NAME" => metadata.info.title = Some(text()),
+            b"AUTH" => metadata.info.artist = Some(text()),
+            b"(c) " => metadata.info.other.push((*b"ICOP", text())),
+            b"ANNO" => {
+                // There can be any number of annotations, but only one comment.
+                let comment = metadata.info.comment.get_or_insert_with(String::new);
+                if !comment.is_empty() {
+                    comment.push('\n');
+                }
+                comment.push_str(&text());
+            }
+            _ => {}
+        }
+    }
+    let Common {
+        channels,
+        frames,
+        sample_size,
+        sample_rate,
+        compression,
+    } = common.ok_or(AiffReadError::MissingChunk(*b"COMM"))?;
+    let bytes_per_sample = sample_size.div_ceil(8);
+    let block_align = usize::from(bytes_per_sample) * usize::from(channels.get());
+    let sound = match sound {
+        Some(sound) => sound,
+        None if frames == 0 => &[],
+        None => return Err(AiffReadError::MissingChunk(*b"SSND")),
+    };
+    let mut data = sound[..sound.len().min((frames as usize).saturating_mul(block_align))].to_vec();
+    match (compression, bytes_per_sample) {
+        // AIFF has signed 8-bit samples, but WAV has unsigned ones.
+        (_, 1) => data.iter_mut().for_each(|byte| *byte ^= 0x80),
+        (Compression::Sowt, _) => {}
+        _ => data.chunks_exact_mut(usize::from(bytes_per_sample)).for_each(<[u8]>::reverse),
+    }
+    Ok(WaveFile {
+        format: compression.format(),
+        channels,
+        sample_rate,
+        bytes_per_sample,
+        valid_bits_per_sample: sample_size,
+        channel_mask: ChannelMask::default_for_channels(channels.get()),
+        metadata,
+        total_frames: None,
+        data: data.into(),
+    })
+}
+
+/// Read an AIFF or AIFF-C file from a reader, as with [`parse`].
+/// # Errors
+/// Returns an [`AiffReadError::Io`] if reading from the reader fails, or any other [`AiffReadError`] that [`parse`] returns.
+pub fn read(reader: &mut impl Read) -> Result<WaveFile<'static>, AiffReadError> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    parse(&bytes)
+}
+
+/// Write a [`WaveFile`] as a plain AIFF file if it has integer samples, or as an AIFF-C file with `fl32` or `fl64` compression if it has floating point samples.
+/// # Errors
+/// Returns an [`AiffWriteError`] for the same reasons as [`write_with_compression`].
+pub fn write(file: &WaveFile, writer: &mut impl Write) -> Result<(), AiffWriteError> {
+    let compression = match (file.format, file.bytes_per_sample) {
+        (Format::FloatingPoint, 8) => Compression::Float64,
+        (Format::FloatingPoint, _) => Compression::Float32,
+        // AIFF-C has its own compressed formats, so compressed WAV samples are left for `write_with_compression` to reject.
+        (Format::PulseCodeModulation | Format::ALaw | Format::MuLaw | Format::ImaAdpcm, _) => Compression::None,
+    };
+    write_with_compression(file, writer, compression)
+}
+
+/// Write a [`WaveFile`] as an AIFF-C file with the given compression type, or as a plain AIFF file for [`Compression::None`].
+///
+/// The title, artist, copyright (`ICOP`) and comment tags are written as `NAME`, `AUTH`, `(c) ` and `ANNO` chunks, and cue points are written as markers (numbered from 1, because
+/// AIFF marker ids are 16-bit) with their labels. Other metadata is not written, because AIFF has nowhere to store it.
+/// # Errors
+/// Returns an [`AiffWriteError::Io`] if writing to the writer fails, [`AiffWriteError::DataTooLong`] if the file is too big for AIFF's 32-bit sizes, or
+/// [`AiffWriteError::UnsupportedSampleFormat`] if the compression type can't store the file's samples (e.g. [`Compression::Float32`] for integer samples).
+pub fn write_with_compression(file: &WaveFile, writer: &mut impl Write, compression: Compression) -> Result<(), AiffWriteError> {
+    let supported = match compression {
+        Compression::None | Compression::Sowt => file.format == Format::PulseCodeModulation && (1..=4).contains(&file.bytes_per_sample),
+        Compression::Float32 => file.format == Format::FloatingPoint && file.bytes_per_sample == 4,
+        Compression::Float64 => file.format == Format::FloatingPoint && file.bytes_per_sample == 8,
+    };
+    if !supported {
+        return Err(AiffWriteError::UnsupportedSampleFormat {
+            format: file.format,
+            bytes_per_sample: file.bytes_per_sample,
+            compression,
+        });
+    }
+    let aifc = compression != Compression::None;
+    let bytes_per_sample = usize::from(file.bytes_per_sample);
+    let block_align = bytes_per_sample * usize::from(file.channels.get());
+    let frames = file.data.len() / block_align;
+    let data = &file.data[..frames * block_align];
+
+    let mut chunks = Vec::new();
+    if aifc {
+        push_chunk(&mut chunks, *b"FVER", &AIFC_VERSION_1.to_be_bytes())?;
+    }
+    let mut common = file.channels.get().to_be_bytes().to_vec();
+    common.extend_from_slice(&u32::try_from(frames).map_err(|_| AiffWriteError::DataTooLong)?.to_be_bytes());
+    common.extend_from_slice(&file.valid_bits_per_sample.min(file.bytes_per_sample * 8).to_be_bytes());
+    common.extend_from_slice(&to_extended(file.sample_rate));
+    if aifc {
+        common.extend_from_slice(&compression.id());
+        push_pascal_string(&mut common, compression.name());
+    }
+    push_chunk(&mut chunks, *b"COMM", &common)?;
+    if !file.metadata.cue_points.is_empty() {
+        let mut markers = u16::try_from(file.metadata.cue_points.len()).map_err(|_| AiffWriteError::DataTooLong)?.to_be_bytes().to_vec();
+        for (id, cue_point) in (1_u16..).zip(&file.metadata.cue_points) {
+            markers.extend_from_slice(&id.to_be_bytes());
+            markers.extend_from_slice(&cue_point.position.to_be_bytes());
+            push_pascal_string(&mut markers, cue_point.label.as_deref().unwrap_or_default());
+        }
+        push_chunk(&mut chunks, *b"MARK", &markers)?;
+    }
+    let info = &file.metadata.info;
+    let copyright = info.other.iter().find(|(id, _)| id == b"ICOP").map(|(_, value)| value);
+    for (id, text) in [
+        (*b"NAME", info.title.as_ref()),
+        (*b"AUTH", info.artist.as_ref()),
+        (*b"(c) ", copyright),
+        (*b"ANNO", info.comment.as_ref()),
+    ] {
+        if let Some(text) = text {
+            push_chunk(&mut chunks, id, text.as_bytes())?;
+        }
+    }
+
+    let padding = data.len() % 2;
+    let form_len = 4 + chunks.len() + 8 + 8 + data.len() + padding;
+    writer.write_all(b"FORM")?;
+    writer.write_all(&u32::try_from(form_len).map_err(|_| AiffWriteError::DataTooLong)?.to_be_bytes())?;
+    writer.write_all(if aifc { b"AIFC" } else { b"AIFF" })?;
+    writer.write_all(&chunks)?;
+    writer.write_all(b"SSND")?;
+    writer.write_all(&u32::try_from(8 + data.len()).map_err(|_| AiffWriteError::DataTooLong)?.to_be_bytes())?;
+    // The offset and block size are only used by files that align their samples to blocks, which this doesn't.
+    writer.write_all(&[0; 8])?;
+    let mut buffer = Vec::with_capacity(WRITE_BUFFER_LEN);
+    for block in data.chunks((WRITE_BUFFER_LEN / block_align).max(1) * block_align) {
+        buffer.clear();
+        buffer.extend_from_slice(block);
+        match (compression, bytes_per_sample) {
+            (_, 1) => buffer.iter_mut().for_each(|byte| *byte ^= 0x80),
+            (Compression::Sowt, _) => {}
+            _ => buffer.chunks_exact_mut(bytes_per_sample).for_each(<[u8]>::reverse),
+        }
+        writer.write_all(&buffer)?;
+    }
+    writer.write_all(&[0; 1][..padding])?;
+    Ok(())
+}
@@ -0,0 +1,290 @@
+use std::{
+    iter::{Copied, StepBy, Take},
+    num::NonZeroU16,
+    ops::{AddAssign, MulAssign, SubAssign},
+    slice::Iter,
+};
+
+use cpal::Sample;
+use num::Float;
+
+use crate::Block;
+
+/// The number of samples that the arithmetic kernels work on at a time, which is enough to fill the widest SIMD registers with `f32`s.
+const LANES: usize = 16;
+
+/// How the samples of an [`AudioBuffer`] are ordered.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Layout {
+    /// Frame by frame, with the samples of each frame next to each other, as in a WAV file or a [`Block`] iterator.
+    #[default]
+    Interleaved,
+    /// Channel by channel, with all of the first channel's samples, then all of the second channel's samples, and so on.
+    Planar,
+}
+
+/// A buffer of samples for any number of channels and frames, chosen at runtime.
+///
+/// Unlike an iterator of [`Block`]s, arithmetic on whole buffers of floating-point samples works on the samples directly rather than converting each one to and from `f64`, in a
+/// way that the compiler can vectorize.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AudioBuffer<T: Sample> {
+    samples: Vec<T>,
+    channels: NonZeroU16,
+    frames: usize,
+    layout: Layout,
+}
+
+/// An iterator over the samples of one channel or frame of an [`AudioBuffer`].
+pub type Samples<'a, T> = Take<StepBy<Copied<Iter<'a, T>>>>;
+
+impl<T: Sample> AudioBuffer<T> {
+    /// Create a new silent [`AudioBuffer`].
+    #[must_use]
+    pub fn new(channels: NonZeroU16, frames: usize, layout: Layout) -> Self {
+        Self {
+            samples: vec![T::EQUILIBRIUM; usize::from(channels.get()) * frames],
+            channels,
+            frames,
+            layout,
+        }
+    }
+
+    /// Create a new [`AudioBuffer`] from samples that are already in the given layout.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of samples is not a multiple of the number of channels.
+    #[must_use]
+    pub fn from_samples(samples: Vec<T>, channels: NonZeroU16, layout: Layout) -> Self {
+        assert!(
+            samples.len().is_multiple_of(usize::from(channels.get())),
+            "{} samples can't be split into {channels} channels",
+            samples.len()
+        );
+        Self {
+            frames: samples.len() / usize::from(channels.get()),
+            samples,
+            channels,
+            layout,
+        }
+    }
+
+    /// Create a new [`AudioBuffer`] from frames, e.g. from [`WaveFile::frames`](crate::wavefile::WaveFile::frames).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `N` is zero or more than [`u16::MAX`].
+    pub fn from_blocks<const N: usize, B: Into<Block<T, N>>>(blocks: impl IntoIterator<Item = B>, layout: Layout) -> Self {
+        let channels = u16::try_from(N).ok().and_then(NonZeroU16::new).expect("the number of channels should be from 1 to 65535");
+        let samples = blocks.into_iter().flat_map(|block| block.into().0).collect();
+        Self::from_samples(samples, channels, Layout::Interleaved).into_layout(layout)
+    }
+
+    /// Return the number of channels.
+    #[must_use]
+    pub const fn channels(&self) -> NonZeroU16 {
+        self.channels
+    }
+
+    /// Return the number of frames, i.e. the number of samples in each channel.
+    #[must_use]
+    pub const fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// Return the order of the samples.
+    #[must_use]
+    pub const fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    /// Return all of the samples, in the order given by [`AudioBuffer::layout`].
+    #[must_use]
+    pub fn as_slice(&self) -> &[T] {
+        &self.samples
+    }
+
+    /// Return all of the samples mutably, in the order given by [`AudioBuffer::layout`].
+    #[must_use]
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        &mut self.samples
+    }
+
+    /// Return all of the samples, in the order given by [`AudioBuffer::layout`].
+    #[must_use]
+    pub fn into_samples(self) -> Vec<T> {
+        self.samples
+    }
+
+    /// Return the position of a sample in [`AudioBuffer::as_slice`], and the distance between consecutive samples in the same channel.
+    const fn position(&self, channel: usize, frame: usize) -> (usize, usize) {
+        let channels = self.channels.get() as usize;
+        match self.layout {
+            Layout::Interleaved => (frame * channels + channel, channels),
+            Layout::Planar => (channel * self.frames + frame, 1),
+        }
+    }
+
+    /// Return a sample, or [`None`] if the channel or frame is out of range.
+    #[must_use]
+    pub fn get(&self, channel: usize, frame: usize) -> Option<T> {
+        (channel < usize::from(self.channels.get()) && frame < self.frames).then(|| self.samples[self.position(channel, frame).0])
+    }
+
+    /// Return a mutable reference to a sample, or [`None`] if the channel or frame is out of range.
+    #[must_use]
+    pub fn get_mut(&mut self, channel: usize, frame: usize) -> Option<&mut T> {
+        if channel < usize::from(self.channels.get()) && frame < self.frames {
+            let (position, _) = self.position(channel, frame);
+            Some(&mut self.samples[position])
+        } else {
+            None
+        }
+    }
+
+    /// Return an iterator over the samples of one channel.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the channel is out of range.
+    pub fn channel(&self, channel: usize) -> Samples<'_, T> {
+        assert!(channel < usize::from(self.channels.get()), "channel {channel} is out of range for {} channels", self.channels);
+        let (start, stride) = self.position(channel, 0);
+        // An empty interleaved buffer has no samples to start from.
+        self.samples.get(start..).unwrap_or_default().iter().copied().step_by(stride).take(self.frames)
+    }
+
+    /// Return an iterator over mutable references to the samples of one channel, which must be in range.
+    fn channel_mut(&mut self, channel: usize) -> impl Iterator<Item = &mut T> {
+        let (start, stride) = self.position(channel, 0);
+        let frames = self.frames;
+        self.samples.get_mut(start..).unwrap_or_default().iter_mut().step_by(stride).take(frames)
+    }
+
+    /// Return an iterator over the samples of one frame.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the frame is out of range.
+    pub fn frame(&self, frame: usize) -> Samples<'_, T> {
+        assert!(frame < self.frames, "frame {frame} is out of range for {} frames", self.frames);
+        let (start, _) = self.position(0, frame);
+        let stride = match self.layout {
+            Layout::Interleaved => 1,
+            Layout::Planar => self.frames,
+        };
+        self.samples[start..].iter().copied().step_by(stride).take(usize::from(self.channels.get()))
+    }
+
+    /// Return an iterator over the frames of the buffer as [`Block`]s.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `N` is not the number of channels.
+    pub fn blocks<const N: usize>(&self) -> impl Iterator<Item = Block<T, N>> + '_ {
+        assert_eq!(N, usize::from(self.channels.get()), "blocks should have one sample for each channel");
+        (0..self.frames).map(|frame| {
+            let mut samples = self.frame(frame);
+            Block(std::array::from_fn(|_| samples.next().unwrap_or(T::EQUILIBRIUM)))
+        })
+    }
+
+    /// Return the buffer with its samples rearranged into the given layout.
+    #[must_use]
+    pub fn into_layout(self, layout: Layout) -> Self {
+        if self.layout == layout || self.channels.get() == 1 {
+            return Self { layout, ..self };
+        }
+        let mut rearranged = Self::new(self.channels, self.frames, layout);
+        for channel in 0..usize::from(self.channels.get()) {
+            for (sample, value) in rearranged.channel_mut(channel).zip(self.channel(channel)) {
+                *sample = value;
+            }
+        }
+        rearranged
+    }
+}
+
+impl<T: Sample + Float> AudioBuffer<T> {
+    /// Combine each sample with the sample in the same position of `other`.
+    ///
+    /// Buffers with the same layout are processed as flat slices, in fixed-size chunks without bounds checks so that the compiler can vectorize the loop.
+    fn combine(&mut self, other: &Self, operation: impl Fn(T, T) -> T) {
+        assert_eq!(
+            (self.channels, self.frames),
+            (other.channels, other.frames),
+            "buffers should have the same number of channels and frames"
+        );
+        if self.layout == other.layout {
+            let mut chunks = self.samples.chunks_exact_mut(LANES);
+            let mut other_chunks = other.samples.chunks_exact(LANES);
+            for (chunk, other_chunk) in (&mut chunks).zip(&mut other_chunks) {
+                for (sample, other) in chunk.iter_mut().zip(other_chunk) {
+                    *sample = operation(*sample, *other);
+                }
+            }
+            for (sample, other) in chunks.into_remainder().iter_mut().zip(other_chunks.remainder()) {
+                *sample = operation(*sample, *other);
+            }
+        } else {
+            for channel in 0..usize::from(self.channels.get()) {
+                for (sample, other) in self.channel_mut(channel).zip(other.channel(channel)) {
+                    *sample = operation(*sample, other);
+                }
+            }
+        }
+    }
+
+    /// Add each sample of `other`, multiplied by `gain`, to this buffer, e.g. to mix a track into a bus.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffers have different numbers of channels or frames.
+    pub fn mix(&mut self, other: &Self, gain: T) {
+        #[allow(clippy::suboptimal_flops, reason = "`mul_add` is a library call without FMA instructions, which stops the loop from being vectorized")]
+        self.combine(other, |sample, other| sample + other * gain);
+    }
+}
+
+impl<T: Sample + Float> AddAssign<&Self> for AudioBuffer<T> {
+    /// Add each sample of another buffer of the same size.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffers have different numbers of channels or frames.
+    fn add_assign(&mut self, rhs: &Self) {
+        self.combine(rhs, |sample, other| sample + other);
+    }
+}
+
+impl<T: Sample + Float> SubAssign<&Self> for AudioBuffer<T> {
+    /// Subtract each sample of another buffer of the same size.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffers have different numbers of channels or frames.
+    fn sub_assign(&mut self, rhs: &Self) {
+        self.combine(rhs, |sample, other| sample - other);
+    }
+}
+
+impl<T: Sample + Float> MulAssign<&Self> for AudioBuffer<T> {
+    /// Multiply each sample by the sample in the same position of another buffer of the same size, e.g. for ring modulation or applying an envelope.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffers have different numbers of channels or frames.
+    fn mul_assign(&mut self, rhs: &Self) {
+        self.combine(rhs, |sample, other| sample * other);
+    }
+}
+
+impl<T: Sample + Float> MulAssign<T> for AudioBuffer<T> {
+    /// Multiply every sample by a gain.
+    fn mul_assign(&mut self, rhs: T) {
+        for sample in &mut self.samples {
+            *sample = *sample * rhs;
+        }
+    }
+}
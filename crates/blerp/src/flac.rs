@@ -0,0 +1,310 @@
+use std::{
+    array,
+    io::{self, Seek, SeekFrom, Write},
+};
+
+use cpal::{Sample, I24, U24};
+use thiserror::Error;
+
+use crate::Block;
+
+mod bits;
+mod frame;
+
+use bits::BitWriter;
+
+/// The length of the `STREAMINFO` metadata block.
+const STREAMINFO_LEN: usize = 34;
+
+/// The vendor string written in the Vorbis comment block.
+const VENDOR: &str = concat!("blerp ", env!("CARGO_PKG_VERSION"));
+
+/// A sample type that can be encoded as FLAC.
+///
+/// FLAC only stores integer samples, so unsigned samples are converted to signed ones of the same width. Wider and floating point samples have to be converted to one of these first,
+/// e.g. with a [`Quantizer`](crate::processing::dither::Quantizer).
+pub trait FlacSample: Sample {
+    /// The number of bits in each sample.
+    const BITS_PER_SAMPLE: u32;
+
+    /// Convert the sample to the signed integer that FLAC stores.
+    fn to_flac(self) -> i32;
+}
+
+macro_rules! impl_flac_sample {
+    (@to_flac I24, $sample:expr) => {
+        I24::from_sample($sample).inner()
+    };
+    (@to_flac $flac:ty, $sample:expr) => {
+        i32::from(<$flac>::from_sample($sample))
+    };
+    ($($ty:ty => $flac:tt, $bits:literal;)+) => {
+        $(
+            impl FlacSample for $ty {
+                const BITS_PER_SAMPLE: u32 = $bits;
+
+                fn to_flac(self) -> i32 {
+                    impl_flac_sample!(@to_flac $flac, self)
+                }
+            }
+        )+
+    };
+}
+
+impl_flac_sample! {
+    i8 => i8, 8;
+    u8 => i8, 8;
+    i16 => i16, 16;
+    u16 => i16, 16;
+    I24 => I24, 24;
+    U24 => I24, 24;
+}
+
+/// Settings for a [`FlacWriter`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlacOptions {
+    /// The number of frames in each FLAC frame, from 16 to 65535.
+    pub block_size: u16,
+    /// The highest order of linear predictor to try, up to 32, or zero to only use the fixed predictors. Higher orders compress better but encode more slowly.
+    pub max_lpc_order: u8,
+    /// The highest Rice partition order to try, up to 15.
+    pub max_partition_order: u8,
+    /// Vorbis comments (tags) to write, as field name and value pairs, e.g. `("TITLE", "Drums")`. No Vorbis comment block is written if this is empty.
+    pub comments: Vec<(String, String)>,
+}
+
+impl Default for FlacOptions {
+    /// The same settings as libFLAC's default compression level.
+    fn default() -> Self {
+        Self {
+            block_size: 4096,
+            max_lpc_order: 8,
+            max_partition_order: 6,
+            comments: Vec::new(),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum FlacWriteError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("invalid number of channels (FLAC supports 1 to 8)")]
+    InvalidChannels,
+    #[error("invalid sample rate (FLAC supports 1 to 655350 Hz)")]
+    InvalidSampleRate,
+    #[error("invalid block size (FLAC supports 16 to 65535 frames)")]
+    InvalidBlockSize,
+    #[error("data too long")]
+    DataTooLong,
+}
+
+/// Append a metadata block header to `out`.
+fn push_block_header(out: &mut Vec<u8>, last: bool, kind: u8, len: usize) -> Result<(), FlacWriteError> {
+    let [_, high, middle, low] = u32::try_from(len).ok().filter(|&len| len < 1 << 24).ok_or(FlacWriteError::DataTooLong)?.to_be_bytes();
+    out.extend_from_slice(&[u8::from(last) << 7 | kind, high, middle, low]);
+    Ok(())
+}
+
+/// A writer that encodes frames to a FLAC file as they are produced.
+///
+/// Frames are buffered until there are enough for a FLAC frame of [`FlacOptions::block_size`], and the `STREAMINFO` block (which holds the length of the stream and the MD5
+/// checksum of its samples) is written with placeholders and patched by [`FlacWriter::finalize`] (or when the writer is dropped).
+pub struct FlacWriter<W: Write + Seek, T: FlacSample, const N: usize> {
+    writer: Option<W>,
+    options: FlacOptions,
+    sample_rate: u32,
+    streaminfo_offset: u64,
+    /// The samples of each channel that haven't been encoded yet.
+    pending: [Vec<i32>; N],
+    frame_number: u32,
+    frames_written: u64,
+    min_frame_len: usize,
+    max_frame_len: usize,
+    md5: md5::Context,
+    buffer: Vec<u8>,
+    sample: std::marker::PhantomData<T>,
+}
+
+impl<W: Write + Seek, T: FlacSample, const N: usize> FlacWriter<W, T, N> {
+    /// Create a new [`FlacWriter`], writing the metadata blocks to `writer` at its current position.
+    /// # Errors
+    /// Returns a [`FlacWriteError::Io`] if writing to the writer fails, [`FlacWriteError::InvalidChannels`] if `N` is zero or more than 8,
+    /// [`FlacWriteError::InvalidSampleRate`] or [`FlacWriteError::InvalidBlockSize`] if they are out of FLAC's range, or [`FlacWriteError::DataTooLong`] if the Vorbis comments are
+    /// too long for a metadata block.
+    pub fn new(mut writer: W, sample_rate: u32, options: FlacOptions) -> Result<Self, FlacWriteError> {
+        if !(1..=8).contains(&N) {
+            return Err(FlacWriteError::InvalidChannels);
+        }
+        if !(1..=655_350).contains(&sample_rate) {
+            return Err(FlacWriteError::InvalidSampleRate);
+        }
+        if options.block_size < 16 {
+            return Err(FlacWriteError::InvalidBlockSize);
+        }
+        let mut header = b"fLaC".to_vec();
+        push_block_header(&mut header, options.comments.is_empty(), 0, STREAMINFO_LEN)?;
+        let streaminfo_offset = writer.stream_position()? + header.len() as u64;
+        let mut this = Self {
+            writer: None,
+            options,
+            sample_rate,
+            streaminfo_offset,
+            pending: array::from_fn(|_| Vec::new()),
+            frame_number: 0,
+            frames_written: 0,
+            min_frame_len: usize::MAX,
+            max_frame_len: 0,
+            md5: md5::Context::new(),
+            buffer: Vec::new(),
+            sample: std::marker::PhantomData,
+        };
+        this.push_streaminfo(&mut header);
+        if !this.options.comments.is_empty() {
+            let mut comments = Vec::new();
+            push_length_prefixed(&mut comments, VENDOR.as_bytes())?;
+            comments.extend_from_slice(&u32::try_from(this.options.comments.len()).map_err(|_| FlacWriteError::DataTooLong)?.to_le_bytes());
+            for (name, value) in &this.options.comments {
+                push_length_prefixed(&mut comments, format!("{name}={value}").as_bytes())?;
+            }
+            push_block_header(&mut header, true, 4, comments.len())?;
+            header.extend_from_slice(&comments);
+        }
+        writer.write_all(&header)?;
+        this.writer = Some(writer);
+        Ok(this)
+    }
+
+    /// Append frames to the stream, encoding a FLAC frame whenever enough of them have been buffered.
+    /// # Errors
+    /// Returns a [`FlacWriteError::Io`] if writing to the writer fails, or [`FlacWriteError::DataTooLong`] if the stream has more frames than FLAC can count.
+    #[allow(clippy::missing_panics_doc, reason = "the writer is only taken out when `self` is consumed")]
+    pub fn write_frames<S: Into<Block<T, N>>>(&mut self, frames: impl IntoIterator<Item = S>) -> Result<(), FlacWriteError> {
+        let block_size = usize::from(self.options.block_size);
+        for Block(samples) in frames.into_iter().map(Into::into) {
+            // A block that failed to be written before goes out before anything is added to it.
+            if self.pending[0].len() == block_size {
+                self.write_pending()?;
+            }
+            for (pending, sample) in self.pending.iter_mut().zip(samples) {
+                pending.push(sample.to_flac());
+            }
+            if self.pending[0].len() == block_size {
+                self.write_pending()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Encode the buffered frames to the writer that `self` holds.
+    fn write_pending(&mut self) -> Result<(), FlacWriteError> {
+        let mut writer = self.writer.take().expect("writer has not been finalized");
+        let result = self.encode_pending(&mut writer);
+        self.writer = Some(writer);
+        result
+    }
+
+    /// Return the number of frames written so far, including those that are buffered.
+    #[must_use]
+    pub const fn frames_written(&self) -> u64 {
+        self.frames_written + self.pending[0].len() as u64
+    }
+
+    /// Encode the buffered frames (which may be fewer than a block at the end of the stream) as a FLAC frame.
+    ///
+    /// Nothing is counted or added to the MD5 checksum until the frame has been written, so a failed write leaves the frames buffered.
+    fn encode_pending(&mut self, writer: &mut W) -> Result<(), FlacWriteError> {
+        let len = self.pending[0].len();
+        if len == 0 {
+            return Ok(());
+        }
+        // Frame numbers are 31-bit.
+        if self.frame_number >= 1 << 31 {
+            return Err(FlacWriteError::DataTooLong);
+        }
+        self.buffer.clear();
+        let channels: Vec<&[i32]> = self.pending.iter().map(Vec::as_slice).collect();
+        frame::encode(&mut self.buffer, &channels, T::BITS_PER_SAMPLE, self.sample_rate, self.frame_number, &self.options);
+        writer.write_all(&self.buffer)?;
+        self.min_frame_len = self.min_frame_len.min(self.buffer.len());
+        self.max_frame_len = self.max_frame_len.max(self.buffer.len());
+        self.frame_number += 1;
+        self.frames_written += len as u64;
+
+        // The MD5 checksum is of the samples as interleaved little-endian integers, each as wide as the sample type.
+        let bytes_per_sample = T::BITS_PER_SAMPLE as usize / 8;
+        self.buffer.clear();
+        for index in 0..len {
+            for channel in &self.pending {
+                self.buffer.extend_from_slice(&channel[index].to_le_bytes()[..bytes_per_sample]);
+            }
+        }
+        self.md5.consume(&self.buffer);
+        for channel in &mut self.pending {
+            channel.clear();
+        }
+        Ok(())
+    }
+
+    /// Append the `STREAMINFO` block for the frames written so far to `out`.
+    fn push_streaminfo(&self, out: &mut Vec<u8>) {
+        // The minimum block size doesn't include the last block, so both sizes are the configured one unless the last block is the only one.
+        let block_size = u64::from(self.options.block_size);
+        let block_size = if self.frame_number <= 1 { self.frames_written.clamp(16, block_size) } else { block_size };
+        let mut writer = BitWriter::default();
+        writer.write(block_size, 16);
+        writer.write(block_size, 16);
+        // Frame sizes are zero when they aren't known yet, and can't be stored if they don't fit in 24 bits.
+        let frame_len = |len: usize| u64::try_from(len).ok().filter(|&len| len < 1 << 24).unwrap_or(0);
+        writer.write(if self.max_frame_len == 0 { 0 } else { frame_len(self.min_frame_len) }, 24);
+        writer.write(frame_len(self.max_frame_len), 24);
+        writer.write(self.sample_rate.into(), 20);
+        writer.write(N as u64 - 1, 3);
+        writer.write(u64::from(T::BITS_PER_SAMPLE - 1), 5);
+        // The total number of frames is 36 bits, or zero if it is too big to store.
+        let total = if self.frames_written < 1 << 36 { self.frames_written } else { 0 };
+        writer.write(total >> 32, 4);
+        writer.write(total & u64::from(u32::MAX), 32);
+        out.extend_from_slice(writer.bytes());
+        out.extend_from_slice(&self.md5.clone().finalize().0);
+    }
+
+    /// Encode the buffered frames, patch the `STREAMINFO` block, and return the underlying writer positioned at the end of the stream.
+    /// # Errors
+    /// Returns a [`FlacWriteError::Io`] if writing to or seeking in the writer fails.
+    #[allow(clippy::missing_panics_doc, reason = "the writer is only taken out when `self` is consumed")]
+    pub fn finalize(mut self) -> Result<W, FlacWriteError> {
+        // Taking the writer out first keeps `drop` from encoding the last frame a second time if this fails partway through.
+        let mut writer = self.writer.take().expect("writer has not been finalized");
+        self.finish(&mut writer)?;
+        Ok(writer)
+    }
+
+    fn finish(&mut self, writer: &mut W) -> Result<(), FlacWriteError> {
+        self.encode_pending(writer)?;
+        let mut streaminfo = Vec::with_capacity(STREAMINFO_LEN);
+        self.push_streaminfo(&mut streaminfo);
+        let end = writer.stream_position()?;
+        writer.seek(SeekFrom::Start(self.streaminfo_offset))?;
+        writer.write_all(&streaminfo)?;
+        writer.seek(SeekFrom::Start(end))?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+impl<W: Write + Seek, T: FlacSample, const N: usize> Drop for FlacWriter<W, T, N> {
+    fn drop(&mut self) {
+        if let Some(mut writer) = self.writer.take() {
+            // Errors can't be reported from `drop`, and a stream with placeholders in its `STREAMINFO` block can still be decoded.
+            let _ = self.finish(&mut writer);
+        }
+    }
+}
+
+/// Append a string with a 32-bit little-endian length before it, as Vorbis comments store them.
+fn push_length_prefixed(out: &mut Vec<u8>, string: &[u8]) -> Result<(), FlacWriteError> {
+    out.extend_from_slice(&u32::try_from(string.len()).map_err(|_| FlacWriteError::DataTooLong)?.to_le_bytes());
+    out.extend_from_slice(string);
+    Ok(())
+}
@@ -0,0 +1,86 @@
+/// A buffer that packs values into bytes most significant bit first, which is how everything in a FLAC frame is stored.
+#[derive(Debug, Default)]
+pub(super) struct BitWriter {
+    bytes: Vec<u8>,
+    /// Bits that haven't filled a byte yet, in the lowest `bits` bits.
+    buffer: u64,
+    bits: u32,
+}
+
+impl BitWriter {
+    /// Write the lowest `bits` bits of `value`, where `bits` is at most 32.
+    pub(super) fn write(&mut self, value: u64, bits: u32) {
+        debug_assert!(bits <= 32);
+        self.buffer = self.buffer << bits | value & ((1 << bits) - 1);
+        self.bits += bits;
+        while self.bits >= 8 {
+            self.bits -= 8;
+            self.bytes.push((self.buffer >> self.bits).to_le_bytes()[0]);
+        }
+    }
+
+    /// Write a signed value as a two's complement number of `bits` bits.
+    pub(super) fn write_signed(&mut self, value: i64, bits: u32) {
+        self.write(value.cast_unsigned(), bits);
+    }
+
+    /// Write `zeros` zero bits followed by a one bit.
+    pub(super) fn write_unary(&mut self, mut zeros: u64) {
+        while zeros >= 32 {
+            self.write(0, 32);
+            zeros -= 32;
+        }
+        self.write(1, u32::try_from(zeros).expect("zeros is less than 32") + 1);
+    }
+
+    /// Write a Rice code with the given parameter, i.e. the quotient in unary followed by the remainder in `parameter` bits.
+    pub(super) fn write_rice(&mut self, value: u32, parameter: u32) {
+        self.write_unary(u64::from(value >> parameter));
+        self.write(u64::from(value), parameter);
+    }
+
+    /// Pad the last byte with zero bits.
+    pub(super) fn align(&mut self) {
+        if self.bits > 0 {
+            self.write(0, 8 - self.bits);
+        }
+    }
+
+    /// Return the bytes written so far, which must be aligned to a byte.
+    pub(super) fn bytes(&self) -> &[u8] {
+        debug_assert_eq!(self.bits, 0);
+        &self.bytes
+    }
+}
+
+const fn crc_table<const WIDTH: u32>(polynomial: u16) -> [u16; 256] {
+    let top = 1 << (WIDTH - 1);
+    let mask = if WIDTH == 16 { u16::MAX } else { (1 << WIDTH) - 1 };
+    let mut table = [0; 256];
+    let mut byte = 0;
+    while byte < 256 {
+        #[allow(clippy::cast_possible_truncation, reason = "byte is less than 256")]
+        let mut crc = (byte as u16) << (WIDTH - 8);
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & top == 0 { crc << 1 } else { crc << 1 ^ polynomial } & mask;
+            bit += 1;
+        }
+        table[byte] = crc;
+        byte += 1;
+    }
+    table
+}
+
+const CRC8_TABLE: [u16; 256] = crc_table::<8>(0x07);
+const CRC16_TABLE: [u16; 256] = crc_table::<16>(0x8005);
+
+/// The CRC-8 (polynomial `x^8 + x^2 + x + 1`) that ends a frame header.
+pub(super) fn crc8(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0, |crc, &byte| CRC8_TABLE[usize::from(crc ^ byte)].to_le_bytes()[0])
+}
+
+/// The CRC-16 (polynomial `x^16 + x^15 + x^2 + 1`) that ends a frame.
+pub(super) fn crc16(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0, |crc, &byte| crc << 8 ^ CRC16_TABLE[usize::from((crc >> 8).to_le_bytes()[0] ^ byte)])
+}
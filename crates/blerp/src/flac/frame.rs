@@ -0,0 +1,483 @@
+use std::f64::consts::PI;
+
+use super::{
+    bits::{crc16, crc8, BitWriter},
+    FlacOptions,
+};
+
+/// The fixed predictors, as the coefficients of the previous samples (most recent first).
+const FIXED_PREDICTORS: [&[i64]; 5] = [&[], &[1], &[2, -1], &[3, -3, 1], &[4, -6, 4, -1]];
+
+/// How the samples of a subframe are predicted from the samples before them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Predictor {
+    /// Every sample has the same value.
+    Constant,
+    /// The samples are stored as-is.
+    Verbatim,
+    /// One of the [`FIXED_PREDICTORS`], by order.
+    Fixed(usize),
+    /// A linear predictor with quantized coefficients (most recent sample first), whose sum is shifted right by `shift`.
+    Lpc { coefficients: Vec<i32>, precision: u32, shift: u32 },
+}
+
+impl Predictor {
+    const fn order(&self) -> usize {
+        match self {
+            Self::Constant | Self::Verbatim => 0,
+            Self::Fixed(order) => *order,
+            Self::Lpc { coefficients, .. } => coefficients.len(),
+        }
+    }
+}
+
+/// The Rice coding of a residual, split into `2^order` partitions that each have their own parameter.
+#[derive(Debug, Clone, Default)]
+struct Rice {
+    order: u32,
+    parameters: Vec<u32>,
+}
+
+impl Rice {
+    /// Rice parameters above this need the 5-bit parameter encoding (the largest 4-bit value is an escape code).
+    const MAX_4_BIT_PARAMETER: u32 = 14;
+    const MAX_PARAMETER: u32 = 30;
+
+    /// Choose the partition order and parameters that give the smallest encoding of `residual` (zigzag-encoded), returning them and an estimate of the number of bits they take,
+    /// including the residual coding method and partition order.
+    fn choose(residual: &[u32], block_size: usize, predictor_order: usize, max_order: u32) -> (Self, u64) {
+        // Partitions have to divide the block evenly, and the first one has to be longer than the warm-up samples that it doesn't include.
+        let mut max_order = max_order;
+        while max_order > 0 && (!block_size.is_multiple_of(1 << max_order) || block_size >> max_order <= predictor_order) {
+            max_order -= 1;
+        }
+        let partition_len = block_size >> max_order;
+        let mut sums: Vec<u64> = (0..1 << max_order)
+            .map(|partition| {
+                let start = (partition * partition_len).saturating_sub(predictor_order);
+                let end = (partition + 1) * partition_len - predictor_order;
+                residual[start..end].iter().copied().map(u64::from).sum()
+            })
+            .collect();
+        let mut best = (Self::default(), u64::MAX);
+        for order in (0..=max_order).rev() {
+            let partition_len = block_size >> order;
+            let (parameters, bits): (Vec<_>, Vec<_>) = sums
+                .iter()
+                .enumerate()
+                .map(|(partition, &sum)| best_parameter(sum, partition_len - if partition == 0 { predictor_order } else { 0 }))
+                .unzip();
+            let parameter_bits = if parameters.iter().any(|&parameter| parameter > Self::MAX_4_BIT_PARAMETER) { 5 } else { 4 };
+            let bits = 2 + 4 + parameter_bits * sums.len() as u64 + bits.iter().sum::<u64>();
+            if bits < best.1 {
+                best = (Self { order, parameters }, bits);
+            }
+            sums = sums.chunks(2).map(|pair| pair.iter().sum()).collect();
+        }
+        best
+    }
+
+    fn write(&self, writer: &mut BitWriter, residual: &[u32], block_size: usize, predictor_order: usize) {
+        let parameter_bits = if self.parameters.iter().any(|&parameter| parameter > Self::MAX_4_BIT_PARAMETER) { 5 } else { 4 };
+        writer.write(u64::from(parameter_bits == 5), 2);
+        writer.write(u64::from(self.order), 4);
+        let partition_len = block_size >> self.order;
+        let mut start = 0;
+        for (partition, &parameter) in self.parameters.iter().enumerate() {
+            let end = (partition + 1) * partition_len - predictor_order;
+            writer.write(u64::from(parameter), parameter_bits);
+            for &value in &residual[start..end] {
+                writer.write_rice(value, parameter);
+            }
+            start = end;
+        }
+    }
+}
+
+/// Return the Rice parameter that (approximately) minimises the size of `len` values that add up to `sum`, and that size in bits.
+fn best_parameter(sum: u64, len: usize) -> (u32, u64) {
+    if len == 0 {
+        return (0, 0);
+    }
+    let len = len as u64;
+    // The best parameter is close to the base 2 logarithm of the mean, so only the parameters around it are tried.
+    let estimate = (sum / len).checked_ilog2().unwrap_or(0).min(Rice::MAX_PARAMETER);
+    (estimate.saturating_sub(1)..=(estimate + 1).min(Rice::MAX_PARAMETER))
+        .map(|parameter| (parameter, len * u64::from(parameter + 1) + (sum >> parameter)))
+        .min_by_key(|&(_, bits)| bits)
+        .expect("there is at least one parameter")
+}
+
+/// Map signed residuals to unsigned ones, so that small negative residuals stay small: 0, -1, 1, -2, 2, ... become 0, 1, 2, 3, 4, ...
+const fn zigzag(value: i32) -> u32 {
+    (value.wrapping_shl(1) ^ value >> 31).cast_unsigned()
+}
+
+/// Compute the residual of `samples` with a predictor that has the given coefficients (most recent sample first), or [`None`] if a residual doesn't fit in an [`i32`], which FLAC
+/// requires.
+fn residual(samples: &[i32], coefficients: &[i64], shift: u32, residual: &mut Vec<u32>) -> Option<()> {
+    residual.clear();
+    for (index, &sample) in samples.iter().enumerate().skip(coefficients.len()) {
+        let prediction = coefficients
+            .iter()
+            .zip(samples[..index].iter().rev())
+            .map(|(coefficient, &sample)| coefficient * i64::from(sample))
+            .sum::<i64>()
+            >> shift;
+        residual.push(zigzag(i32::try_from(i64::from(sample) - prediction).ok()?));
+    }
+    Some(())
+}
+
+/// A subframe, ready to be written.
+#[derive(Debug, Clone)]
+pub(super) struct Subframe {
+    predictor: Predictor,
+    /// The number of zero bits at the bottom of every sample, which aren't stored.
+    wasted_bits: u32,
+    residual: Vec<u32>,
+    rice: Rice,
+    /// The size of the subframe, in bits.
+    pub(super) bits: u64,
+}
+
+impl Subframe {
+    /// Find the smallest encoding of a channel's samples, each of which has `bits_per_sample` bits.
+    pub(super) fn encode(samples: &[i32], bits_per_sample: u32, options: &FlacOptions) -> Self {
+        let constant = Self {
+            predictor: Predictor::Constant,
+            wasted_bits: 0,
+            residual: Vec::new(),
+            rice: Rice::default(),
+            bits: 8 + u64::from(bits_per_sample),
+        };
+        let Some(&first) = samples.first() else {
+            return constant;
+        };
+        if samples.iter().all(|&sample| sample == first) {
+            return constant;
+        }
+        // Samples that were padded with zero bits (e.g. 20-bit samples in a 24-bit file) don't need to store the padding.
+        let wasted_bits = samples.iter().fold(0, |bits, sample| bits | sample).trailing_zeros();
+        let shifted: Vec<i32> = samples.iter().map(|sample| sample >> wasted_bits).collect();
+        let bits_per_sample = bits_per_sample - wasted_bits;
+        let header_bits = 8 + u64::from(wasted_bits);
+        let warm_up_bits = |order: usize| header_bits + order as u64 * u64::from(bits_per_sample);
+        let block_size = samples.len();
+
+        let mut best = Self {
+            predictor: Predictor::Verbatim,
+            wasted_bits,
+            residual: Vec::new(),
+            rice: Rice::default(),
+            bits: warm_up_bits(block_size),
+        };
+        let mut buffer = Vec::with_capacity(block_size);
+        let mut try_predictor = |predictor: Predictor, coefficients: &[i64], shift: u32, extra_bits: u64, best: &mut Self| {
+            let order = coefficients.len();
+            if residual(&shifted, coefficients, shift, &mut buffer).is_none() {
+                return;
+            }
+            let (rice, rice_bits) = Rice::choose(&buffer, block_size, order, options.max_partition_order.min(15).into());
+            let bits = warm_up_bits(order) + extra_bits + rice_bits;
+            if bits < best.bits {
+                best.predictor = predictor;
+                best.rice = rice;
+                best.bits = bits;
+                std::mem::swap(&mut best.residual, &mut buffer);
+            }
+        };
+        for (order, coefficients) in FIXED_PREDICTORS.iter().enumerate().take(block_size) {
+            try_predictor(Predictor::Fixed(order), coefficients, 0, 0, &mut best);
+        }
+        let max_lpc_order = usize::from(options.max_lpc_order.min(32)).min(block_size - 1);
+        if max_lpc_order > 0 {
+            let precision = lpc_precision(block_size);
+            for coefficients in lpc_coefficients(&shifted, max_lpc_order) {
+                let Some((quantized, shift)) = quantize(&coefficients, precision) else {
+                    continue;
+                };
+                let wide: Vec<i64> = quantized.iter().copied().map(i64::from).collect();
+                let extra_bits = 4 + 5 + wide.len() as u64 * u64::from(precision);
+                let predictor = Predictor::Lpc {
+                    coefficients: quantized,
+                    precision,
+                    shift,
+                };
+                try_predictor(predictor, &wide, shift, extra_bits, &mut best);
+            }
+        }
+        best
+    }
+
+    /// Write the subframe for `samples`, which must be the samples that it was encoded from.
+    pub(super) fn write(&self, writer: &mut BitWriter, samples: &[i32], bits_per_sample: u32) {
+        let order = self.predictor.order();
+        let kind = match &self.predictor {
+            Predictor::Constant => 0,
+            Predictor::Verbatim => 1,
+            Predictor::Fixed(order) => 0b00_1000 | *order as u64,
+            Predictor::Lpc { coefficients, .. } => 0b10_0000 | (coefficients.len() as u64 - 1),
+        };
+        writer.write(kind, 7);
+        if self.wasted_bits > 0 {
+            writer.write(1, 1);
+            writer.write_unary(u64::from(self.wasted_bits - 1));
+        } else {
+            writer.write(0, 1);
+        }
+        if self.predictor == Predictor::Constant {
+            writer.write_signed(samples[0].into(), bits_per_sample);
+            return;
+        }
+        let bits_per_sample = bits_per_sample - self.wasted_bits;
+        let warm_up = if self.predictor == Predictor::Verbatim { samples.len() } else { order };
+        for &sample in &samples[..warm_up] {
+            writer.write_signed((sample >> self.wasted_bits).into(), bits_per_sample);
+        }
+        if let Predictor::Lpc { coefficients, precision, shift } = &self.predictor {
+            writer.write(u64::from(precision - 1), 4);
+            writer.write(u64::from(*shift), 5);
+            for &coefficient in coefficients {
+                writer.write_signed(coefficient.into(), *precision);
+            }
+        }
+        if self.predictor != Predictor::Verbatim {
+            self.rice.write(writer, &self.residual, samples.len(), order);
+        }
+    }
+}
+
+/// The precision of quantized LPC coefficients, which libFLAC picks from the block size in the same way.
+const fn lpc_precision(block_size: usize) -> u32 {
+    match block_size {
+        ..=192 => 7,
+        193..=384 => 8,
+        385..=576 => 9,
+        577..=1152 => 10,
+        1153..=2304 => 11,
+        2305..=4608 => 12,
+        _ => 13,
+    }
+}
+
+/// Return the coefficients of the best linear predictor of each order from 1 to `max_order` (most recent sample first), from the autocorrelation of the samples with a Tukey
+/// window applied.
+#[allow(clippy::cast_precision_loss, reason = "samples have at most 25 bits, and the window is an approximation anyway")]
+fn lpc_coefficients(samples: &[i32], max_order: usize) -> Vec<Vec<f64>> {
+    // A Tukey window that tapers the first and last quarter of the block, like libFLAC's default.
+    let taper = (samples.len() / 4).max(1);
+    let windowed: Vec<f64> = samples
+        .iter()
+        .enumerate()
+        .map(|(index, &sample)| {
+            let distance = index.min(samples.len() - 1 - index);
+            let weight = if distance < taper {
+                (-0.5_f64).mul_add((PI * distance as f64 / taper as f64).cos(), 0.5)
+            } else {
+                1.
+            };
+            f64::from(sample) * weight
+        })
+        .collect();
+    let autocorrelation: Vec<f64> = (0..=max_order).map(|lag| windowed[lag..].iter().zip(&windowed).map(|(a, b)| a * b).sum()).collect();
+    if autocorrelation[0] <= 0. {
+        return Vec::new();
+    }
+    // Levinson-Durbin recursion, solving for each order in turn.
+    let mut error = autocorrelation[0];
+    let mut lpc = vec![0.; max_order];
+    let mut orders = Vec::with_capacity(max_order);
+    for order in 0..max_order {
+        let reflection = -(autocorrelation[order + 1] + (0..order).map(|index| lpc[index] * autocorrelation[order - index]).sum::<f64>()) / error;
+        lpc[order] = reflection;
+        for index in 0..order / 2 {
+            let tmp = lpc[index];
+            lpc[index] += reflection * lpc[order - 1 - index];
+            lpc[order - 1 - index] += reflection * tmp;
+        }
+        if order % 2 == 1 {
+            lpc[order / 2] += lpc[order / 2] * reflection;
+        }
+        error *= reflection.mul_add(-reflection, 1.);
+        orders.push(lpc[..=order].iter().map(|coefficient| -coefficient).collect());
+        if error <= 0. {
+            break;
+        }
+    }
+    orders
+}
+
+/// Quantize LPC coefficients to integers with `precision` bits, returning them and the shift that scales them back down, or [`None`] if they are too big to quantize.
+#[allow(clippy::cast_possible_truncation, reason = "the coefficients are clamped to the range of the precision")]
+fn quantize(coefficients: &[f64], precision: u32) -> Option<(Vec<i32>, u32)> {
+    let max = coefficients.iter().fold(0., |max: f64, coefficient| max.max(coefficient.abs()));
+    if max <= 0. || !max.is_finite() {
+        return None;
+    }
+    // Scale the largest coefficient up to just fit in the precision.
+    let shift = i32::try_from(precision).ok()? - 1 - (max.log2().floor() as i32 + 1);
+    let shift = u32::try_from(shift.min(15)).ok()?;
+    let limit = f64::from(1 << (precision - 1));
+    // Carry the rounding error of each coefficient over to the next, so that the errors don't add up.
+    let mut error = 0.;
+    let quantized = coefficients
+        .iter()
+        .map(|coefficient| {
+            error += coefficient * f64::from(1 << shift);
+            let quantized = error.round().clamp(-limit, limit - 1.);
+            error -= quantized;
+            quantized as i32
+        })
+        .collect();
+    Some((quantized, shift))
+}
+
+/// How the channels of a frame are stored, from the channel assignment in its header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChannelAssignment {
+    Independent,
+    LeftSide,
+    SideRight,
+    MidSide,
+}
+
+/// The sample rate code of a frame header, for sample rates that have one (the rest are read from the `STREAMINFO` block).
+const fn sample_rate_code(sample_rate: u32) -> u8 {
+    match sample_rate {
+        88200 => 1,
+        176_400 => 2,
+        192_000 => 3,
+        8000 => 4,
+        16000 => 5,
+        22050 => 6,
+        24000 => 7,
+        32000 => 8,
+        44100 => 9,
+        48000 => 10,
+        96000 => 11,
+        _ => 0,
+    }
+}
+
+/// The sample size code of a frame header.
+const fn sample_size_code(bits_per_sample: u32) -> u8 {
+    match bits_per_sample {
+        8 => 1,
+        12 => 2,
+        16 => 4,
+        20 => 5,
+        24 => 6,
+        32 => 7,
+        _ => 0,
+    }
+}
+
+/// The block size code of a frame header, which is followed by the block size minus one in 8 or 16 bits if it isn't one of the common sizes.
+const fn block_size_code(block_size: usize) -> u8 {
+    match block_size {
+        192 => 1,
+        576 => 2,
+        1152 => 3,
+        2304 => 4,
+        4608 => 5,
+        256 => 8,
+        512 => 9,
+        1024 => 10,
+        2048 => 11,
+        4096 => 12,
+        8192 => 13,
+        16384 => 14,
+        32768 => 15,
+        _ if block_size <= 256 => 6,
+        _ => 7,
+    }
+}
+
+/// Write the frame number in the variable-length form that UTF-8 uses for code points.
+fn push_frame_number(header: &mut Vec<u8>, frame_number: u32) {
+    let bytes = frame_number.to_be_bytes();
+    if frame_number < 0x80 {
+        header.push(bytes[3]);
+        return;
+    }
+    let len = match frame_number {
+        ..0x800 => 2,
+        0x800..0x1_0000 => 3,
+        0x1_0000..0x20_0000 => 4,
+        0x20_0000..0x400_0000 => 5,
+        _ => 6,
+    };
+    // The first byte has as many leading ones as there are bytes, followed by the top bits of the value, and the rest of the bytes hold 6 bits each after `10`.
+    let first = (0xFF_u32 << (8 - len) | frame_number >> (6 * (len - 1))).to_le_bytes()[0];
+    header.push(first);
+    for byte in (0..len - 1).rev() {
+        header.push(0x80 | (frame_number >> (6 * byte) & 0x3F).to_le_bytes()[0]);
+    }
+}
+
+/// Encode a frame from the samples of each channel, which must all have the same length, and append it to `out`.
+pub(super) fn encode(out: &mut Vec<u8>, channels: &[&[i32]], bits_per_sample: u32, sample_rate: u32, frame_number: u32, options: &FlacOptions) {
+    let block_size = channels[0].len();
+    let (assignment, samples, subframes) = if let [left, right] = channels {
+        // Try every way of storing the channels, because which one is smallest depends on how similar they are.
+        let side: Vec<i32> = left.iter().zip(*right).map(|(left, right)| left - right).collect();
+        let mid: Vec<i32> = left.iter().zip(*right).map(|(left, right)| (left + right) >> 1).collect();
+        let left_subframe = Subframe::encode(left, bits_per_sample, options);
+        let right_subframe = Subframe::encode(right, bits_per_sample, options);
+        let side_subframe = Subframe::encode(&side, bits_per_sample + 1, options);
+        let mid_subframe = Subframe::encode(&mid, bits_per_sample, options);
+        let independent = left_subframe.bits + right_subframe.bits;
+        let left_side = left_subframe.bits + side_subframe.bits;
+        let side_right = side_subframe.bits + right_subframe.bits;
+        let mid_side = mid_subframe.bits + side_subframe.bits;
+        let smallest = independent.min(left_side).min(side_right).min(mid_side);
+        if smallest == independent {
+            (ChannelAssignment::Independent, vec![left.to_vec(), right.to_vec()], vec![left_subframe, right_subframe])
+        } else if smallest == left_side {
+            (ChannelAssignment::LeftSide, vec![left.to_vec(), side], vec![left_subframe, side_subframe])
+        } else if smallest == side_right {
+            (ChannelAssignment::SideRight, vec![side, right.to_vec()], vec![side_subframe, right_subframe])
+        } else {
+            (ChannelAssignment::MidSide, vec![mid, side], vec![mid_subframe, side_subframe])
+        }
+    } else {
+        (
+            ChannelAssignment::Independent,
+            channels.iter().map(|samples| samples.to_vec()).collect(),
+            channels.iter().map(|samples| Subframe::encode(samples, bits_per_sample, options)).collect(),
+        )
+    };
+
+    let start = out.len();
+    let block_size_code = block_size_code(block_size);
+    let channel_code = match assignment {
+        ChannelAssignment::Independent => channels.len() - 1,
+        ChannelAssignment::LeftSide => 8,
+        ChannelAssignment::SideRight => 9,
+        ChannelAssignment::MidSide => 10,
+    };
+    // The sync code, followed by a reserved bit and the fixed block size strategy.
+    out.extend_from_slice(&[0xFF, 0xF8]);
+    out.push(block_size_code << 4 | sample_rate_code(sample_rate));
+    out.push((channel_code << 4).to_le_bytes()[0] | sample_size_code(bits_per_sample) << 1);
+    push_frame_number(out, frame_number);
+    let [.., high, low] = (block_size - 1).to_be_bytes();
+    match block_size_code {
+        6 => out.push(low),
+        7 => out.extend_from_slice(&[high, low]),
+        _ => {}
+    }
+    out.push(crc8(&out[start..]));
+
+    let mut writer = BitWriter::default();
+    for (index, (subframe, samples)) in subframes.iter().zip(&samples).enumerate() {
+        // The side channel has an extra bit, because it is the difference of two channels.
+        let side = matches!((assignment, index), (ChannelAssignment::LeftSide | ChannelAssignment::MidSide, 1) | (ChannelAssignment::SideRight, 0));
+        subframe.write(&mut writer, samples, bits_per_sample + u32::from(side));
+    }
+    writer.align();
+    out.extend_from_slice(writer.bytes());
+    let crc = crc16(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
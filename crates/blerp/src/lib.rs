@@ -1,14 +1,21 @@
 #![warn(clippy::nursery, clippy::pedantic, clippy::undocumented_unsafe_blocks)]
-use std::{iter::Sum, ops::Div};
+use std::{
+    iter::Sum,
+    ops::{Add, Div, Mul, Sub},
+};
 
 use cpal::{FromSample, Sample};
 use itertools::Itertools;
 
+pub mod aiff;
+pub mod buffer;
 pub mod device;
+pub mod flac;
 pub mod processing;
+pub mod raw;
 pub mod wavefile;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(transparent)]
 pub struct Block<T: Sample, const N: usize>([T; N]);
 
@@ -24,6 +31,47 @@ impl<T: Sample, const N: usize> From<[T; N]> for Block<T, N> {
     }
 }
 
+impl<T: Sample, const N: usize> From<Block<T, N>> for [T; N] {
+    fn from(Block(value): Block<T, N>) -> Self {
+        value
+    }
+}
+
+impl<T: Sample + FromSample<f64>, const N: usize> Add for Block<T, N>
+where
+    f64: FromSample<T>,
+{
+    type Output = Self;
+
+    fn add(self, Self(rhs): Self) -> Self::Output {
+        let mut rhs = rhs.into_iter();
+        Self(self.0.map(|sample| T::from_sample(f64::from_sample(sample) + rhs.next().map_or(0., f64::from_sample))))
+    }
+}
+
+impl<T: Sample + FromSample<f64>, const N: usize> Sub for Block<T, N>
+where
+    f64: FromSample<T>,
+{
+    type Output = Self;
+
+    fn sub(self, Self(rhs): Self) -> Self::Output {
+        let mut rhs = rhs.into_iter();
+        Self(self.0.map(|sample| T::from_sample(f64::from_sample(sample) - rhs.next().map_or(0., f64::from_sample))))
+    }
+}
+
+impl<T: Sample + FromSample<f64>, const N: usize> Mul<T> for Block<T, N>
+where
+    f64: FromSample<T>,
+{
+    type Output = Self;
+
+    fn mul(self, rhs: T) -> Self::Output {
+        Self(self.0.map(|sample| T::from_sample(f64::from_sample(sample) * f64::from_sample(rhs))))
+    }
+}
+
 impl<T: Sample + FromSample<f64>, const N: usize> Div<T> for Block<T, N>
 where
     f64: FromSample<T>,
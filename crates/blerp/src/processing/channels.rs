@@ -0,0 +1,153 @@
+use std::f64::consts::{FRAC_1_SQRT_2, FRAC_PI_2};
+
+use cpal::{FromSample, Sample};
+
+use crate::Block;
+
+/// How a mono signal's level is split between the left and right channels as it is panned, which mostly matters for how loud it is in the centre.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PanLaw {
+    /// Constant power (sine/cosine gains), which is 3 dB down in the centre so that the signal sounds equally loud wherever it is panned.
+    #[default]
+    Minus3Db,
+    /// A compromise between the -3 dB and -6 dB laws, which is 4.5 dB down in the centre.
+    Minus4_5Db,
+    /// Constant amplitude (squared sine/cosine gains), which is 6 dB down in the centre so that the channels sum to the original level when folded to mono.
+    Minus6Db,
+    /// Gains that change linearly with the pan position. Like [`PanLaw::Minus6Db`], this is 6 dB down in the centre, but it moves away from the centre more quickly.
+    Linear,
+}
+
+impl PanLaw {
+    /// Return the gains of the left and right channels for a pan position from -1 (hard left) to 1 (hard right), which is clamped to that range.
+    #[must_use]
+    pub fn gains(self, pan: f64) -> [f64; 2] {
+        let position = f64::midpoint(pan.clamp(-1., 1.), 1.);
+        // Both channels use the same curve, mirrored, so that they are exactly equal in the centre.
+        [self.gain(1. - position), self.gain(position)]
+    }
+
+    /// Return the gain of the right channel for a position from 0 (hard left) to 1 (hard right).
+    fn gain(self, position: f64) -> f64 {
+        let gain = (position * FRAC_PI_2).sin();
+        match self {
+            Self::Minus3Db => gain,
+            Self::Minus4_5Db => gain.powf(1.5),
+            Self::Minus6Db => gain * gain,
+            Self::Linear => position,
+        }
+    }
+
+    /// Return the gain of each channel when panned to the centre.
+    #[must_use]
+    pub fn centre_gain(self) -> f64 {
+        self.gains(0.)[0]
+    }
+}
+
+/// A mono-to-stereo panner with a fixed position, which works out the gains of the channels once rather than for every sample.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Panner {
+    gains: [f64; 2],
+}
+
+impl Panner {
+    /// Create a new [`Panner`] for a pan position from -1 (hard left) to 1 (hard right), with the given [`PanLaw`].
+    #[must_use]
+    pub fn new(pan: f64, law: PanLaw) -> Self {
+        Self { gains: law.gains(pan) }
+    }
+
+    /// Place a mono sample on a stereo track.
+    pub fn pan<T: Sample + FromSample<f64>>(&self, block: impl Into<Block<T, 1>>) -> Block<T, 2>
+    where
+        f64: FromSample<T>,
+    {
+        let Block([sample]) = block.into();
+        let sample = f64::from_sample(sample);
+        Block(self.gains.map(|gain| T::from_sample(sample * gain)))
+    }
+}
+
+/// Fold a stereo block down to mono by summing the channels, scaled so that a mono signal panned to the centre with the same [`PanLaw`] comes back at its original level.
+///
+/// With [`PanLaw::Minus6Db`] or [`PanLaw::Linear`] the channels are simply added together, and with [`PanLaw::Minus3Db`] they are added and then lowered by 3 dB.
+pub fn fold_to_mono<T: Sample + FromSample<f64>>(block: impl Into<Block<T, 2>>, law: PanLaw) -> Block<T, 1>
+where
+    f64: FromSample<T>,
+{
+    let Block([left, right]) = block.into();
+    Block([T::from_sample((f64::from_sample(left) + f64::from_sample(right)) / (2. * law.centre_gain()))])
+}
+
+/// Convert a left/right block into mid (the average of the channels) and side (half of their difference), in that order.
+pub fn mid_side_encode<T: Sample + FromSample<f64>>(block: impl Into<Block<T, 2>>) -> Block<T, 2>
+where
+    f64: FromSample<T>,
+{
+    let Block([left, right]) = block.into();
+    let (left, right) = (f64::from_sample(left), f64::from_sample(right));
+    Block([T::from_sample(f64::midpoint(left, right)), T::from_sample((left - right) / 2.)])
+}
+
+/// Convert a mid/side block from [`mid_side_encode`] back into left and right.
+pub fn mid_side_decode<T: Sample + FromSample<f64>>(block: impl Into<Block<T, 2>>) -> Block<T, 2>
+where
+    f64: FromSample<T>,
+{
+    let Block([mid, side]) = block.into();
+    let (mid, side) = (f64::from_sample(mid), f64::from_sample(side));
+    Block([T::from_sample(mid + side), T::from_sample(mid - side)])
+}
+
+/// The coefficients for downmixing 5.1 surround to stereo.
+///
+/// Each of the left and right outputs is the sum of its front channel, the centre, its surround channel and the LFE channel, scaled by these.
+/// The input channels are in WAV order: front left, front right, centre, LFE, surround left, surround right.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Downmix {
+    pub front: f64,
+    pub centre: f64,
+    pub surround: f64,
+    pub lfe: f64,
+}
+
+impl Default for Downmix {
+    /// The ITU-R BS.775 coefficients, which add the centre and surround channels at -3 dB and leave out the LFE channel.
+    fn default() -> Self {
+        Self {
+            front: 1.,
+            centre: FRAC_1_SQRT_2,
+            surround: FRAC_1_SQRT_2,
+            lfe: 0.,
+        }
+    }
+}
+
+impl Downmix {
+    /// Return the coefficients scaled so that they add up to 1, which makes the downmix quieter but means that it can't clip.
+    #[must_use]
+    pub fn normalized(self) -> Self {
+        let scale = 1. / (self.front + self.centre + self.surround + self.lfe);
+        Self {
+            front: self.front * scale,
+            centre: self.centre * scale,
+            surround: self.surround * scale,
+            lfe: self.lfe * scale,
+        }
+    }
+
+    /// Downmix a 5.1 block to stereo.
+    pub fn apply<T: Sample + FromSample<f64>>(&self, block: impl Into<Block<T, 6>>) -> Block<T, 2>
+    where
+        f64: FromSample<T>,
+    {
+        let Block(samples) = block.into();
+        let [left, right, centre, lfe, surround_left, surround_right] = samples.map(f64::from_sample);
+        let shared = self.centre.mul_add(centre, self.lfe * lfe);
+        Block([
+            T::from_sample(self.front.mul_add(left, self.surround.mul_add(surround_left, shared))),
+            T::from_sample(self.front.mul_add(right, self.surround.mul_add(surround_right, shared))),
+        ])
+    }
+}
@@ -0,0 +1,88 @@
+use cpal::{FromSample, Sample};
+
+use super::random::Rng;
+use crate::Block;
+
+/// How to dither samples when reducing their bit depth.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dither {
+    /// Round to the nearest step without any dither, which leaves distortion that follows the signal.
+    None,
+    /// Add rectangular (uniform) noise of ±½ LSB, which removes the distortion but makes the noise level follow the signal.
+    Rectangular,
+    /// Add triangular (TPDF) noise of ±1 LSB, which removes both the distortion and the noise modulation.
+    Triangular,
+    /// Add triangular noise and shape the total quantization noise towards high frequencies, where hearing is least sensitive.
+    NoiseShaped,
+}
+
+/// The error feedback filter used for noise shaping (Wannamaker's 3-tap F-weighted filter, designed for 44.1 kHz).
+const NOISE_SHAPING: [f64; 3] = [1.623, -0.982, 0.109];
+
+/// A conversion stage that reduces samples to a lower bit depth with dither, e.g. from a floating point mix to 16 or 24-bit PCM for export.
+///
+/// The dither noise comes from an [`Rng`] seeded in [`Quantizer::new`], so the same input and seed always produce the same output.
+#[derive(Debug, Clone)]
+pub struct Quantizer<const N: usize> {
+    dither: Dither,
+    /// The number of steps between zero and full scale, i.e. `2^(bits - 1)`.
+    steps: f64,
+    rng: Rng,
+    /// The last few quantization errors of each channel (most recent first), for noise shaping.
+    errors: [[f64; NOISE_SHAPING.len()]; N],
+}
+
+impl<const N: usize> Quantizer<N> {
+    /// Create a new [`Quantizer`] that quantizes to `bits` bits per sample.
+    /// # Panics
+    /// Panics if `bits` is zero or more than 32.
+    #[must_use]
+    pub fn new(bits: u32, dither: Dither, seed: u64) -> Self {
+        assert!((1..=32).contains(&bits), "bit depth must be between 1 and 32");
+        Self {
+            dither,
+            steps: f64::from(1_u32 << (bits - 1)),
+            rng: Rng::new(seed),
+            errors: [[0.; NOISE_SHAPING.len()]; N],
+        }
+    }
+
+    /// Quantize a block of samples, returning it as `T`.
+    ///
+    /// `T` should have at least as many bits as the [`Quantizer`], so that converting the quantized sample into it is exact.
+    pub fn quantize<S: Sample, T: Sample + FromSample<f64>>(&mut self, block: impl Into<Block<S, N>>) -> Block<T, N>
+    where
+        f64: FromSample<S>,
+    {
+        let Block(samples) = block.into();
+        let mut channel = 0;
+        Block(samples.map(|sample| {
+            let errors = &mut self.errors[channel];
+            channel += 1;
+            let sample = f64::from_sample(sample) * self.steps;
+            let shaped = if self.dither == Dither::NoiseShaped {
+                sample - NOISE_SHAPING.iter().zip(&*errors).map(|(coefficient, error)| coefficient * error).sum::<f64>()
+            } else {
+                sample
+            };
+            let noise = match self.dither {
+                Dither::None => 0.,
+                Dither::Rectangular => self.rng.bipolar() / 2.,
+                Dither::Triangular | Dither::NoiseShaped => self.rng.uniform() - self.rng.uniform(),
+            };
+            let quantized = (shaped + noise).round().clamp(-self.steps, self.steps - 1.);
+            // Limit the error that is fed back, so that clipping can't make the noise shaping filter unstable.
+            errors.rotate_right(1);
+            errors[0] = (quantized - shaped).clamp(-2., 2.);
+            T::from_sample(quantized / self.steps)
+        }))
+    }
+
+    /// Return an iterator that quantizes every block from `blocks`, as with [`Quantizer::quantize`].
+    pub fn quantize_all<'a, S: Sample + 'a, T: Sample + FromSample<f64> + 'a, B: Into<Block<S, N>>>(&'a mut self, blocks: impl IntoIterator<Item = B> + 'a) -> impl Iterator<Item = Block<T, N>> + 'a
+    where
+        f64: FromSample<S>,
+    {
+        blocks.into_iter().map(|block| self.quantize(block))
+    }
+}
@@ -0,0 +1,248 @@
+use cpal::{FromSample, Sample};
+
+use crate::Block;
+
+/// How sharply [`Curve::Exponential`] bends. The segment covers about 99% of its distance by the time it's 90% through.
+const CURVATURE: f64 = 5.;
+
+/// How a [`Segment`] moves from where it starts to its target.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Curve {
+    /// A straight line.
+    #[default]
+    Linear,
+    /// Quickly at first, then slowing down as it gets close to the target, like a capacitor charging in an analogue synthesizer. This sounds more even than a straight line,
+    /// since hearing is logarithmic.
+    Exponential,
+}
+
+impl Curve {
+    /// Return how far through its distance a segment is, given how far through its time it is, both from 0 to 1.
+    fn shape(self, progress: f64) -> f64 {
+        match self {
+            Self::Linear => progress,
+            Self::Exponential => (-CURVATURE * progress).exp_m1() / (-CURVATURE).exp_m1(),
+        }
+    }
+}
+
+/// One stage of an [`Envelope`], which moves from the level that the envelope is at to `target` over `time` seconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Segment {
+    pub target: f64,
+    pub time: f64,
+    pub curve: Curve,
+}
+
+impl Segment {
+    /// Create a new [`Segment`].
+    #[must_use]
+    pub const fn new(target: f64, time: f64, curve: Curve) -> Self {
+        Self { target, time, curve }
+    }
+}
+
+/// What happens when a note starts while the previous one is still held.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Mode {
+    /// Start the envelope again, from the level it is at so that it doesn't click.
+    #[default]
+    Retrigger,
+    /// Carry on as if the first note were still playing, for smoothly connected notes. Notes that start after the previous one is released still start the envelope again.
+    Legato,
+}
+
+/// A multi-segment envelope, which shapes a level (usually gain) over time.
+///
+/// A note start plays the segments in order, stopping at the end of the sustain segment (if there is one) for as long as the note is held. When the note is released, the envelope
+/// jumps to the segments after the sustain segment, from whatever level it is at. Envelopes without a sustain segment always play all the way through, e.g. for drums.
+///
+/// The envelope starts at 0, and stays at the target of its last segment when it is finished.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Envelope {
+    segments: Vec<Segment>,
+    sustain: Option<usize>,
+    mode: Mode,
+    sample_rate: u32,
+    /// The segment being played, or [`None`] when the envelope is finished.
+    segment: Option<usize>,
+    /// The number of samples into the segment being played.
+    position: u64,
+    /// The level that the segment being played started from.
+    start: f64,
+    level: f64,
+    held: bool,
+}
+
+impl Envelope {
+    /// Create a new [`Envelope`], which holds the level at the end of segment number `sustain` while a note is held.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sustain` is not the index of one of the segments.
+    #[must_use]
+    pub fn new(segments: Vec<Segment>, sustain: Option<usize>, sample_rate: u32) -> Self {
+        assert!(sustain.is_none_or(|sustain| sustain < segments.len()), "the sustain segment must be one of the segments");
+        Self {
+            segments,
+            sustain,
+            mode: Mode::default(),
+            sample_rate,
+            segment: None,
+            position: 0,
+            start: 0.,
+            level: 0.,
+            held: false,
+        }
+    }
+
+    /// Change what happens when a note starts while the previous one is still held.
+    pub const fn set_mode(&mut self, mode: Mode) {
+        self.mode = mode;
+    }
+
+    /// Start a note, from the first segment.
+    pub const fn note_on(&mut self) {
+        let legato = matches!(self.mode, Mode::Legato) && self.held && self.segment.is_some();
+        if !legato {
+            self.jump(0);
+        }
+        self.held = true;
+    }
+
+    /// Release the note, moving on to the segments after the sustain segment. This does nothing for envelopes without one, which play all the way through anyway.
+    pub const fn note_off(&mut self) {
+        self.held = false;
+        if let (Some(sustain), Some(segment)) = (self.sustain, self.segment) {
+            if segment <= sustain {
+                self.jump(sustain + 1);
+            }
+        }
+    }
+
+    /// Stop the envelope and go back to 0, e.g. when a voice is reused for another note.
+    pub const fn reset(&mut self) {
+        self.segment = None;
+        self.level = 0.;
+        self.held = false;
+    }
+
+    /// Return whether the envelope is still moving or sustaining, so a voice using it is still making sound.
+    #[must_use]
+    pub const fn is_active(&self) -> bool {
+        self.segment.is_some()
+    }
+
+    /// Return the level of the last sample.
+    #[must_use]
+    pub const fn level(&self) -> f64 {
+        self.level
+    }
+
+    /// Start playing a segment from the current level, or finish if there is no such segment.
+    const fn jump(&mut self, segment: usize) {
+        self.segment = if segment < self.segments.len() { Some(segment) } else { None };
+        self.position = 0;
+        self.start = self.level;
+    }
+
+    /// Return the level of the next sample, and move on to the one after it.
+    pub fn next_value(&mut self) -> f64 {
+        while let Some(index) = self.segment {
+            let segment = self.segments[index];
+            #[allow(
+                clippy::cast_possible_truncation,
+                clippy::cast_sign_loss,
+                reason = "segments are far shorter than 2^64 samples, and negative times are treated as 0"
+            )]
+            let length = (segment.time * f64::from(self.sample_rate)).round().max(0.) as u64;
+            if self.position < length {
+                self.position += 1;
+                #[allow(clippy::cast_precision_loss, reason = "segments are far shorter than 2^52 samples")]
+                let progress = self.position as f64 / length as f64;
+                // Land exactly on the target, whatever the rounding.
+                self.level = if self.position == length {
+                    segment.target
+                } else {
+                    (segment.target - self.start).mul_add(segment.curve.shape(progress), self.start)
+                };
+                return self.level;
+            }
+            self.level = segment.target;
+            if self.held && self.sustain == Some(index) {
+                return self.level;
+            }
+            self.jump(index + 1);
+        }
+        self.level
+    }
+
+    /// Use the envelope as gain automation, multiplying each block by the next level.
+    pub fn apply<T: Sample + FromSample<f64>, const N: usize>(&mut self, blocks: &mut [Block<T, N>])
+    where
+        f64: FromSample<T>,
+    {
+        for Block(block) in blocks {
+            let level = self.next_value();
+            *block = block.map(|sample| T::from_sample(f64::from_sample(sample) * level));
+        }
+    }
+
+    /// Shape the amplitude of a function over time from [`generation`](super::generation), starting a note straight away and releasing it `note_off` seconds in.
+    ///
+    /// The envelope moves on by one sample every time the returned function is called, so it should be called once for every sample.
+    pub fn modulate<T: Sample + FromSample<f64>, const N: usize>(mut self, mut wave: impl FnMut(f64) -> Block<T, N>, note_off: f64) -> impl FnMut(f64) -> Block<T, N>
+    where
+        f64: FromSample<T>,
+    {
+        self.note_on();
+        move |time| {
+            if self.held && time >= note_off {
+                self.note_off();
+            }
+            let mut block = [wave(time)];
+            self.apply(&mut block);
+            let [block] = block;
+            block
+        }
+    }
+}
+
+/// The settings for a classic attack, decay, sustain and release envelope.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Adsr {
+    /// The time in seconds to rise from where the envelope is to 1.
+    pub attack: f64,
+    /// The time in seconds to fall from 1 to the sustain level.
+    pub decay: f64,
+    /// The level held while the note is held.
+    pub sustain: f64,
+    /// The time in seconds to fall to 0 after the note is released.
+    pub release: f64,
+    pub curve: Curve,
+}
+
+impl Adsr {
+    /// Create a new [`Adsr`].
+    #[must_use]
+    pub const fn new(attack: f64, decay: f64, sustain: f64, release: f64, curve: Curve) -> Self {
+        Self {
+            attack,
+            decay,
+            sustain,
+            release,
+            curve,
+        }
+    }
+
+    /// Return an [`Envelope`] with these settings.
+    #[must_use]
+    pub fn envelope(self, sample_rate: u32) -> Envelope {
+        let segments = vec![
+            Segment::new(1., self.attack, self.curve),
+            Segment::new(self.sustain, self.decay, self.curve),
+            Segment::new(0., self.release, self.curve),
+        ];
+        Envelope::new(segments, Some(1), sample_rate)
+    }
+}
@@ -1 +1,20 @@
+use std::io::Write;
 
+use crate::{
+    wavefile::{SampleExt, WaveFile, WaveFileWriteError},
+    Block,
+};
+
+/// Render `duration` seconds of a function over time, such as those in [`generation`](super::generation), and write it to `writer` as a WAV file.
+///
+/// # Errors
+///
+/// Returns [`WaveFileWriteError::InvalidChannels`] if there are no channels or more than 65535 of them, or any error from [`WaveFile::write`].
+pub fn write_wave<T: SampleExt, const N: usize>(mut wave: impl FnMut(f64) -> Block<T, N>, duration: f64, sample_rate: u32, writer: &mut impl Write) -> Result<(), WaveFileWriteError> {
+    let rate = f64::from(sample_rate);
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "durations are positive and far shorter than 2^32 samples")]
+    let len = (duration * rate).round().max(0.) as u32;
+    WaveFile::from_samples((0..len).map(|sample| wave(f64::from(sample) / rate)), sample_rate)
+        .ok_or(WaveFileWriteError::InvalidChannels)?
+        .write(writer)
+}
@@ -0,0 +1,230 @@
+use std::f64::consts::TAU;
+
+use cpal::{FromSample, Sample};
+
+use super::{envelope::Envelope, generation::Harmonic};
+use crate::Block;
+
+/// A sine wave oscillator whose phase can be modulated by other operators and by itself, with its own envelope.
+///
+/// An operator's output is its level times its envelope times the sine wave. For a carrier, the level is the amplitude where 1 is full scale, and for a modulator, it is the
+/// modulation index: how far in radians it moves the phase of the operators it modulates.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Operator {
+    ratio: f64,
+    level: f64,
+    feedback: f64,
+    envelope: Envelope,
+    /// The position in the current cycle, from 0 (inclusive) to 1 (exclusive).
+    phase: f64,
+    /// The last two values of the sine wave, for feedback.
+    previous: [f64; 2],
+}
+
+impl Operator {
+    /// Create a new [`Operator`] at `ratio` times the voice's frequency, which should use the same sample rate as the voice.
+    #[must_use]
+    pub const fn new(ratio: f64, level: f64, envelope: Envelope) -> Self {
+        Self {
+            ratio,
+            level,
+            feedback: 0.,
+            envelope,
+            phase: 0.,
+            previous: [0.; 2],
+        }
+    }
+
+    /// Create an [`Operator`] that plays a harmonic of the voice's frequency, scaled as in [`harmonics`](super::generation::harmonics).
+    #[must_use]
+    pub fn from_harmonic<T: Sample>(harmonic: Harmonic<T>, envelope: Envelope) -> Self
+    where
+        f64: FromSample<T>,
+    {
+        #[allow(clippy::cast_precision_loss, reason = "harmonic indices are far below 2^52")]
+        let ratio = harmonic.index() as f64 + 1.;
+        Self::new(ratio, f64::from_sample(harmonic.amplitude()) / ratio, envelope)
+    }
+
+    /// Change the frequency, as a multiple of the voice's frequency.
+    pub const fn set_ratio(&mut self, ratio: f64) {
+        self.ratio = ratio;
+    }
+
+    /// Change the level, which is the amplitude for a carrier or the modulation index for a modulator.
+    pub const fn set_level(&mut self, level: f64) {
+        self.level = level;
+    }
+
+    /// Change how far in radians the operator modulates its own phase. Around 1.5 sounds like a sawtooth wave, and much more than that turns into noise.
+    ///
+    /// The feedback is the average of the last two samples, which keeps it from oscillating between them.
+    pub const fn set_feedback(&mut self, feedback: f64) {
+        self.feedback = feedback;
+    }
+
+    /// Return the operator's envelope, e.g. to change its mode.
+    pub const fn envelope_mut(&mut self) -> &mut Envelope {
+        &mut self.envelope
+    }
+
+    /// Return the next sample with the phase moved by `modulation` radians, and move on by `increment` cycles of the voice's frequency.
+    fn next_sample(&mut self, modulation: f64, increment: f64) -> f64 {
+        let feedback = self.feedback * f64::midpoint(self.previous[0], self.previous[1]);
+        let wave = TAU.mul_add(self.phase, modulation + feedback).sin();
+        self.previous = [wave, self.previous[0]];
+        self.phase = self.ratio.mul_add(increment, self.phase).rem_euclid(1.);
+        wave * self.level * self.envelope.next_value()
+    }
+}
+
+/// How the operators of an [`FmVoice`] are connected.
+///
+/// Operators are numbered from 0, and each can only modulate operators with lower numbers, so that they can be worked out from the highest number down. The carriers are the
+/// operators that are heard.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Algorithm {
+    /// The modulator and the operator it modulates, for every connection.
+    modulations: Vec<(usize, usize)>,
+    carriers: Vec<usize>,
+}
+
+impl Algorithm {
+    /// Create a new [`Algorithm`] from pairs of a modulator and the operator that it modulates, and the operators that are heard.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a modulator's number is not higher than the number of the operator it modulates.
+    #[must_use]
+    pub fn new(modulations: Vec<(usize, usize)>, carriers: Vec<usize>) -> Self {
+        assert!(
+            modulations.iter().all(|(modulator, target)| modulator > target),
+            "operators can only modulate operators with lower numbers"
+        );
+        Self { modulations, carriers }
+    }
+
+    /// Return an [`Algorithm`] where each operator modulates the one below it, and operator 0 is heard. This is the brightest arrangement.
+    #[must_use]
+    pub fn stack(operators: usize) -> Self {
+        Self::new((1..operators).map(|modulator| (modulator, modulator - 1)).collect(), vec![0])
+    }
+
+    /// Return an [`Algorithm`] where every operator is heard, with no modulation, for additive synthesis.
+    #[must_use]
+    pub fn parallel(operators: usize) -> Self {
+        Self::new(Vec::new(), (0..operators).collect())
+    }
+
+    /// Return an [`Algorithm`] where each odd operator modulates the even one below it, which is heard.
+    #[must_use]
+    pub fn pairs(operators: usize) -> Self {
+        Self::new((1..operators).step_by(2).map(|modulator| (modulator, modulator - 1)).collect(), (0..operators).step_by(2).collect())
+    }
+
+    /// Return an [`Algorithm`] where every other operator modulates operator 0, which is heard.
+    #[must_use]
+    pub fn branch(operators: usize) -> Self {
+        Self::new((1..operators).map(|modulator| (modulator, 0)).collect(), vec![0])
+    }
+
+    /// Return the number of operators that the algorithm needs.
+    fn operators(&self) -> usize {
+        self.modulations
+            .iter()
+            .map(|(modulator, _)| modulator + 1)
+            .chain(self.carriers.iter().map(|carrier| carrier + 1))
+            .max()
+            .unwrap_or_default()
+    }
+}
+
+/// A voice of a DX-style FM synthesizer, made of [`Operator`]s connected by an [`Algorithm`].
+///
+/// Like most FM synthesizers, this actually modulates phase rather than frequency, which sounds the same for sine waves but keeps the pitch steady however the operators are
+/// connected.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FmVoice {
+    operators: Vec<Operator>,
+    algorithm: Algorithm,
+    frequency: f64,
+    amplitude: f64,
+    sample_rate: u32,
+    /// The output of each operator for the current sample.
+    outputs: Vec<f64>,
+}
+
+impl FmVoice {
+    /// Create a new [`FmVoice`] that plays `frequency` hertz, which is silent until a note starts. The carriers are added together and then scaled by `amplitude`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the algorithm uses more operators than there are.
+    #[must_use]
+    pub fn new(operators: Vec<Operator>, algorithm: Algorithm, frequency: f64, amplitude: f64, sample_rate: u32) -> Self {
+        assert!(algorithm.operators() <= operators.len(), "the algorithm uses more operators than there are");
+        Self {
+            outputs: vec![0.; operators.len()],
+            operators,
+            algorithm,
+            frequency,
+            amplitude,
+            sample_rate,
+        }
+    }
+
+    /// Change the frequency in hertz, carrying on from each operator's current phase.
+    pub const fn set_frequency(&mut self, frequency: f64) {
+        self.frequency = frequency;
+    }
+
+    /// Change the amplitude, where 1 is full scale.
+    pub const fn set_amplitude(&mut self, amplitude: f64) {
+        self.amplitude = amplitude;
+    }
+
+    /// Return an operator, e.g. to change its level.
+    #[must_use]
+    pub fn operator_mut(&mut self, index: usize) -> Option<&mut Operator> {
+        self.operators.get_mut(index)
+    }
+
+    /// Start a note in every operator's envelope.
+    pub fn note_on(&mut self) {
+        self.operators.iter_mut().for_each(|operator| operator.envelope.note_on());
+    }
+
+    /// Release the note in every operator's envelope.
+    pub fn note_off(&mut self) {
+        self.operators.iter_mut().for_each(|operator| operator.envelope.note_off());
+    }
+
+    /// Return whether any carrier's envelope is still active, so the voice is still making sound.
+    #[must_use]
+    pub fn is_active(&self) -> bool {
+        self.algorithm.carriers.iter().any(|&carrier| self.operators[carrier].envelope.is_active())
+    }
+
+    /// Return the next sample, as a linear level where 1 is full scale, and move on to the one after it.
+    pub fn next_sample(&mut self) -> f64 {
+        let increment = self.frequency / f64::from(self.sample_rate);
+        for (index, operator) in self.operators.iter_mut().enumerate().rev() {
+            let modulation = self
+                .algorithm
+                .modulations
+                .iter()
+                .filter(|(_, target)| *target == index)
+                .map(|&(modulator, _)| self.outputs[modulator])
+                .sum();
+            self.outputs[index] = operator.next_sample(modulation, increment);
+        }
+        self.algorithm.carriers.iter().map(|&carrier| self.outputs[carrier]).sum::<f64>() * self.amplitude
+    }
+
+    /// Fill a buffer with the next samples, with the same sound in every channel.
+    pub fn fill<T: Sample + FromSample<f64>, const N: usize>(&mut self, blocks: &mut [Block<T, N>]) {
+        for block in blocks {
+            *block = Block([T::from_sample(self.next_sample()); N]);
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use std::f64::consts::FRAC_PI_2;
+
+use cpal::{FromSample, Sample};
+
+use crate::Block;
+
+/// Convert a level in decibels to a linear gain, where 0 dB is a gain of 1 and [`f64::NEG_INFINITY`] is silence.
+#[must_use]
+pub fn db_to_gain(db: f64) -> f64 {
+    10_f64.powf(db / 20.)
+}
+
+/// Convert a linear gain to a level in decibels, where a gain of 1 is 0 dB and silence is [`f64::NEG_INFINITY`]. The sign of the gain is ignored.
+#[must_use]
+pub fn gain_to_db(gain: f64) -> f64 {
+    20. * gain.abs().log10()
+}
+
+/// A curve that limits samples to a threshold.
+///
+/// The soft curves all have a slope of 1 around zero, so quiet samples pass through unchanged, and bend more and more smoothly towards the threshold, which adds fewer high
+/// harmonics than cutting samples off.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Clip {
+    /// Leave samples as they are, even past full scale.
+    None,
+    /// Cut samples off at the threshold, which leaves everything below it untouched.
+    #[default]
+    Hard,
+    /// The hyperbolic tangent, which only reaches the threshold at infinity.
+    Tanh,
+    /// A cubic that reaches the threshold with a slope of 0 at 1.5 times the threshold, and stays there. This is the cheapest soft curve.
+    Cubic,
+    /// A scaled arctangent, which bends more gently than [`Clip::Tanh`] and only reaches the threshold at infinity.
+    Arctangent,
+}
+
+impl Clip {
+    /// Apply the curve to a sample relative to a threshold of 1.
+    #[must_use]
+    pub fn shape(self, sample: f64) -> f64 {
+        match self {
+            Self::None => sample,
+            Self::Hard => sample.clamp(-1., 1.),
+            Self::Tanh => sample.tanh(),
+            Self::Cubic => {
+                let sample = sample.clamp(-1.5, 1.5);
+                (sample * sample * sample).mul_add(-4. / 27., sample)
+            }
+            Self::Arctangent => (sample * FRAC_PI_2).atan() / FRAC_PI_2,
+        }
+    }
+
+    /// Apply the curve to a sample, so that it never goes past `threshold`, a linear level where 1 is full scale.
+    #[must_use]
+    pub fn apply<T: Sample + FromSample<f64>>(self, sample: T, threshold: f64) -> T
+    where
+        f64: FromSample<T>,
+    {
+        T::from_sample(self.shape(f64::from_sample(sample) / threshold) * threshold)
+    }
+}
+
+/// A parameter that glides exponentially towards its target instead of jumping, to avoid the clicks and "zipper noise" of changing a gain suddenly.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Smoother {
+    value: f64,
+    target: f64,
+    /// The fraction of the remaining distance to the target that is covered in each sample.
+    coefficient: f64,
+}
+
+impl Smoother {
+    /// Create a new [`Smoother`] that starts at `value`.
+    ///
+    /// `time` is the time constant in seconds, the time that it takes to cover 63% of the distance to a new target. A time of 0 makes the value jump straight to the target.
+    #[must_use]
+    pub fn new(value: f64, time: f64, sample_rate: u32) -> Self {
+        Self {
+            value,
+            target: value,
+            coefficient: 1. - (-1. / (time * f64::from(sample_rate))).exp(),
+        }
+    }
+
+    /// Set the value to glide towards.
+    pub const fn set_target(&mut self, target: f64) {
+        self.target = target;
+    }
+
+    /// Jump straight to a value, without gliding.
+    pub const fn reset(&mut self, value: f64) {
+        self.value = value;
+        self.target = value;
+    }
+
+    /// Return the value to glide towards.
+    #[must_use]
+    pub const fn target(&self) -> f64 {
+        self.target
+    }
+
+    /// Return the current value.
+    #[must_use]
+    pub const fn value(&self) -> f64 {
+        self.value
+    }
+
+    /// Move one sample closer to the target, and return the new value.
+    pub fn next_value(&mut self) -> f64 {
+        self.value = (self.target - self.value).mul_add(self.coefficient, self.value);
+        // Snap to the target once the difference is far below anything audible, so that the value doesn't creep towards it forever.
+        if (self.target - self.value).abs() < 1e-9 {
+            self.value = self.target;
+        }
+        self.value
+    }
+}
+
+/// A gain stage with a smoothed gain, followed by a [`Clip`] curve that keeps the output below a ceiling.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GainStage<const N: usize> {
+    gain: Smoother,
+    clip: Clip,
+    ceiling: f64,
+}
+
+impl<const N: usize> GainStage<N> {
+    /// Create a new [`GainStage`] with a gain in decibels and a ceiling of full scale.
+    ///
+    /// `smoothing` is the time constant in seconds for changes to the gain, as in [`Smoother::new`].
+    #[must_use]
+    pub fn new(gain_db: f64, clip: Clip, smoothing: f64, sample_rate: u32) -> Self {
+        Self {
+            gain: Smoother::new(db_to_gain(gain_db), smoothing, sample_rate),
+            clip,
+            ceiling: 1.,
+        }
+    }
+
+    /// Set the gain in decibels, which the stage glides towards.
+    pub fn set_gain_db(&mut self, gain_db: f64) {
+        self.gain.set_target(db_to_gain(gain_db));
+    }
+
+    /// Return the gain in decibels that the stage is gliding towards.
+    #[must_use]
+    pub fn gain_db(&self) -> f64 {
+        gain_to_db(self.gain.target())
+    }
+
+    /// Set the level in decibels that the output never goes past (unless the curve is [`Clip::None`]).
+    pub fn set_ceiling_db(&mut self, ceiling_db: f64) {
+        self.ceiling = db_to_gain(ceiling_db);
+    }
+
+    /// Set the curve that keeps the output below the ceiling.
+    pub const fn set_clip(&mut self, clip: Clip) {
+        self.clip = clip;
+    }
+
+    /// Apply the gain and then the clip curve to a block.
+    pub fn process<T: Sample + FromSample<f64>>(&mut self, block: impl Into<Block<T, N>>) -> Block<T, N>
+    where
+        f64: FromSample<T>,
+    {
+        let Block(samples) = block.into();
+        let gain = self.gain.next_value();
+        Block(samples.map(|sample| T::from_sample(self.clip.shape(f64::from_sample(sample) * gain / self.ceiling) * self.ceiling)))
+    }
+}
@@ -1,7 +1,8 @@
-use std::f64::consts::TAU;
+use std::f64::consts::{PI, TAU};
 
 use cpal::{FromSample, Sample};
 
+use super::noise::{Noise, NoiseKind};
 use crate::Block;
 
 /// Given a `frequency` in hertz and an `amplitude`, return a function over time (in seconds) that generates a sine wave.
@@ -13,14 +14,21 @@ where
 }
 
 /// Given a `frequency` in hertz and an `amplitude`, return a function over time (in seconds) that generates a square wave.
+///
+/// The wave is not band-limited, so it aliases. Use an [`Oscillator`](super::oscillator::Oscillator) for audio that will be listened to.
 pub fn square_wave<T: Sample + FromSample<f64>, const N: usize>(frequency: f64, amplitude: T) -> impl FnMut(f64) -> Block<T, N>
 where
     f64: FromSample<T>,
 {
-    move |time| Block([T::from_sample((-1_f64).powf(2. * frequency * time) * f64::from_sample(amplitude)); N])
+    move |time| {
+        let sign = if (frequency * time).rem_euclid(1.) < 0.5 { 1. } else { -1. };
+        Block([T::from_sample(sign * f64::from_sample(amplitude)); N])
+    }
 }
 
 /// Given a `frequency` in hertz and an `amplitude`, return a function over time (in seconds) that generates a triangle wave.
+///
+/// The wave is not band-limited, so it aliases. Use an [`Oscillator`](super::oscillator::Oscillator) for audio that will be listened to.
 pub fn triangle_wave<T: Sample + FromSample<f64>, const N: usize>(frequency: f64, amplitude: T) -> impl FnMut(f64) -> Block<T, N>
 where
     f64: FromSample<T>,
@@ -29,6 +37,8 @@ where
 }
 
 /// Given a `frequency` in hertz and an `amplitude`, return a function over time (in seconds) that generates a sawtooth wave.
+///
+/// The wave is not band-limited, so it aliases. Use an [`Oscillator`](super::oscillator::Oscillator) for audio that will be listened to.
 pub fn sawtooth_wave<T: Sample + FromSample<f64>, const N: usize>(frequency: f64, amplitude: T) -> impl FnMut(f64) -> Block<T, N>
 where
     f64: FromSample<T>,
@@ -41,6 +51,159 @@ pub fn silence<T: Sample, const N: usize>() -> impl FnMut(f64) -> Block<T, N> {
     move |_| Block([T::EQUILIBRIUM; N])
 }
 
+/// Return a function that generates a constant offset, e.g. to test that a signal chain blocks DC.
+pub fn dc_offset<T: Sample, const N: usize>(level: T) -> impl FnMut(f64) -> Block<T, N> {
+    move |_| Block([level; N])
+}
+
+/// Return a function over time (in seconds) that generates a single sample of `amplitude` at the sample closest to time `at`, and silence everywhere else.
+pub fn impulse<T: Sample, const N: usize>(at: f64, amplitude: T, sample_rate: u32) -> impl FnMut(f64) -> Block<T, N> {
+    let rate = f64::from(sample_rate);
+    move |time| {
+        if ((time - at) * rate).abs() < 0.5 {
+            Block([amplitude; N])
+        } else {
+            Block([T::EQUILIBRIUM; N])
+        }
+    }
+}
+
+/// How the frequency of a [`sweep`] moves from its start to its end.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Sweep {
+    /// The same number of hertz every second, which spends most of the time in the highest octaves.
+    Linear,
+    /// The same number of octaves every second (also called an exponential sweep). Distortion products arrive before the linear response after deconvolution, so they can be
+    /// cut off.
+    #[default]
+    Logarithmic,
+}
+
+impl Sweep {
+    /// Return the phase in cycles of a sweep from `start` to `end` hertz over `duration` seconds, at `time`.
+    fn phase(self, start: f64, end: f64, duration: f64, time: f64) -> f64 {
+        match self {
+            Self::Linear => (end - start).mul_add(time * time / (2. * duration), start * time),
+            Self::Logarithmic => {
+                let rate = duration / (end / start).ln();
+                start * rate * (time / rate).exp_m1()
+            }
+        }
+    }
+
+    /// Return how quickly the frequency is changing at `time`, in hertz per second.
+    fn slope(self, start: f64, end: f64, duration: f64, time: f64) -> f64 {
+        match self {
+            Self::Linear => (end - start) / duration,
+            Self::Logarithmic => {
+                let rate = duration / (end / start).ln();
+                start * (time / rate).exp() / rate
+            }
+        }
+    }
+}
+
+/// Return a function over time (in seconds) that generates a sine wave sweeping from `start` to `end` hertz over `duration` seconds, and silence outside of that.
+///
+/// Convolving a recording of the sweep with its [`inverse_sweep`] gives the impulse response of whatever it was played through.
+pub fn sweep<T: Sample + FromSample<f64>, const N: usize>(kind: Sweep, start: f64, end: f64, duration: f64, amplitude: T) -> impl FnMut(f64) -> Block<T, N>
+where
+    f64: FromSample<T>,
+{
+    move |time| {
+        if (0. ..duration).contains(&time) {
+            Block([T::from_sample(f64::from_sample(amplitude) * (TAU * kind.phase(start, end, duration, time)).sin()); N])
+        } else {
+            Block([T::EQUILIBRIUM; N])
+        }
+    }
+}
+
+/// Return the inverse filter of a [`sweep`] with the same settings, sampled at `sample_rate`, for deconvolution.
+///
+/// This is the sweep reversed in time, weighted so that convolving it with the sweep has a gain of 1 between `start` and `end` hertz. The impulse response of a recording of
+/// the sweep starts at sample `len - 1` of its convolution with the filter, where `len` is the length of the filter.
+#[must_use]
+pub fn inverse_sweep(kind: Sweep, start: f64, end: f64, duration: f64, amplitude: f64, sample_rate: u32) -> Vec<f64> {
+    let rate = f64::from(sample_rate);
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "sweeps are positive and far shorter than 2^64 samples")]
+    let len = (duration * rate).round().max(0.) as usize;
+    (0..len)
+        .rev()
+        .map(|sample| {
+            #[allow(clippy::cast_precision_loss, reason = "sweeps are far shorter than 2^52 samples")]
+            let time = sample as f64 / rate;
+            // Each frequency is in the sweep for a time inversely proportional to how fast it is sweeping, so weighting by that evens out the energy at every frequency.
+            let weight = 4. * kind.slope(start, end, duration, time) / (amplitude * rate * rate);
+            weight * (TAU * kind.phase(start, end, duration, time)).sin()
+        })
+        .collect()
+}
+
+/// Return a function over time (in seconds) that generates bursts of pink noise `on` seconds long, separated by `off` seconds of silence, e.g. for measuring reverb time.
+///
+/// Each channel has its own noise. The noise moves on by one sample every time the function is called, so it should be called once for every sample.
+pub fn pink_noise_bursts<T: Sample + FromSample<f64>, const N: usize>(on: f64, off: f64, amplitude: T, seed: u64, sample_rate: u32) -> impl FnMut(f64) -> Block<T, N>
+where
+    f64: FromSample<T>,
+{
+    let mut noise = Noise::new(NoiseKind::Pink, f64::from_sample(amplitude), seed, sample_rate);
+    move |time| {
+        let block = noise.next_block();
+        if time.rem_euclid(on + off) < on {
+            block
+        } else {
+            Block([T::EQUILIBRIUM; N])
+        }
+    }
+}
+
+/// The centre frequencies of the octave bands from 31.5 Hz to 16 kHz (from ISO 266), for a [`schroeder_multi_tone`] that covers the whole audible range.
+pub const ISO_OCTAVE_BANDS: [f64; 10] = [31.5, 63., 125., 250., 500., 1000., 2000., 4000., 8000., 16000.];
+
+/// Return a function over time (in seconds) that generates the sum of sine waves at the given frequencies, e.g. for measuring frequency response and intermodulation in one go.
+///
+/// The tones are scaled so that the peak is at most `amplitude`. They have Schroeder phases, which keep the peaks much lower than that when the tones are all harmonics of one
+/// frequency.
+///
+/// This is a general-purpose signal rather than a standardised one. For the ITU-T O.42 four-tone signal, use [`itu_four_tone`].
+pub fn schroeder_multi_tone<T: Sample + FromSample<f64>, const N: usize>(frequencies: &[f64], amplitude: T) -> impl FnMut(f64) -> Block<T, N> + use<'_, T, N>
+where
+    f64: FromSample<T>,
+{
+    #[allow(clippy::cast_precision_loss, reason = "there are far fewer than 2^52 tones")]
+    let count = frequencies.len() as f64;
+    let scale = f64::from_sample(amplitude) / count.max(1.);
+    move |time| {
+        #[allow(clippy::cast_precision_loss, reason = "there are far fewer than 2^52 tones")]
+        let sum: f64 = frequencies
+            .iter()
+            .enumerate()
+            .map(|(index, frequency)| TAU.mul_add(frequency * time, -PI * (index * (index + 1)) as f64 / count).sin())
+            .sum();
+        Block([T::from_sample(sum * scale); N])
+    }
+}
+
+/// The frequencies of the four-tone intermodulation test signal of ITU-T O.42 (the same as IEEE 743): two pairs of tones around 860 Hz and 1380 Hz.
+pub const ITU_FOUR_TONES: [f64; 4] = [857., 863., 1372., 1388.];
+
+/// Return a function over time (in seconds) that generates the four-tone signal of ITU-T O.42, for measuring second and third-order intermodulation distortion.
+///
+/// The tones at [`ITU_FOUR_TONES`] have equal levels, each a quarter of `amplitude` so that the peak is at most `amplitude`, and all start at zero phase. The distortion
+/// products of a system that the signal is played through fall in bands between the tones, around 520 Hz and 2240 Hz for second-order products and around 1900 Hz for third-order
+/// ones.
+pub fn itu_four_tone<T: Sample + FromSample<f64>, const N: usize>(amplitude: T) -> impl FnMut(f64) -> Block<T, N>
+where
+    f64: FromSample<T>,
+{
+    let scale = f64::from_sample(amplitude) / 4.;
+    move |time| {
+        let sum: f64 = ITU_FOUR_TONES.iter().map(|frequency| (TAU * frequency * time).sin()).sum();
+        Block([T::from_sample(sum * scale); N])
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 /// A sine wave with a given `amplitude` and `index`.
 ///
@@ -55,6 +218,16 @@ impl<T: Sample> Harmonic<T> {
     pub const fn new(amplitude: T, index: usize) -> Self {
         Self { amplitude, index }
     }
+
+    /// Return the amplitude of the harmonic.
+    pub const fn amplitude(&self) -> T {
+        self.amplitude
+    }
+
+    /// Return the index of the harmonic, which is 0 for the fundamental.
+    pub const fn index(&self) -> usize {
+        self.index
+    }
 }
 
 /// Return a function over time (in seconds) that generates a wave resulting from the sum of the given harmonics.
@@ -0,0 +1,359 @@
+use std::{f64::consts::TAU, fmt::Debug};
+
+use super::{envelope::Envelope, gain::GainStage, oscillator::Oscillator, random::Rng, wavetable::WavetableOscillator};
+
+/// The shape of an [`Lfo`]'s wave. Every shape starts at zero and rises, like a sine wave, apart from [`LfoShape::SampleAndHold`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LfoShape {
+    #[default]
+    Sine,
+    Triangle,
+    /// A rising ramp that jumps from 1 to -1 halfway through each cycle.
+    Sawtooth,
+    /// 1 for the first half of each cycle, then -1 for the second half.
+    Square,
+    /// A new random value at the start of each cycle, held until the next one.
+    SampleAndHold,
+}
+
+/// How fast an [`Lfo`] runs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rate {
+    /// A frequency in hertz.
+    Hertz(f64),
+    /// The length of one cycle in beats at the LFO's tempo, e.g. 0.25 for a cycle every sixteenth note.
+    Beats(f64),
+}
+
+/// A low-frequency oscillator, for moving parameters back and forth between -1 and 1.
+///
+/// LFO rates are far below the Nyquist frequency, so unlike an [`Oscillator`], the waves don't need to be band-limited.
+#[derive(Debug, Clone)]
+pub struct Lfo {
+    shape: LfoShape,
+    rate: Rate,
+    /// The tempo in beats per minute, for [`Rate::Beats`].
+    tempo: f64,
+    sample_rate: u32,
+    /// The position in the current cycle, from 0 (inclusive) to 1 (exclusive).
+    phase: f64,
+    rng: Rng,
+    /// The value held by [`LfoShape::SampleAndHold`] until the end of the cycle.
+    held: f64,
+}
+
+impl Lfo {
+    /// Create a new [`Lfo`] at the start of its cycle, with a tempo of 120 beats per minute.
+    #[must_use]
+    pub fn new(shape: LfoShape, rate: Rate, sample_rate: u32) -> Self {
+        let mut rng = Rng::new(0);
+        Self {
+            shape,
+            rate,
+            tempo: 120.,
+            sample_rate,
+            phase: 0.,
+            held: rng.bipolar(),
+            rng,
+        }
+    }
+
+    /// Seed the random values of [`LfoShape::SampleAndHold`], so that different LFOs don't move together.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+        self.held = self.rng.bipolar();
+    }
+
+    /// Change the shape, carrying on from the current phase.
+    pub const fn set_shape(&mut self, shape: LfoShape) {
+        self.shape = shape;
+    }
+
+    /// Change the rate, carrying on from the current phase.
+    pub const fn set_rate(&mut self, rate: Rate) {
+        self.rate = rate;
+    }
+
+    /// Change the tempo in beats per minute that [`Rate::Beats`] follows.
+    pub const fn set_tempo(&mut self, tempo: f64) {
+        self.tempo = tempo;
+    }
+
+    /// Return the frequency in hertz.
+    #[must_use]
+    pub fn frequency(&self) -> f64 {
+        match self.rate {
+            Rate::Hertz(frequency) => frequency,
+            Rate::Beats(beats) => self.tempo / 60. / beats,
+        }
+    }
+
+    /// Return the position in the current cycle, from 0 (inclusive) to 1 (exclusive).
+    #[must_use]
+    pub const fn phase(&self) -> f64 {
+        self.phase
+    }
+
+    /// Jump to a position in the cycle, e.g. 0 to restart the wave. Only the fractional part is used.
+    pub fn set_phase(&mut self, phase: f64) {
+        self.phase = phase.rem_euclid(1.);
+    }
+
+    /// Return the next value, from -1 to 1, and move on to the one after it.
+    pub fn next_value(&mut self) -> f64 {
+        let phase = self.phase;
+        let value = match self.shape {
+            LfoShape::Sine => (TAU * phase).sin(),
+            LfoShape::Triangle => {
+                // The distance from the peak at a quarter of the way through the cycle.
+                let distance = (phase - 0.25).rem_euclid(1.).min((0.25 - phase).rem_euclid(1.));
+                4.0_f64.mul_add(-distance, 1.)
+            }
+            LfoShape::Sawtooth => 2.0_f64.mul_add((phase + 0.5).rem_euclid(1.), -1.),
+            LfoShape::Square => {
+                if phase < 0.5 {
+                    1.
+                } else {
+                    -1.
+                }
+            }
+            LfoShape::SampleAndHold => self.held,
+        };
+        self.phase = phase + self.frequency() / f64::from(self.sample_rate);
+        if !(0. ..1.).contains(&self.phase) {
+            self.phase = self.phase.rem_euclid(1.);
+            self.held = self.rng.bipolar();
+        }
+        value
+    }
+}
+
+/// Something that makes a stream of values to modulate parameters with, such as an [`Lfo`] or an [`Envelope`].
+pub trait Source: Debug {
+    /// Return the next value, and move on to the one after it.
+    fn next_value(&mut self) -> f64;
+
+    /// Return whether the values are from -1 to 1, rather than from 0 to 1.
+    fn bipolar(&self) -> bool {
+        true
+    }
+
+    /// Start a note, e.g. to start an envelope or restart an LFO's cycle.
+    fn note_on(&mut self) {}
+
+    /// Release the note.
+    fn note_off(&mut self) {}
+}
+
+impl Source for Lfo {
+    fn next_value(&mut self) -> f64 {
+        Self::next_value(self)
+    }
+
+    /// Restart the cycle, so that every note starts the same way.
+    fn note_on(&mut self) {
+        self.set_phase(0.);
+    }
+}
+
+impl Source for Envelope {
+    fn next_value(&mut self) -> f64 {
+        Self::next_value(self)
+    }
+
+    fn bipolar(&self) -> bool {
+        false
+    }
+
+    fn note_on(&mut self) {
+        Self::note_on(self);
+    }
+
+    fn note_off(&mut self) {
+        Self::note_off(self);
+    }
+}
+
+/// A generator or effect with parameters that can be changed while it runs, by a [`ModulationMatrix`].
+pub trait Modulate {
+    /// The parameters that can be modulated.
+    type Parameter: Copy + PartialEq;
+
+    /// Set a parameter, in its own units.
+    fn set_parameter(&mut self, parameter: Self::Parameter, value: f64);
+}
+
+/// The parameters of an [`Oscillator`] or [`WavetableOscillator`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OscillatorParameter {
+    /// The frequency in hertz, e.g. for vibrato.
+    Frequency,
+    /// The amplitude, where 1 is full scale, e.g. for tremolo.
+    Amplitude,
+    /// The position between the first frame of a wavetable (0) and the last (1). [`Oscillator`]s ignore this.
+    Position,
+}
+
+impl Modulate for Oscillator {
+    type Parameter = OscillatorParameter;
+
+    fn set_parameter(&mut self, parameter: Self::Parameter, value: f64) {
+        match parameter {
+            OscillatorParameter::Frequency => self.set_frequency(value),
+            OscillatorParameter::Amplitude => self.set_amplitude(value),
+            OscillatorParameter::Position => {}
+        }
+    }
+}
+
+impl Modulate for WavetableOscillator<'_> {
+    type Parameter = OscillatorParameter;
+
+    fn set_parameter(&mut self, parameter: Self::Parameter, value: f64) {
+        match parameter {
+            OscillatorParameter::Frequency => self.set_frequency(value),
+            OscillatorParameter::Amplitude => self.set_amplitude(value),
+            OscillatorParameter::Position => self.set_position(value),
+        }
+    }
+}
+
+/// The parameters of a [`GainStage`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GainParameter {
+    /// The gain in decibels, which is smoothed by the gain stage.
+    Gain,
+    /// The level in decibels that the clip curve limits the output to.
+    Ceiling,
+}
+
+impl<const N: usize> Modulate for GainStage<N> {
+    type Parameter = GainParameter;
+
+    fn set_parameter(&mut self, parameter: Self::Parameter, value: f64) {
+        match parameter {
+            GainParameter::Gain => self.set_gain_db(value),
+            GainParameter::Ceiling => self.set_ceiling_db(value),
+        }
+    }
+}
+
+/// How a [`Source`]'s values are used by a route.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Polarity {
+    /// From -1 to 1, moving the parameter both ways from where it is set, e.g. for vibrato.
+    #[default]
+    Bipolar,
+    /// From 0 to 1, moving the parameter only one way, e.g. for tremolo with a negative depth.
+    Unipolar,
+}
+
+/// A connection from a [`Source`] to a parameter in a [`ModulationMatrix`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Route<P> {
+    pub source: usize,
+    pub parameter: P,
+    /// How far the parameter moves, in its own units, when the source is at 1.
+    pub depth: f64,
+    pub polarity: Polarity,
+}
+
+/// Routes any number of [`Source`]s to the parameters of something that can be modulated, with a depth and polarity for each route.
+///
+/// Each parameter is set to its base value plus the sum of the routes to it, once for every sample. A parameter needs a base value before anything can be routed to it, since
+/// the matrix can't know where the target has it set.
+#[derive(Debug)]
+pub struct ModulationMatrix<P> {
+    sources: Vec<Box<dyn Source + Send>>,
+    /// The value of each source for the current sample.
+    values: Vec<f64>,
+    bases: Vec<(P, f64)>,
+    routes: Vec<Route<P>>,
+}
+
+impl<P> Default for ModulationMatrix<P> {
+    fn default() -> Self {
+        Self {
+            sources: Vec::new(),
+            values: Vec::new(),
+            bases: Vec::new(),
+            routes: Vec::new(),
+        }
+    }
+}
+
+impl<P: Copy + PartialEq> ModulationMatrix<P> {
+    /// Create a new [`ModulationMatrix`] with no sources or routes.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a source, and return its index for routing it.
+    pub fn add_source(&mut self, source: impl Source + Send + 'static) -> usize {
+        self.sources.push(Box::new(source));
+        self.values.push(0.);
+        self.sources.len() - 1
+    }
+
+    /// Return a source, e.g. to change an LFO's rate.
+    #[must_use]
+    pub fn source_mut(&mut self, index: usize) -> Option<&mut (dyn Source + Send + 'static)> {
+        self.sources.get_mut(index).map(AsMut::as_mut)
+    }
+
+    /// Set the value of a parameter before modulation.
+    pub fn set_base(&mut self, parameter: P, value: f64) {
+        match self.bases.iter_mut().find(|(base, _)| *base == parameter) {
+            Some((_, base)) => *base = value,
+            None => self.bases.push((parameter, value)),
+        }
+    }
+
+    /// Route a source to a parameter, and return the route's index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no source with index `source`, or if the parameter has no base value from [`ModulationMatrix::set_base`].
+    pub fn route(&mut self, source: usize, parameter: P, depth: f64, polarity: Polarity) -> usize {
+        assert!(source < self.sources.len(), "there is no source {source}");
+        assert!(self.bases.iter().any(|(base, _)| *base == parameter), "a parameter needs a base value before it can be modulated");
+        self.routes.push(Route { source, parameter, depth, polarity });
+        self.routes.len() - 1
+    }
+
+    /// Return a route, e.g. to change its depth.
+    #[must_use]
+    pub fn route_mut(&mut self, index: usize) -> Option<&mut Route<P>> {
+        self.routes.get_mut(index)
+    }
+
+    /// Start a note in every source.
+    pub fn note_on(&mut self) {
+        self.sources.iter_mut().for_each(|source| source.note_on());
+    }
+
+    /// Release the note in every source.
+    pub fn note_off(&mut self) {
+        self.sources.iter_mut().for_each(|source| source.note_off());
+    }
+
+    /// Move every source on by one sample, and set each parameter of `target` that has a base value.
+    pub fn apply(&mut self, target: &mut impl Modulate<Parameter = P>) {
+        for (source, value) in self.sources.iter_mut().zip(&mut self.values) {
+            *value = source.next_value();
+        }
+        for &(parameter, base) in &self.bases {
+            let value = self.routes.iter().filter(|route| route.parameter == parameter).fold(base, |value, route| {
+                let source = self.values[route.source];
+                let source = match (route.polarity, self.sources[route.source].bipolar()) {
+                    (Polarity::Bipolar, true) | (Polarity::Unipolar, false) => source,
+                    (Polarity::Bipolar, false) => source.mul_add(2., -1.),
+                    (Polarity::Unipolar, true) => f64::midpoint(source, 1.),
+                };
+                source.mul_add(route.depth, value)
+            });
+            target.set_parameter(parameter, value);
+        }
+    }
+}
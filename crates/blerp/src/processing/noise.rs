@@ -0,0 +1,154 @@
+use cpal::{FromSample, Sample};
+
+use super::random::Rng;
+use crate::Block;
+
+/// The number of rows of random values that are summed for pink noise, which keeps the slope going down to about `sample_rate / 2^17` hertz.
+const PINK_ROWS: usize = 16;
+/// How much of the previous brown noise sample is kept, which puts the corner below which the slope flattens out at about `sample_rate / 6000` hertz.
+const BROWN_LEAK: f64 = 0.999;
+/// The gain of the white noise that is integrated into brown noise, which makes it reach full scale only rarely.
+const BROWN_STEP: f64 = 0.025;
+
+/// The spectrum of a [`Noise`] generator.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum NoiseKind {
+    /// Equal power at every frequency.
+    #[default]
+    White,
+    /// Equal power in every octave, falling by 3 dB per octave, like many natural sounds. This is made with the Voss-McCartney algorithm.
+    Pink,
+    /// Power falling by 6 dB per octave (also called red noise), like a random walk.
+    Brown,
+    /// Sparse impulses of random sign, one at a random position in each period of `1 / density` seconds. With a density of a few thousand impulses per second, this sounds as
+    /// smooth as white noise, but is much cheaper to convolve with, e.g. for reverb.
+    Velvet { density: f64 },
+}
+
+/// The state of one channel of a [`Noise`] generator.
+#[derive(Debug, Clone)]
+struct Channel {
+    rng: Rng,
+    /// The random values summed for pink noise. Row `n` is replaced every `2^(n + 1)` samples.
+    rows: [f64; PINK_ROWS],
+    /// The sum of `rows`.
+    sum: f64,
+    /// The number of samples generated so far.
+    counter: u64,
+    /// The last brown noise sample.
+    brown: f64,
+    /// The sample that the next velvet noise impulse falls on, and its sign.
+    impulse: (u64, f64),
+    /// The number of the velvet noise period that the next impulse is in.
+    grid: u64,
+}
+
+impl Channel {
+    fn new(mut rng: Rng) -> Self {
+        let rows = std::array::from_fn(|_| rng.bipolar());
+        Self {
+            rng,
+            rows,
+            sum: rows.iter().sum(),
+            counter: 0,
+            brown: 0.,
+            impulse: (0, 0.),
+            grid: 0,
+        }
+    }
+
+    /// Place the velvet noise impulse in the next period of `period` samples, which starts and ends on the first whole samples at or after its bounds.
+    fn schedule_impulse(&mut self, period: f64) {
+        #[allow(clippy::cast_precision_loss, reason = "the period number only loses precision after far longer than anything will run")]
+        let [start, end] = [self.grid, self.grid + 1].map(|grid| (grid as f64 * period).ceil());
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "the position is positive, and rounded down on purpose")]
+        let position = self.rng.uniform().mul_add(end - start, start) as u64;
+        let sign = if self.rng.next_u64() >> 63 == 0 { 1. } else { -1. };
+        self.impulse = (position, sign);
+        self.grid += 1;
+    }
+
+    fn next_sample(&mut self, kind: NoiseKind, sample_rate: u32) -> f64 {
+        let sample = match kind {
+            NoiseKind::White => self.rng.bipolar(),
+            NoiseKind::Pink => {
+                // Each row is replaced half as often as the one before it, which is the row given by the number of trailing zeros of the counter.
+                let row = (self.counter + 1).trailing_zeros() as usize;
+                if let Some(value) = self.rows.get_mut(row) {
+                    self.sum -= *value;
+                    *value = self.rng.bipolar();
+                    self.sum += *value;
+                }
+                #[allow(clippy::cast_precision_loss, reason = "there are only a few rows")]
+                let scale = (PINK_ROWS + 1) as f64;
+                (self.sum + self.rng.bipolar()) / scale
+            }
+            NoiseKind::Brown => {
+                self.brown = BROWN_LEAK.mul_add(self.brown, BROWN_STEP * self.rng.bipolar()).clamp(-1., 1.);
+                self.brown
+            }
+            NoiseKind::Velvet { density } => {
+                let period = (f64::from(sample_rate) / density).max(1.);
+                if self.counter == 0 {
+                    self.schedule_impulse(period);
+                }
+                let (position, sign) = self.impulse;
+                if self.counter == position {
+                    self.schedule_impulse(period);
+                    sign
+                } else {
+                    0.
+                }
+            }
+        };
+        self.counter += 1;
+        sample
+    }
+}
+
+/// A seedable noise generator with a separate, uncorrelated stream for each channel.
+///
+/// The same seed always produces the same noise, so anything that uses it can be rendered deterministically.
+#[derive(Debug, Clone)]
+pub struct Noise<const N: usize> {
+    kind: NoiseKind,
+    amplitude: f64,
+    sample_rate: u32,
+    channels: [Channel; N],
+}
+
+impl<const N: usize> Noise<N> {
+    /// Create a new [`Noise`] generator, where an amplitude of 1 keeps the noise within full scale.
+    #[must_use]
+    pub fn new(kind: NoiseKind, amplitude: f64, seed: u64, sample_rate: u32) -> Self {
+        // Seed each channel from its own value of another generator, so that the channels of one seed don't share a stream with those of another.
+        let mut seeds = Rng::new(seed);
+        Self {
+            kind,
+            amplitude,
+            sample_rate,
+            channels: std::array::from_fn(|_| Channel::new(Rng::new(seeds.next_u64()))),
+        }
+    }
+
+    /// Change the amplitude, where 1 keeps the noise within full scale.
+    pub const fn set_amplitude(&mut self, amplitude: f64) {
+        self.amplitude = amplitude;
+    }
+
+    /// Return the next block of noise.
+    pub fn next_block<T: Sample + FromSample<f64>>(&mut self) -> Block<T, N> {
+        let (kind, amplitude, sample_rate) = (self.kind, self.amplitude, self.sample_rate);
+        let mut channels = self.channels.iter_mut();
+        Block(std::array::from_fn(|_| {
+            channels.next().map_or(T::EQUILIBRIUM, |channel| T::from_sample(channel.next_sample(kind, sample_rate) * amplitude))
+        }))
+    }
+
+    /// Fill a buffer with the next blocks of noise.
+    pub fn fill<T: Sample + FromSample<f64>>(&mut self, blocks: &mut [Block<T, N>]) {
+        for block in blocks {
+            *block = self.next_block();
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use std::f64::consts::TAU;
+
+use cpal::{FromSample, Sample};
+
+use crate::Block;
+
+/// The shape of an [`Oscillator`]'s wave. Every waveform starts at zero and rises, like a sine wave.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Waveform {
+    #[default]
+    Sine,
+    /// Full scale for the first half of each cycle, then negative full scale for the second half.
+    Square,
+    Triangle,
+    /// A rising ramp that jumps from full scale to negative full scale halfway through each cycle.
+    Sawtooth,
+}
+
+/// The correction for a step of 1 at `phase` 0, spread over the samples on either side of it (the 2-point `PolyBLEP` residual).
+///
+/// `increment` is the distance that the phase moves in one sample.
+fn blep(phase: f64, increment: f64) -> f64 {
+    if phase < increment {
+        let position = phase / increment;
+        -(1. - position).powi(2) / 2.
+    } else if phase > 1. - increment {
+        let position = (phase - 1.) / increment;
+        (1. + position).powi(2) / 2.
+    } else {
+        0.
+    }
+}
+
+/// The correction for a change in slope of 1 per sample at `phase` 0, which is the integral of [`blep`] (the 2-point `PolyBLAMP` residual).
+fn blamp(phase: f64, increment: f64) -> f64 {
+    if phase < increment {
+        let position = phase / increment;
+        (1. - position).powi(3) / 6.
+    } else if phase > 1. - increment {
+        let position = (phase - 1.) / increment;
+        (1. + position).powi(3) / 6.
+    } else {
+        0.
+    }
+}
+
+/// An oscillator that keeps track of its phase between samples, and smooths the corners of its waveforms to reduce aliasing.
+///
+/// Unlike the functions in [`generation`](super::generation), the phase stays between 0 and 1 however long the oscillator runs, and changing the frequency doesn't make the wave
+/// jump.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Oscillator {
+    waveform: Waveform,
+    frequency: f64,
+    amplitude: f64,
+    sample_rate: u32,
+    /// The position in the current cycle, from 0 (inclusive) to 1 (exclusive).
+    phase: f64,
+}
+
+impl Oscillator {
+    /// Create a new [`Oscillator`] at the start of its cycle.
+    ///
+    /// Frequencies above half the sample rate can't be represented, so they still alias.
+    #[must_use]
+    pub const fn new(waveform: Waveform, frequency: f64, amplitude: f64, sample_rate: u32) -> Self {
+        Self {
+            waveform,
+            frequency,
+            amplitude,
+            sample_rate,
+            phase: 0.,
+        }
+    }
+
+    /// Return the frequency in hertz.
+    #[must_use]
+    pub const fn frequency(&self) -> f64 {
+        self.frequency
+    }
+
+    /// Change the frequency in hertz, carrying on from the current phase.
+    pub const fn set_frequency(&mut self, frequency: f64) {
+        self.frequency = frequency;
+    }
+
+    /// Change the amplitude, where 1 is full scale.
+    pub const fn set_amplitude(&mut self, amplitude: f64) {
+        self.amplitude = amplitude;
+    }
+
+    /// Change the waveform, carrying on from the current phase.
+    pub const fn set_waveform(&mut self, waveform: Waveform) {
+        self.waveform = waveform;
+    }
+
+    /// Return the position in the current cycle, from 0 (inclusive) to 1 (exclusive).
+    #[must_use]
+    pub const fn phase(&self) -> f64 {
+        self.phase
+    }
+
+    /// Jump to a position in the cycle, e.g. 0 to restart the wave. Only the fractional part is used.
+    pub fn set_phase(&mut self, phase: f64) {
+        self.phase = phase.rem_euclid(1.);
+    }
+
+    /// Return the next sample, as a linear level where 1 is full scale, and move on to the one after it.
+    pub fn next_sample(&mut self) -> f64 {
+        let increment = (self.frequency / f64::from(self.sample_rate)).abs();
+        let phase = self.phase;
+        let sample = match self.waveform {
+            Waveform::Sine => (TAU * phase).sin(),
+            Waveform::Square => {
+                let naive = if phase < 0.5 { 1. } else { -1. };
+                2.0_f64.mul_add(blep(phase, increment) - blep((phase - 0.5).rem_euclid(1.), increment), naive)
+            }
+            Waveform::Triangle => {
+                let naive = if phase < 0.25 {
+                    4. * phase
+                } else if phase < 0.75 {
+                    4.0_f64.mul_add(-phase, 2.)
+                } else {
+                    4.0_f64.mul_add(phase, -4.)
+                };
+                // The slope changes by 8 per cycle at each corner.
+                let slope_change = 8. * increment;
+                slope_change.mul_add(
+                    blamp((phase - 0.75).rem_euclid(1.), increment),
+                    slope_change.mul_add(-blamp((phase - 0.25).rem_euclid(1.), increment), naive),
+                )
+            }
+            Waveform::Sawtooth => {
+                let shifted = (phase + 0.5).rem_euclid(1.);
+                2.0_f64.mul_add(shifted - blep(shifted, increment), -1.)
+            }
+        };
+        self.phase = (phase + self.frequency / f64::from(self.sample_rate)).rem_euclid(1.);
+        sample * self.amplitude
+    }
+
+    /// Fill a buffer with the next samples, with the same wave in every channel.
+    pub fn fill<T: Sample + FromSample<f64>, const N: usize>(&mut self, blocks: &mut [Block<T, N>]) {
+        for block in blocks {
+            *block = Block([T::from_sample(self.next_sample()); N]);
+        }
+    }
+}
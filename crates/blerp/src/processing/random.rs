@@ -0,0 +1,54 @@
+/// A small, fast, seedable pseudo-random number generator (xoshiro256++), used wherever processing needs noise.
+///
+/// The same seed always produces the same sequence, on every platform, so anything that uses it can be rendered deterministically.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: [u64; 4],
+}
+
+impl Rng {
+    /// Create a new [`Rng`] from a seed. Every seed (including zero) is valid.
+    #[must_use]
+    pub fn new(seed: u64) -> Self {
+        // Expand the seed with SplitMix64, as recommended by the authors of xoshiro, so that similar seeds produce unrelated sequences.
+        let mut seed = seed;
+        let mut split_mix = || {
+            seed = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+            let mut z = seed;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+            z ^ (z >> 31)
+        };
+        Self {
+            state: [split_mix(), split_mix(), split_mix(), split_mix()],
+        }
+    }
+
+    /// Return the next 64 random bits.
+    pub const fn next_u64(&mut self) -> u64 {
+        let [s0, s1, s2, s3] = &mut self.state;
+        let result = s0.wrapping_add(*s3).rotate_left(23).wrapping_add(*s0);
+        let shifted = *s1 << 17;
+        *s2 ^= *s0;
+        *s3 ^= *s1;
+        *s1 ^= *s2;
+        *s0 ^= *s3;
+        *s2 ^= shifted;
+        *s3 = s3.rotate_left(45);
+        result
+    }
+
+    /// Return a random number uniformly distributed in `[0, 1)`.
+    pub fn uniform(&mut self) -> f64 {
+        // The top 53 bits fill the mantissa of an `f64` exactly.
+        #[allow(clippy::cast_precision_loss, reason = "the value has at most 53 significant bits")]
+        {
+            (self.next_u64() >> 11) as f64 / (1_u64 << 53) as f64
+        }
+    }
+
+    /// Return a random number uniformly distributed in `[-1, 1)`.
+    pub fn bipolar(&mut self) -> f64 {
+        self.uniform().mul_add(2., -1.)
+    }
+}
@@ -0,0 +1,289 @@
+use std::{borrow::Cow, f64::consts::PI, iter::repeat_n, sync::Arc};
+
+use cpal::{FromSample, Sample, I24};
+use num::integer::gcd;
+
+use crate::{
+    wavefile::{Format, SampleExt, WaveFile, WaveFileDecodeError},
+    Block,
+};
+
+/// How hard a [`Resampler`] works to keep the passband flat and reject aliases, at the cost of speed and latency.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Quality {
+    /// A short filter that is flat to about 55% of the lower Nyquist frequency and rejects aliases by about 60 dB, for previews.
+    Fast,
+    /// Flat to about 80% of the lower Nyquist frequency, rejecting aliases by about 80 dB.
+    Medium,
+    /// Flat to about 87% of the lower Nyquist frequency (19 kHz at 44.1 kHz), rejecting aliases by about 100 dB.
+    #[default]
+    High,
+    /// Flat to about 92% of the lower Nyquist frequency (20 kHz at 44.1 kHz), rejecting aliases by about 120 dB, for mastering.
+    Best,
+}
+
+impl Quality {
+    /// Return the number of input frames on each side of the filter (when not downsampling), the cutoff as a fraction of the Nyquist frequency, the Kaiser window's β, and the number
+    /// of phases to interpolate between when the ratio between the rates can't be represented exactly.
+    const fn parameters(self) -> (usize, f64, f64, usize) {
+        match self {
+            Self::Fast => (8, 0.77, 5.65, 64),
+            Self::Medium => (24, 0.895, 7.86, 256),
+            Self::High => (48, 0.933, 10.06, 1024),
+            Self::Best => (96, 0.96, 12.26, 2048),
+        }
+    }
+}
+
+/// The zeroth-order modified Bessel function of the first kind, for the Kaiser window. 50 terms of the power series are plenty for the values of β that [`Quality`] uses.
+fn bessel_i0(x: f64) -> f64 {
+    let mut term = 1.;
+    1. + (1..50)
+        .map(|k| {
+            term *= (x / (2. * f64::from(k))).powi(2);
+            term
+        })
+        .sum::<f64>()
+}
+
+/// A windowed-sinc low-pass filter, sampled at a number of fractional offsets (phases) between input frames.
+#[derive(Debug)]
+struct Kernel {
+    /// The taps of every phase, one after the other, with an extra phase at the end (the first one shifted by a frame) to interpolate towards.
+    taps: Vec<f64>,
+    /// The number of taps in each phase, which is even.
+    len: usize,
+    phases: usize,
+    /// Whether each output frame falls exactly on a phase, because the number of phases is the output rate divided by the GCD of the rates.
+    exact: bool,
+}
+
+impl Kernel {
+    fn new(input_rate: u64, output_rate: u64, quality: Quality) -> Self {
+        let (half_len, cutoff, beta, interpolated_phases) = quality.parameters();
+        // When downsampling, the cutoff has to move down to the output's Nyquist frequency, and the filter gets longer to keep the same transition width.
+        #[allow(clippy::cast_precision_loss, reason = "sample rates are far below 2^52")]
+        let ratio = (output_rate as f64 / input_rate as f64).min(1.);
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, clippy::cast_precision_loss, reason = "the length is small and positive")]
+        let half_len = (half_len as f64 / ratio).ceil() as usize;
+        let cutoff = cutoff * ratio;
+        let exact = usize::try_from(output_rate).is_ok_and(|rate| rate <= interpolated_phases);
+        let phases = if exact { usize::try_from(output_rate).unwrap_or_default() } else { interpolated_phases };
+        let len = half_len * 2;
+        let mut taps = Vec::with_capacity((phases + 1) * len);
+        for phase in 0..=phases {
+            #[allow(clippy::cast_precision_loss, reason = "the number of phases is small")]
+            let fraction = phase as f64 / phases as f64;
+            let start = taps.len();
+            taps.extend((0..len).map(|tap| {
+                // The distance from the output frame to the input frame of this tap, where the first tap is `half_len - 1` frames before the output frame's integer position.
+                #[allow(clippy::cast_precision_loss, reason = "the length is small")]
+                let distance = tap as f64 - (half_len - 1) as f64 - fraction;
+                #[allow(clippy::cast_precision_loss, reason = "the length is small")]
+                let relative = distance / half_len as f64;
+                let window = relative.mul_add(-relative, 1.);
+                let sinc = if distance == 0. { 1. } else { (PI * cutoff * distance).sin() / (PI * cutoff * distance) };
+                sinc * bessel_i0(beta * window.max(0.).sqrt()) / bessel_i0(beta)
+            }));
+            // Normalize every phase to unity gain at DC, so that interpolating between phases can't add a ripple of its own.
+            let sum: f64 = taps[start..].iter().sum();
+            taps[start..].iter_mut().for_each(|tap| *tap /= sum);
+        }
+        Self { taps, len, phases, exact }
+    }
+
+    /// Return the output frame at `fraction / denominator` of the way between `frames[len / 2 - 1]` and `frames[len / 2]`, where `frames` holds exactly `len` frames.
+    fn apply<const N: usize>(&self, frames: &[[f64; N]], fraction: u64, denominator: u64) -> [f64; N] {
+        let mut output = [0.; N];
+        if self.exact {
+            let phase = usize::try_from(fraction * self.phases as u64 / denominator).unwrap_or_default();
+            let taps = &self.taps[phase * self.len..(phase + 1) * self.len];
+            for (frame, tap) in frames.iter().zip(taps) {
+                for (output, sample) in output.iter_mut().zip(frame) {
+                    *output = sample.mul_add(*tap, *output);
+                }
+            }
+        } else {
+            #[allow(clippy::cast_precision_loss, reason = "the fraction's precision only has to be a small fraction of a phase")]
+            let position = fraction as f64 / denominator as f64 * self.phases as f64;
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "the position is between zero and the number of phases")]
+            let phase = (position as usize).min(self.phases - 1);
+            #[allow(clippy::cast_precision_loss, reason = "the number of phases is small")]
+            let weight = position - phase as f64;
+            let (before, after) = self.taps[phase * self.len..(phase + 2) * self.len].split_at(self.len);
+            for ((frame, before), after) in frames.iter().zip(before).zip(after) {
+                let tap = weight.mul_add(after - before, *before);
+                for (output, sample) in output.iter_mut().zip(frame) {
+                    *output = sample.mul_add(tap, *output);
+                }
+            }
+        }
+        output
+    }
+}
+
+/// A sample-rate converter using a band-limited (Kaiser-windowed sinc) polyphase filter, for streams of frames that arrive in chunks.
+///
+/// The output is aligned with the input, so the first output frame is at the same time as the first input frame, and the filter's delay is made up for by holding back output until
+/// enough input has arrived. Call [`Resampler::finish`] at the end of the stream to get the rest.
+#[derive(Debug, Clone)]
+pub struct Resampler<const N: usize> {
+    kernel: Arc<Kernel>,
+    /// The input rate divided by the GCD of the rates, i.e. the number of input frames for every `output_step` output frames.
+    input_step: u64,
+    output_step: u64,
+    /// The input frames that are still needed, starting with the first tap of the next output frame.
+    history: Vec<[f64; N]>,
+    /// How far the next output frame is past `history[len / 2 - 1]`, in units of `1 / output_step` input frames.
+    fraction: u64,
+    input_frames: u64,
+    output_frames: u64,
+}
+
+impl<const N: usize> Resampler<N> {
+    /// Create a new [`Resampler`] that converts from `input_rate` to `output_rate`.
+    /// # Panics
+    /// Panics if either rate is zero.
+    #[must_use]
+    pub fn new(input_rate: u32, output_rate: u32, quality: Quality) -> Self {
+        assert!(input_rate > 0 && output_rate > 0, "sample rates must be greater than zero");
+        let divisor = gcd(input_rate, output_rate);
+        let (input_step, output_step) = (u64::from(input_rate / divisor), u64::from(output_rate / divisor));
+        let kernel = Arc::new(Kernel::new(input_step, output_step, quality));
+        Self {
+            history: vec![[0.; N]; kernel.len / 2 - 1],
+            kernel,
+            input_step,
+            output_step,
+            fraction: 0,
+            input_frames: 0,
+            output_frames: 0,
+        }
+    }
+
+    /// Return the number of input frames that have to arrive after an input frame before the output frame at the same time can be produced.
+    #[must_use]
+    pub fn latency(&self) -> usize {
+        self.kernel.len / 2
+    }
+
+    /// Resample a chunk of frames, returning every output frame that can be produced so far.
+    pub fn process<T: Sample + FromSample<f64>, B: Into<Block<T, N>>>(&mut self, frames: impl IntoIterator<Item = B>) -> Vec<Block<T, N>>
+    where
+        f64: FromSample<T>,
+    {
+        let start = self.history.len();
+        self.history.extend(frames.into_iter().map(|frame| {
+            let Block(samples) = frame.into();
+            samples.map(f64::from_sample)
+        }));
+        self.input_frames += (self.history.len() - start) as u64;
+        self.drain(u64::MAX)
+    }
+
+    /// Produce the output frames at the end of the stream, by padding the input with silence, and reset the [`Resampler`] so that it can be used for a new stream.
+    ///
+    /// The whole stream ends up with the input's duration at the new rate, rounded up to a whole frame.
+    pub fn finish<T: Sample + FromSample<f64>>(&mut self) -> Vec<Block<T, N>> {
+        let total = (self.input_frames * self.output_step).div_ceil(self.input_step);
+        self.history.extend(repeat_n([0.; N], self.kernel.len));
+        let output = self.drain(total - self.output_frames);
+        self.history = vec![[0.; N]; self.kernel.len / 2 - 1];
+        self.fraction = 0;
+        self.input_frames = 0;
+        self.output_frames = 0;
+        output
+    }
+
+    /// Produce up to `limit` output frames from the input frames that have arrived, and drop the input frames that are no longer needed.
+    fn drain<T: Sample + FromSample<f64>>(&mut self, limit: u64) -> Vec<Block<T, N>> {
+        let mut output = Vec::new();
+        let mut position = 0;
+        while position + self.kernel.len <= self.history.len() && (output.len() as u64) < limit {
+            let frame = self.kernel.apply(&self.history[position..position + self.kernel.len], self.fraction, self.output_step);
+            output.push(Block(frame.map(T::from_sample)));
+            self.fraction += self.input_step;
+            position += usize::try_from(self.fraction / self.output_step).unwrap_or_default();
+            self.fraction %= self.output_step;
+        }
+        self.history.drain(..position.min(self.history.len()));
+        self.output_frames += output.len() as u64;
+        output
+    }
+}
+
+/// Resample a whole stream of frames at once, as with [`Resampler::process`] followed by [`Resampler::finish`].
+/// # Panics
+/// Panics if either rate is zero.
+pub fn resample<T: Sample + FromSample<f64>, const N: usize, B: Into<Block<T, N>>>(frames: impl IntoIterator<Item = B>, input_rate: u32, output_rate: u32, quality: Quality) -> Vec<Block<T, N>>
+where
+    f64: FromSample<T>,
+{
+    let mut resampler = Resampler::new(input_rate, output_rate, quality);
+    let mut output = resampler.process(frames);
+    output.extend(resampler.finish());
+    output
+}
+
+/// Scale a position in frames from one sample rate to another, rounding to the nearest frame.
+fn rescale(position: u32, input_rate: u32, output_rate: u32) -> u32 {
+    let scaled = (u64::from(position) * u64::from(output_rate) + u64::from(input_rate) / 2) / u64::from(input_rate);
+    u32::try_from(scaled).unwrap_or(u32::MAX)
+}
+
+/// Resample a [`WaveFile`] to a new sample rate, returning a new [`WaveFile`] that owns its data.
+///
+/// PCM and floating point samples are stored in the same format as the original, and G.711 and IMA ADPCM samples (which would lose more quality if they were compressed again)
+/// become 16-bit PCM. Positions in the metadata (cue points, regions, loops and the broadcast time reference) are moved to the new rate.
+/// # Errors
+/// Returns a [`WaveFileDecodeError::UnsupportedSampleFormat`] if the samples can't be decoded (see [`WaveFile::frames`]).
+/// # Panics
+/// Panics if `sample_rate` or the file's sample rate is zero.
+pub fn resample_wave_file(file: &WaveFile, sample_rate: u32, quality: Quality) -> Result<WaveFile<'static>, WaveFileDecodeError> {
+    let channels = usize::from(file.channels.get());
+    let samples: Vec<f64> = file.samples()?.collect();
+    let resampled: Vec<Vec<f64>> = (0..channels)
+        .map(|channel| {
+            let input = samples.iter().skip(channel).step_by(channels).copied();
+            resample::<f64, 1, _>(input, file.sample_rate, sample_rate, quality).into_iter().map(|Block([sample])| sample).collect()
+        })
+        .collect();
+    let interleaved = (0..resampled[0].len()).flat_map(|frame| resampled.iter().map(move |channel| channel[frame]));
+    let (format, bytes_per_sample) = match (file.format, file.bytes_per_sample) {
+        (Format::PulseCodeModulation, 1..=4) | (Format::FloatingPoint, 4 | 8) => (file.format, file.bytes_per_sample),
+        _ => (Format::PulseCodeModulation, 2),
+    };
+    let data: Vec<u8> = match (format, bytes_per_sample) {
+        (Format::PulseCodeModulation, 1) => interleaved.flat_map(|sample| u8::from_sample(sample).to_wav_bytes()).collect(),
+        (Format::PulseCodeModulation, 2) => interleaved.flat_map(|sample| i16::from_sample(sample).to_wav_bytes()).collect(),
+        (Format::PulseCodeModulation, 3) => interleaved.flat_map(|sample| I24::from_sample(sample).to_wav_bytes()).collect(),
+        (Format::PulseCodeModulation, _) => interleaved.flat_map(|sample| i32::from_sample(sample).to_wav_bytes()).collect(),
+        (_, 4) => interleaved.flat_map(|sample| f32::from_sample(sample).to_wav_bytes()).collect(),
+        _ => interleaved.flat_map(SampleExt::to_wav_bytes).collect(),
+    };
+    let mut metadata = file.metadata.clone();
+    let rescale = |position| rescale(position, file.sample_rate, sample_rate);
+    for cue_point in &mut metadata.cue_points {
+        cue_point.position = rescale(cue_point.position);
+        if let Some(region) = &mut cue_point.region {
+            region.length = rescale(region.length);
+        }
+    }
+    if let Some(sampler) = &mut metadata.sampler {
+        sampler.sample_period = u32::try_from(1_000_000_000 / u64::from(sample_rate)).unwrap_or(u32::MAX);
+        for sample_loop in &mut sampler.loops {
+            sample_loop.start = rescale(sample_loop.start);
+            sample_loop.end = rescale(sample_loop.end);
+        }
+    }
+    if let Some(broadcast) = &mut metadata.broadcast {
+        broadcast.time_reference = u64::try_from(u128::from(broadcast.time_reference) * u128::from(sample_rate) / u128::from(file.sample_rate)).unwrap_or(u64::MAX);
+    }
+    Ok(WaveFile {
+        metadata,
+        channel_mask: file.channel_mask,
+        valid_bits_per_sample: if format == file.format { file.valid_bits_per_sample } else { bytes_per_sample * 8 },
+        data: Cow::Owned(data),
+        ..WaveFile::from_raw_data(&[], format, file.channels, sample_rate, bytes_per_sample)
+    })
+}
@@ -0,0 +1,255 @@
+use std::f64::consts::TAU;
+
+use cpal::{FromSample, Sample};
+use thiserror::Error;
+
+use super::generation::Harmonic;
+use crate::{
+    wavefile::{WaveFile, WaveFileDecodeError},
+    Block,
+};
+
+/// The number of samples in each table, which is enough for the 1023 harmonics of the first mip level.
+const TABLE_LEN: usize = 2048;
+/// The number of harmonics in the first mip level. Each level after it has half as many, down to just the fundamental.
+const MAX_HARMONIC: usize = TABLE_LEN / 2 - 1;
+/// The number of mip levels.
+const LEVELS: usize = MAX_HARMONIC.ilog2() as usize + 1;
+
+/// Return the fractional part of a phase, from 0 (inclusive) to 1 (exclusive).
+///
+/// `rem_euclid` rounds a tiny negative phase up to exactly 1, which would read past the end of the table.
+fn wrap(phase: f64) -> f64 {
+    let phase = phase.rem_euclid(1.);
+    if phase < 1. {
+        phase
+    } else {
+        0.
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum WavetableError {
+    #[error("could not decode the file: {0}")]
+    Decode(#[from] WaveFileDecodeError),
+    #[error("there are no samples to make a wavetable from")]
+    Empty,
+}
+
+/// The cosine and sine coefficients of each harmonic of a single cycle, from the first harmonic (the fundamental) up to [`MAX_HARMONIC`].
+type Spectrum = Vec<(f64, f64)>;
+
+/// A set of single-cycle waves (frames) to play and morph between, each stored at several levels of detail.
+///
+/// Each frame has a table for every mip level, with fewer harmonics in each one, so that a [`WavetableOscillator`] can pick the most detailed table that has no harmonics above the
+/// Nyquist frequency at the note it is playing. The constant offset of each frame is removed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Wavetable {
+    /// The tables of each frame, one after another, from the most detailed level to the least. Each table has an extra copy of its first sample at the end, for interpolation.
+    tables: Vec<f32>,
+    frames: usize,
+}
+
+impl Wavetable {
+    /// Build the mip levels of each frame from its spectrum.
+    ///
+    /// Each level is built from the one with fewer harmonics after it, with every harmonic added once from a table of cosines.
+    fn from_spectra(spectra: &[Spectrum]) -> Self {
+        #[allow(clippy::cast_precision_loss, reason = "the table has 2048 samples")]
+        let cosines: Vec<f64> = (0..TABLE_LEN).map(|index| (TAU * index as f64 / TABLE_LEN as f64).cos()).collect();
+        let mut tables = vec![0.; spectra.len() * LEVELS * (TABLE_LEN + 1)];
+        for (spectrum, frame_tables) in spectra.iter().zip(tables.chunks_exact_mut(LEVELS * (TABLE_LEN + 1))) {
+            let mut sum = vec![0.; TABLE_LEN];
+            let mut added = 0;
+            for (level, table) in frame_tables.chunks_exact_mut(TABLE_LEN + 1).enumerate().rev() {
+                let limit = (MAX_HARMONIC >> level).min(spectrum.len());
+                for (harmonic, &(cosine, sine)) in spectrum.iter().enumerate().take(limit).skip(added) {
+                    let harmonic = harmonic + 1;
+                    for (index, sample) in sum.iter_mut().enumerate() {
+                        let position = harmonic * index;
+                        // sin(x) = cos(x - π/2), which is a quarter of the table before.
+                        let sin = cosines[(position + TABLE_LEN * 3 / 4) % TABLE_LEN];
+                        *sample += cosine.mul_add(cosines[position % TABLE_LEN], sine * sin);
+                    }
+                }
+                added = added.max(limit);
+                #[allow(clippy::cast_possible_truncation, reason = "the tables are stored as `f32` to save memory")]
+                for (sample, value) in table.iter_mut().zip(sum.iter().chain(&sum[..1])) {
+                    *sample = *value as f32;
+                }
+            }
+        }
+        Self { tables, frames: spectra.len() }
+    }
+
+    /// Create a [`Wavetable`] with a single frame made of sine wave harmonics, scaled as in [`harmonics`](super::generation::harmonics).
+    ///
+    /// Harmonics past the 1023rd are left out.
+    #[must_use]
+    pub fn from_harmonics<T: Sample>(harmonics: &[Harmonic<T>]) -> Self
+    where
+        f64: FromSample<T>,
+    {
+        let mut spectrum = vec![(0., 0.); MAX_HARMONIC];
+        for harmonic in harmonics.iter().filter(|harmonic| harmonic.index() < MAX_HARMONIC) {
+            #[allow(clippy::cast_precision_loss, reason = "the index is less than `MAX_HARMONIC`")]
+            let amplitude = f64::from_sample(harmonic.amplitude()) / (harmonic.index() as f64 + 1.);
+            spectrum[harmonic.index()].1 += amplitude;
+        }
+        Self::from_spectra(&[spectrum])
+    }
+
+    /// Create a [`Wavetable`] from single cycles of any length, one for each frame.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WavetableError::Empty`] if there are no cycles, or any of them are empty.
+    pub fn from_cycles<C: AsRef<[f64]>>(cycles: &[C]) -> Result<Self, WavetableError> {
+        if cycles.is_empty() || cycles.iter().any(|cycle| cycle.as_ref().is_empty()) {
+            return Err(WavetableError::Empty);
+        }
+        let spectra: Vec<Spectrum> = cycles.iter().map(|cycle| spectrum(cycle.as_ref())).collect();
+        Ok(Self::from_spectra(&spectra))
+    }
+
+    /// Create a [`Wavetable`] from a WAV file, splitting it into frames of `cycle_len` samples, with the channels mixed together.
+    ///
+    /// For a file that holds a single cycle, `cycle_len` is the number of frames in the file. Any incomplete cycle at the end is ignored, unless it is the only one.
+    /// # Errors
+    /// Returns a [`WavetableError::Decode`] if the samples can't be decoded, or [`WavetableError::Empty`] if the file has no samples.
+    /// # Panics
+    /// Panics if `cycle_len` is zero.
+    pub fn from_wave_file(file: &WaveFile, cycle_len: usize) -> Result<Self, WavetableError> {
+        assert!(cycle_len > 0, "cycles must have at least one sample");
+        let channels = usize::from(file.channels.get());
+        let samples: Vec<f64> = file.samples::<f64>()?.collect();
+        #[allow(clippy::cast_precision_loss, reason = "there are at most 65535 channels")]
+        let mono: Vec<f64> = samples.chunks_exact(channels).map(|frame| frame.iter().sum::<f64>() / channels as f64).collect();
+        if mono.len() < cycle_len {
+            return Self::from_cycles(&[mono]);
+        }
+        Self::from_cycles(&mono.chunks_exact(cycle_len).collect::<Vec<_>>())
+    }
+
+    /// Return the number of frames that can be morphed between.
+    #[must_use]
+    pub const fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// Return the most detailed mip level whose harmonics are all below the Nyquist frequency when playing `frequency`.
+    fn level(frequency: f64, sample_rate: u32) -> usize {
+        let harmonics = f64::from(sample_rate) / 2. / frequency.abs();
+        #[allow(clippy::cast_precision_loss, reason = "the limit is at most `MAX_HARMONIC`")]
+        (0..LEVELS).find(|level| (MAX_HARMONIC >> level) as f64 <= harmonics).unwrap_or(LEVELS - 1)
+    }
+
+    /// Return the table of a frame at a mip level.
+    fn table(&self, frame: usize, level: usize) -> &[f32] {
+        let start = (frame * LEVELS + level) * (TABLE_LEN + 1);
+        &self.tables[start..=start + TABLE_LEN]
+    }
+}
+
+/// Return the spectrum of a single cycle, with a discrete Fourier transform at each harmonic below the cycle's Nyquist frequency.
+fn spectrum(cycle: &[f64]) -> Spectrum {
+    let len = cycle.len();
+    #[allow(clippy::cast_precision_loss, reason = "cycles are far shorter than 2^52 samples")]
+    let angles: Vec<(f64, f64)> = (0..len).map(|index| (TAU * index as f64 / len as f64).sin_cos()).collect();
+    #[allow(clippy::cast_precision_loss, reason = "cycles are far shorter than 2^52 samples")]
+    let scale = 2. / len as f64;
+    (1..len.div_ceil(2).min(MAX_HARMONIC + 1))
+        .map(|harmonic| {
+            let (cosine, sine) = cycle.iter().enumerate().fold((0., 0.), |(cosine, sine), (index, sample)| {
+                let (sin, cos) = angles[harmonic * index % len];
+                (sample.mul_add(cos, cosine), sample.mul_add(sin, sine))
+            });
+            (cosine * scale, sine * scale)
+        })
+        .collect()
+}
+
+/// An oscillator that plays a [`Wavetable`], choosing the mip level for its frequency and morphing smoothly between frames.
+///
+/// The table is borrowed, so many voices can share it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WavetableOscillator<'a> {
+    wavetable: &'a Wavetable,
+    frequency: f64,
+    amplitude: f64,
+    sample_rate: u32,
+    /// The position in the current cycle, from 0 (inclusive) to 1 (exclusive).
+    phase: f64,
+    /// The position between the first frame (0) and the last (1).
+    position: f64,
+}
+
+impl<'a> WavetableOscillator<'a> {
+    /// Create a new [`WavetableOscillator`] at the start of its cycle and the first frame.
+    #[must_use]
+    pub const fn new(wavetable: &'a Wavetable, frequency: f64, amplitude: f64, sample_rate: u32) -> Self {
+        Self {
+            wavetable,
+            frequency,
+            amplitude,
+            sample_rate,
+            phase: 0.,
+            position: 0.,
+        }
+    }
+
+    /// Change the frequency in hertz, carrying on from the current phase.
+    pub const fn set_frequency(&mut self, frequency: f64) {
+        self.frequency = frequency;
+    }
+
+    /// Change the amplitude, where 1 is full scale.
+    pub const fn set_amplitude(&mut self, amplitude: f64) {
+        self.amplitude = amplitude;
+    }
+
+    /// Jump to a position in the cycle, e.g. 0 to restart the wave. Only the fractional part is used.
+    pub fn set_phase(&mut self, phase: f64) {
+        self.phase = wrap(phase);
+    }
+
+    /// Move between the frames of the wavetable, from the first (0) to the last (1), which is clamped to that range. Positions between frames mix the two nearest ones.
+    pub const fn set_position(&mut self, position: f64) {
+        self.position = position.clamp(0., 1.);
+    }
+
+    /// Return the next sample, as a linear level where 1 is full scale, and move on to the one after it.
+    pub fn next_sample(&mut self) -> f64 {
+        let level = Wavetable::level(self.frequency, self.sample_rate);
+        #[allow(clippy::cast_precision_loss, reason = "wavetables have far fewer than 2^52 frames")]
+        let frame = self.position * (self.wavetable.frames - 1) as f64;
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "the frame is between 0 and the last frame")]
+        let first = (frame as usize).min(self.wavetable.frames.saturating_sub(2));
+        #[allow(clippy::cast_precision_loss, reason = "the table has 2048 samples")]
+        let index = self.phase * TABLE_LEN as f64;
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, reason = "the phase is between 0 and 1")]
+        let whole = index as usize;
+        let fraction = index.fract();
+        let read = |frame: usize| {
+            let table = self.wavetable.table(frame, level);
+            (f64::from(table[whole + 1]) - f64::from(table[whole])).mul_add(fraction, f64::from(table[whole]))
+        };
+        let sample = if self.wavetable.frames == 1 {
+            read(0)
+        } else {
+            #[allow(clippy::cast_precision_loss, reason = "wavetables have far fewer than 2^52 frames")]
+            let blend = frame - first as f64;
+            let from = read(first);
+            (read(first + 1) - from).mul_add(blend, from)
+        };
+        self.phase = wrap(self.phase + self.frequency / f64::from(self.sample_rate));
+        sample * self.amplitude
+    }
+
+    /// Fill a buffer with the next samples, with the same wave in every channel.
+    pub fn fill<T: Sample + FromSample<f64>, const N: usize>(&mut self, blocks: &mut [Block<T, N>]) {
+        for block in blocks {
+            *block = Block([T::from_sample(self.next_sample()); N]);
+        }
+    }
+}
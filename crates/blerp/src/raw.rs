@@ -0,0 +1,175 @@
+use std::{
+    borrow::Cow,
+    io::{self, Read, Write},
+    num::NonZeroU16,
+};
+
+use thiserror::Error;
+
+use crate::wavefile::{Format, WaveFile};
+
+/// The order of the bytes in each sample of a raw file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ByteOrder {
+    #[default]
+    LittleEndian,
+    BigEndian,
+}
+
+/// How the samples are laid out in a headerless raw file, for [`parse`], [`read`] and [`write`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawOptions {
+    pub byte_order: ByteOrder,
+    /// Whether integer samples are signed. WAV has unsigned 8-bit samples and signed wider ones, so any other combination is converted.
+    pub signed: bool,
+    /// Whether each channel is stored one after the other (e.g. all of the left samples, then all of the right samples) instead of interleaved frame by frame.
+    pub planar: bool,
+    /// The number of bytes before the samples, e.g. for a header that isn't understood. These are skipped when reading, and written as zeros when writing.
+    pub offset: usize,
+}
+
+impl Default for RawOptions {
+    fn default() -> Self {
+        Self {
+            byte_order: ByteOrder::LittleEndian,
+            signed: true,
+            planar: false,
+            offset: 0,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum RawReadError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("offset {offset} is past the end of the {len}-byte file")]
+    OffsetOutOfRange { offset: usize, len: usize },
+    #[error("unsupported sample format: {bytes_per_sample}-byte {format:?}")]
+    UnsupportedSampleFormat { format: Format, bytes_per_sample: u16 },
+}
+
+#[derive(Error, Debug)]
+pub enum RawWriteError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("unsupported sample format: {bytes_per_sample}-byte {format:?}")]
+    UnsupportedSampleFormat { format: Format, bytes_per_sample: u16 },
+}
+
+/// Return whether raw samples of the format can be converted, which rules out IMA ADPCM (which has no standard raw layout) and sizes that [`WaveFile::frames`] can't decode.
+const fn is_supported(format: Format, bytes_per_sample: u16) -> bool {
+    matches!(
+        (format, bytes_per_sample),
+        (Format::PulseCodeModulation, 1..=4) | (Format::FloatingPoint, 4 | 8) | (Format::ALaw | Format::MuLaw, 1)
+    )
+}
+
+/// Return whether the sign bit of each sample has to be flipped to convert between the raw file and a [`WaveFile`].
+fn flips_sign(format: Format, bytes_per_sample: u16, options: &RawOptions) -> bool {
+    format == Format::PulseCodeModulation && options.signed != (bytes_per_sample > 1)
+}
+
+/// Rearrange `frames` frames of `channels` samples each between interleaved and planar layouts.
+fn transpose(data: &[u8], bytes_per_sample: usize, channels: usize, frames: usize, to_planar: bool) -> Vec<u8> {
+    let mut transposed = vec![0; data.len()];
+    for frame in 0..frames {
+        for channel in 0..channels {
+            let interleaved = (frame * channels + channel) * bytes_per_sample;
+            let planar = (channel * frames + frame) * bytes_per_sample;
+            let (from, to) = if to_planar { (interleaved, planar) } else { (planar, interleaved) };
+            transposed[to..to + bytes_per_sample].copy_from_slice(&data[from..from + bytes_per_sample]);
+        }
+    }
+    transposed
+}
+
+/// Parse headerless samples from `bytes`, converting them to a [`WaveFile`]'s little-endian, interleaved layout.
+///
+/// The format, channels, sample rate and sample size are given separately, as in [`WaveFile::from_raw_data`]. The samples are borrowed from `bytes` if they are already laid out as
+/// a [`WaveFile`] expects them, and any incomplete frame at the end is ignored.
+/// # Errors
+/// Returns [`RawReadError::OffsetOutOfRange`] if the offset is past the end of `bytes`, or [`RawReadError::UnsupportedSampleFormat`] if the samples are not 8/16/24/32-bit integers,
+/// 32/64-bit floating point or 8-bit G.711.
+pub fn parse<'a>(bytes: &'a [u8], format: Format, channels: NonZeroU16, sample_rate: u32, bytes_per_sample: u16, options: &RawOptions) -> Result<WaveFile<'a>, RawReadError> {
+    if !is_supported(format, bytes_per_sample) {
+        return Err(RawReadError::UnsupportedSampleFormat { format, bytes_per_sample });
+    }
+    let samples = bytes.get(options.offset..).ok_or(RawReadError::OffsetOutOfRange {
+        offset: options.offset,
+        len: bytes.len(),
+    })?;
+    let sample_len = usize::from(bytes_per_sample);
+    let frames = samples.len() / (sample_len * usize::from(channels.get()));
+    let samples = &samples[..frames * sample_len * usize::from(channels.get())];
+    let flip = flips_sign(format, bytes_per_sample, options);
+    let swap = options.byte_order == ByteOrder::BigEndian && sample_len > 1;
+    let planar = options.planar && channels.get() > 1;
+    if !flip && !swap && !planar {
+        return Ok(WaveFile::from_raw_data(samples, format, channels, sample_rate, bytes_per_sample));
+    }
+    let mut data = if planar {
+        transpose(samples, sample_len, usize::from(channels.get()), frames, false)
+    } else {
+        samples.to_vec()
+    };
+    for sample in data.chunks_exact_mut(sample_len) {
+        if swap {
+            sample.reverse();
+        }
+        if flip {
+            sample[sample_len - 1] ^= 0x80;
+        }
+    }
+    Ok(WaveFile {
+        data: Cow::Owned(data),
+        ..WaveFile::from_raw_data(&[], format, channels, sample_rate, bytes_per_sample)
+    })
+}
+
+/// Read headerless samples from a reader, until the end of the reader, as with [`parse`].
+/// # Errors
+/// Returns a [`RawReadError::Io`] if reading from the reader fails, or any other [`RawReadError`] that [`parse`] returns.
+pub fn read(reader: &mut impl Read, format: Format, channels: NonZeroU16, sample_rate: u32, bytes_per_sample: u16, options: &RawOptions) -> Result<WaveFile<'static>, RawReadError> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    let file = parse(&bytes, format, channels, sample_rate, bytes_per_sample, options)?;
+    Ok(WaveFile {
+        data: Cow::Owned(file.data.into_owned()),
+        ..file
+    })
+}
+
+/// Write the samples of a [`WaveFile`] without a header, converting them to the layout in `options`. Any incomplete frame at the end of the data is left out.
+///
+/// Nothing but the samples is written, so the format, channels, sample rate and sample size need to be known to read the file back.
+/// # Errors
+/// Returns a [`RawWriteError::Io`] if writing to the writer fails, or [`RawWriteError::UnsupportedSampleFormat`] for the same formats that [`parse`] rejects.
+pub fn write(file: &WaveFile, writer: &mut impl Write, options: &RawOptions) -> Result<(), RawWriteError> {
+    if !is_supported(file.format, file.bytes_per_sample) {
+        return Err(RawWriteError::UnsupportedSampleFormat {
+            format: file.format,
+            bytes_per_sample: file.bytes_per_sample,
+        });
+    }
+    let sample_len = usize::from(file.bytes_per_sample);
+    let channels = usize::from(file.channels.get());
+    let frames = file.data.len() / (sample_len * channels);
+    let mut data = file.data[..frames * sample_len * channels].to_vec();
+    let flip = flips_sign(file.format, file.bytes_per_sample, options);
+    let swap = options.byte_order == ByteOrder::BigEndian && sample_len > 1;
+    for sample in data.chunks_exact_mut(sample_len) {
+        if flip {
+            sample[sample_len - 1] ^= 0x80;
+        }
+        if swap {
+            sample.reverse();
+        }
+    }
+    if options.planar && channels > 1 {
+        data = transpose(&data, sample_len, channels, frames, true);
+    }
+    io::copy(&mut io::repeat(0).take(options.offset as u64), writer)?;
+    writer.write_all(&data)?;
+    Ok(())
+}
@@ -2,23 +2,45 @@ use std::{
     borrow::Cow,
     fmt::Debug,
     io::{self, Write},
-    mem::size_of,
     num::NonZeroU16,
 };
 
 use cpal::{Sample, I24, I48, U24, U48};
 use itertools::Itertools;
-use num::traits::ToBytes;
 use thiserror::Error;
 
 use crate::Block;
 
+mod codec;
+mod decode;
+mod format;
+mod mapped;
+mod markers;
+mod metadata;
+mod read;
+mod writer;
+
+pub use codec::Codec;
+pub use decode::FromWaveSample;
+pub use format::{ChannelMask, WAVE_FORMAT_EXTENSIBLE};
+pub use mapped::MappedWaveFile;
+pub use markers::{CuePoint, LoopKind, Region, SampleLoop, Sampler};
+pub use metadata::{BroadcastExtension, Chunk, Info, Metadata};
+pub use writer::WaveWriter;
+
 #[derive(Debug, Clone)]
 pub struct WaveFile<'a> {
     pub format: Format,
     pub channels: NonZeroU16,
     pub sample_rate: u32,
+    /// The size of each sample, in bytes. For IMA ADPCM, where samples are packed into blocks, this is the size of each channel's part of a block instead.
     pub bytes_per_sample: u16,
+    /// The number of bits in each sample that hold audio, which can be less than the `bytes_per_sample` container (e.g. 24-bit samples padded to 32 bits).
+    pub valid_bits_per_sample: u16,
+    pub channel_mask: ChannelMask,
+    pub metadata: Metadata,
+    /// The number of frames from the `fact` chunk, which is only needed for IMA ADPCM, where the last block can be padded with extra frames.
+    pub total_frames: Option<u64>,
     pub data: Cow<'a, [u8]>,
 }
 
@@ -27,6 +49,12 @@ pub struct WaveFile<'a> {
 pub enum Format {
     PulseCodeModulation = 1,
     FloatingPoint = 3,
+    /// G.711 A-law, with 8-bit samples.
+    ALaw = 6,
+    /// G.711 µ-law, with 8-bit samples.
+    MuLaw = 7,
+    /// IMA (DVI) ADPCM, with 4-bit samples packed into blocks.
+    ImaAdpcm = 0x11,
 }
 
 #[derive(Error, Debug)]
@@ -35,83 +63,108 @@ pub enum WaveFileWriteError {
     Io(#[from] io::Error),
     #[error("data too long")]
     DataTooLong,
+    #[error("invalid number of channels")]
+    InvalidChannels,
+    #[error("invalid block alignment")]
+    InvalidBlockAlign,
+}
+
+#[derive(Error, Debug)]
+pub enum WaveFileReadError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("not a RIFF/WAVE file")]
+    BadMagic,
+    #[error("`{}` chunk is truncated", .0.escape_ascii())]
+    TruncatedChunk([u8; 4]),
+    #[error("missing `{}` chunk", .0.escape_ascii())]
+    MissingChunk([u8; 4]),
+    #[error("unsupported format tag {0:#06x}")]
+    UnsupportedFormat(u16),
+    #[error("unsupported sub-format GUID {0:02x?}")]
+    UnsupportedSubFormat([u8; 16]),
+    #[error("invalid `fmt ` chunk")]
+    InvalidFormatChunk,
+    #[error("invalid block alignment {0} for the format")]
+    InvalidBlockAlign(u16),
+}
+
+#[derive(Error, Debug)]
+pub enum WaveFileDecodeError {
+    #[error("expected {expected} channels, found {found}")]
+    ChannelMismatch { expected: usize, found: NonZeroU16 },
+    #[error("unsupported sample format: {bytes_per_sample}-byte {format:?}")]
+    UnsupportedSampleFormat { format: Format, bytes_per_sample: u16 },
 }
 
-pub trait SampleExt<Unsigned = Self>: Sized + Sample {
+/// A sample type that can be written to a [`WaveFile`].
+///
+/// WAV only defines 8-bit unsigned, 16/24/32-bit signed and 32/64-bit floating point samples, so other types are converted to the closest of those: signed 8-bit samples become
+/// unsigned, unsigned wider samples become signed, and 48/64-bit integer samples become 32-bit.
+pub trait SampleExt: Sample {
+    /// The format that the samples are stored in.
     const SAMPLE_FORMAT: Format;
-    const BYTES_PER_SAMPLE: u16 = {
-        assert!(size_of::<Self>() <= u16::MAX as usize);
-        #[allow(clippy::cast_possible_truncation, reason = "we just checked it")]
-        {
-            size_of::<Self>() as u16
-        }
-    };
+    /// The size of each stored sample, in bytes.
+    const BYTES_PER_SAMPLE: u16;
+    /// The little-endian bytes of a stored sample.
+    type Bytes: AsRef<[u8]> + IntoIterator<Item = u8>;
 
-    fn to_wav_sample(self) -> Unsigned;
+    /// Convert the sample into the stored format, returning its little-endian bytes.
+    fn to_wav_bytes(self) -> Self::Bytes;
 }
 
 macro_rules! impl_sample_ext {
-    (@to_wav_sample) => {
-        fn to_wav_sample(self) -> Self {
-            self
-        }
-    };
-    (@to_wav_sample $ty:ty) => {
-        fn to_wav_sample(self) -> $ty {
-            use cpal::{Sample};
-            <$ty>::from_sample(self)
-        }
+    (@to_wav_bytes I24, $sample:expr) => {{
+        let [a, b, c, _] = I24::from_sample($sample).inner().to_le_bytes();
+        [a, b, c]
+    }};
+    (@to_wav_bytes $wav:ty, $sample:expr) => {
+        <$wav>::from_sample($sample).to_le_bytes()
     };
-    (@signed_impl) => {};
-    (@signed_impl $unsigned:ty, $ty:ty, $format:ident) => {
-        impl SampleExt for $ty {
-            const SAMPLE_FORMAT: Format = Format::$format;
-            impl_sample_ext!(@to_wav_sample);
-        }
-    };
-    ($($ty:ty, $format:ident $(,$unsigned:ty)?;)+) => {
+    ($($ty:ty => $wav:tt, $format:ident, $bytes:literal;)+) => {
         $(
-            impl SampleExt $(< $unsigned >)? for $ty {
+            impl SampleExt for $ty {
                 const SAMPLE_FORMAT: Format = Format::$format;
-                impl_sample_ext!(@to_wav_sample $($unsigned)?);
+                const BYTES_PER_SAMPLE: u16 = $bytes;
+                type Bytes = [u8; $bytes];
+
+                fn to_wav_bytes(self) -> Self::Bytes {
+                    impl_sample_ext!(@to_wav_bytes $wav, self)
+                }
             }
-            impl_sample_ext!(@signed_impl $($unsigned, $ty, $format)?);
         )+
     };
 }
 
 impl_sample_ext! {
-    f32, FloatingPoint;
-    f64, FloatingPoint;
-    i8, PulseCodeModulation;
-    i16, PulseCodeModulation;
-    i32, PulseCodeModulation;
-    i64, PulseCodeModulation;
-    u8, PulseCodeModulation;
-    u16, PulseCodeModulation, i16;
-    u32, PulseCodeModulation, i32;
-    u64, PulseCodeModulation, i64;
-    I24, PulseCodeModulation, U24;
-    I48, PulseCodeModulation, U48;
-    U24, PulseCodeModulation, I24;
-    U48, PulseCodeModulation, I48;
+    f32 => f32, FloatingPoint, 4;
+    f64 => f64, FloatingPoint, 8;
+    u8 => u8, PulseCodeModulation, 1;
+    i8 => u8, PulseCodeModulation, 1;
+    i16 => i16, PulseCodeModulation, 2;
+    u16 => i16, PulseCodeModulation, 2;
+    I24 => I24, PulseCodeModulation, 3;
+    U24 => I24, PulseCodeModulation, 3;
+    i32 => i32, PulseCodeModulation, 4;
+    u32 => i32, PulseCodeModulation, 4;
+    I48 => i32, PulseCodeModulation, 4;
+    U48 => i32, PulseCodeModulation, 4;
+    i64 => i32, PulseCodeModulation, 4;
+    u64 => i32, PulseCodeModulation, 4;
 }
 
 impl<'a> WaveFile<'a> {
     /// Create a new [`WaveFile`] from an iterable of samples and a sample rate, or [`None`] if the number of channels does not fit in a [`NonZeroU16`], because it was zero or more than [`u16::MAX`].
-    /// # Panics
-    /// Panics if the size of the sample type is too large to fit in a [`u16`], because there were more than [`u16::MAX`] channels.
-    pub fn from_samples<T: SampleExt + ToBytes<Bytes = [u8; BYTES]> + Debug, const BYTES: usize, const N: usize, S: Into<Block<T, N>>>(
-        samples: impl IntoIterator<Item = S>,
-        sample_rate: u32,
-    ) -> Option<Self> {
+    ///
+    /// The samples are converted to a format that WAV defines, as described in [`SampleExt`].
+    pub fn from_samples<T: SampleExt, const N: usize, S: Into<Block<T, N>>>(samples: impl IntoIterator<Item = S>, sample_rate: u32) -> Option<Self> {
         let format = T::SAMPLE_FORMAT;
         let channels = NonZeroU16::new(u16::try_from(N).ok()?)?;
-        let bytes_per_sample = u16::try_from(size_of::<T>()).expect("size of sample type is too large");
+        let bytes_per_sample = T::BYTES_PER_SAMPLE;
         let data = samples
             .into_iter()
             .map_into()
-            .flat_map(|Block(channels)| channels.map(|sample| sample.to_wav_sample().to_le_bytes()))
+            .flat_map(|Block(channels)| channels.map(SampleExt::to_wav_bytes))
             .flatten()
             .collect_vec()
             .into();
@@ -120,6 +173,10 @@ impl<'a> WaveFile<'a> {
             channels,
             sample_rate,
             bytes_per_sample,
+            valid_bits_per_sample: bytes_per_sample * 8,
+            channel_mask: ChannelMask::default_for_channels(channels.get()),
+            metadata: Metadata::default(),
+            total_frames: None,
             data,
         })
     }
@@ -131,45 +188,75 @@ impl<'a> WaveFile<'a> {
             channels,
             sample_rate,
             bytes_per_sample,
+            valid_bits_per_sample: bytes_per_sample * 8,
+            channel_mask: ChannelMask::default_for_channels(channels.get()),
+            metadata: Metadata::default(),
+            total_frames: None,
             data: data.into(),
         }
     }
 
     /// Write the [`WaveFile`] to a writer.
+    ///
+    /// A `WAVE_FORMAT_EXTENSIBLE` `fmt ` chunk is written if [`WaveFile::is_extensible`] returns `true`, and an RF64 file is written (see [`WaveFile::write_rf64`]) if the file would be
+    /// too big for the 32-bit sizes in a RIFF file.
     /// # Errors
-    /// Returns an [`WaveFileWriteError::Io`] if writing to the writer fails (from calls to [`Write::write_all`]), or [`WaveFileWriteError::DataTooLong`] if the data was longer than [`u32::MAX`]
-    /// bytes.
+    /// Returns an [`WaveFileWriteError::Io`] if writing to the writer fails (from calls to [`Write::write_all`]), [`WaveFileWriteError::DataTooLong`] if a metadata chunk is longer than
+    /// [`u32::MAX`] bytes, or [`WaveFileWriteError::InvalidBlockAlign`] if the data isn't made of whole blocks of the format (see [`WaveFile::block_align`]).
     pub fn write(&self, writer: &mut impl Write) -> Result<(), WaveFileWriteError> {
-        const RIFF_DATA_LEN_PCM: usize = 4 + 4 + 4 + 2 + 2 + 4 + 4 + 2 + 2 + 4 + 4;
-        const RIFF_DATA_LEN_FLOAT: usize = RIFF_DATA_LEN_PCM + 2 + 4 + 4 + 4;
-        writer.write_all(b"RIFF")?;
-        writer.write_all(
-            &u32::try_from(if self.format == Format::FloatingPoint { RIFF_DATA_LEN_FLOAT } else { RIFF_DATA_LEN_PCM } + self.data.len())
-                .map_err(|_| WaveFileWriteError::DataTooLong)?
-                .to_le_bytes(),
-        )?;
-        writer.write_all(b"WAVEfmt ")?;
-        writer.write_all(&if self.format == Format::FloatingPoint { 18_u32 } else { 16_u32 }.to_le_bytes())?;
-        writer.write_all(&(self.format as u16).to_le_bytes())?;
-        writer.write_all(&self.channels.get().to_le_bytes())?;
-        writer.write_all(&self.sample_rate.to_le_bytes())?;
-        writer.write_all(&(self.sample_rate * u32::from(self.channels.get()) * u32::from(self.bytes_per_sample)).to_le_bytes())?;
-        writer.write_all(&(self.bytes_per_sample).to_le_bytes())?;
-        writer.write_all(&(self.bytes_per_sample * 8).to_le_bytes())?;
-        if self.format == Format::FloatingPoint {
-            writer.write_all(&0_u16.to_le_bytes())?;
+        self.write_with(writer, false)
+    }
 
+    /// Write the [`WaveFile`] to a writer as an RF64 file, which stores the sizes of the file and its `data` chunk in a `ds64` chunk as 64-bit integers.
+    ///
+    /// [`WaveFile::write`] switches to this automatically when it is needed, so this is only useful for software that expects RF64 regardless of size.
+    /// # Errors
+    /// Returns an [`WaveFileWriteError`] for the same reasons as [`WaveFile::write`].
+    pub fn write_rf64(&self, writer: &mut impl Write) -> Result<(), WaveFileWriteError> {
+        self.write_with(writer, true)
+    }
+
+    fn write_with(&self, writer: &mut impl Write, rf64: bool) -> Result<(), WaveFileWriteError> {
+        const DS64_LEN: usize = 8 + 8 + 8 + 8 + 4;
+        self.check_block_align()?;
+        let format_chunk = self.format_chunk();
+        let metadata_chunks = self.metadata.to_chunks()?;
+        // Formats other than PCM need a `fact` chunk holding the number of frames.
+        let frames = self.frame_count();
+        let fact = self.format != Format::PulseCodeModulation;
+        let padding = self.data.len() % 2;
+        let riff_len = 4 + 8 + format_chunk.len() + if fact { 8 + 4 } else { 0 } + metadata_chunks.len() + 8 + self.data.len() + padding;
+        let rf64 = rf64 || u32::try_from(riff_len).is_err();
+        // In an RF64 file, the 32-bit sizes are all `0xFFFFFFFF` and the real sizes are in the `ds64` chunk.
+        let to_u32 = |len: usize| if rf64 { u32::MAX } else { u32::try_from(len).unwrap_or(u32::MAX) };
+        if rf64 {
+            writer.write_all(b"RF64")?;
+            writer.write_all(&u32::MAX.to_le_bytes())?;
+            writer.write_all(b"WAVE")?;
+            writer.write_all(b"ds64")?;
+            writer.write_all(&28_u32.to_le_bytes())?;
+            writer.write_all(&((riff_len + DS64_LEN) as u64).to_le_bytes())?;
+            writer.write_all(&(self.data.len() as u64).to_le_bytes())?;
+            writer.write_all(&(frames as u64).to_le_bytes())?;
+            writer.write_all(&0_u32.to_le_bytes())?;
+        } else {
+            writer.write_all(b"RIFF")?;
+            writer.write_all(&to_u32(riff_len).to_le_bytes())?;
+            writer.write_all(b"WAVE")?;
+        }
+        writer.write_all(b"fmt ")?;
+        writer.write_all(&u32::try_from(format_chunk.len()).map_err(|_| WaveFileWriteError::DataTooLong)?.to_le_bytes())?;
+        writer.write_all(&format_chunk)?;
+        if fact {
             writer.write_all(b"fact")?;
             writer.write_all(&4_u32.to_le_bytes())?;
-            writer.write_all(
-                &u32::try_from(self.data.len() / self.bytes_per_sample as usize)
-                    .map_err(|_| WaveFileWriteError::DataTooLong)?
-                    .to_le_bytes(),
-            )?;
+            writer.write_all(&to_u32(frames).to_le_bytes())?;
         }
+        writer.write_all(&metadata_chunks)?;
         writer.write_all(b"data")?;
-        writer.write_all(&u32::try_from(self.data.len()).map_err(|_| WaveFileWriteError::DataTooLong)?.to_le_bytes())?;
+        writer.write_all(&to_u32(self.data.len()).to_le_bytes())?;
         writer.write_all(&self.data)?;
+        writer.write_all(&[0; 1][..padding])?;
         Ok(())
     }
 }
@@ -0,0 +1,247 @@
+use std::num::NonZeroU16;
+
+use cpal::{FromSample, Sample};
+use itertools::Itertools;
+
+use super::{format::adpcm_frames_per_block, ChannelMask, Format, Metadata, WaveFile, WaveFileWriteError};
+use crate::Block;
+
+/// A compressed format to store samples in, for [`WaveFile::encode`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    /// G.711 A-law, which stores each sample in 8 bits with the precision of 13-bit samples near silence.
+    ALaw,
+    /// G.711 µ-law, which stores each sample in 8 bits with the precision of 14-bit samples near silence.
+    MuLaw,
+    /// IMA ADPCM, which stores each sample in 4 bits, in blocks of `frames_per_block` frames that can each be decoded on their own. This must be at least 9 and one more than a multiple
+    /// of 8, and is usually 505 (for 256-byte mono blocks) or 1017 (for 512-byte mono blocks).
+    ImaAdpcm { frames_per_block: u16 },
+}
+
+/// The largest magnitude that µ-law can store, before the bias is added.
+const MU_LAW_CLIP: i16 = 32635;
+/// The bias that µ-law adds to every magnitude, so that the segments all start at a power of two.
+const MU_LAW_BIAS: i16 = 0x84;
+
+/// Decode a G.711 A-law byte into a 16-bit sample.
+pub(super) const fn alaw_to_linear(byte: u8) -> i16 {
+    let byte = byte ^ 0x55;
+    let segment = (byte >> 4) & 0x7;
+    let mantissa = ((byte & 0xF) as i16) << 4;
+    let magnitude = match segment {
+        0 => mantissa + 8,
+        _ => (mantissa + 0x108) << (segment - 1),
+    };
+    if byte & 0x80 == 0 {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
+
+/// Encode a 16-bit sample as a G.711 A-law byte, which only keeps the top 13 bits.
+fn linear_to_alaw(sample: i16) -> u8 {
+    let sample = sample >> 3;
+    let (magnitude, mask) = if sample >= 0 { (sample, 0xD5) } else { (-sample - 1, 0x55) };
+    // The segment is the position of the highest set bit, where the first two segments share a step size.
+    let segment = (16 - magnitude.leading_zeros()).saturating_sub(5);
+    if segment >= 8 {
+        return 0x7F ^ mask;
+    }
+    let shift = segment.max(1);
+    let mantissa = (magnitude >> shift).to_le_bytes()[0] & 0xF;
+    let segment = u8::try_from(segment).unwrap_or_default();
+    (segment << 4 | mantissa) ^ mask
+}
+
+/// Decode a G.711 µ-law byte into a 16-bit sample.
+pub(super) const fn mulaw_to_linear(byte: u8) -> i16 {
+    let byte = !byte;
+    let magnitude = ((((byte & 0xF) as i16) << 3) + MU_LAW_BIAS) << ((byte >> 4) & 0x7);
+    if byte & 0x80 == 0 {
+        magnitude - MU_LAW_BIAS
+    } else {
+        MU_LAW_BIAS - magnitude
+    }
+}
+
+/// Encode a 16-bit sample as a G.711 µ-law byte.
+fn linear_to_mulaw(sample: i16) -> u8 {
+    let (magnitude, mask) = if sample < 0 { (sample.saturating_neg(), 0x7F) } else { (sample, 0xFF) };
+    let magnitude = magnitude.min(MU_LAW_CLIP) + MU_LAW_BIAS;
+    // The bias puts the highest set bit at position 7 or above, and the segment counts up from there.
+    let segment = 15 - magnitude.leading_zeros() - 7;
+    let mantissa = (magnitude >> (segment + 3)).to_le_bytes()[0] & 0xF;
+    let segment = u8::try_from(segment).unwrap_or_default();
+    (segment << 4 | mantissa) ^ mask
+}
+
+/// How much the step index changes after each IMA ADPCM sample, by its magnitude (the lowest 3 bits).
+const INDEX_TABLE: [isize; 8] = [-1, -1, -1, -1, 2, 4, 6, 8];
+
+/// The IMA ADPCM step sizes, which grow by roughly 10% each.
+const STEP_TABLE: [i32; 89] = [
+    7, 8, 9, 10, 11, 12, 13, 14, 16, 17, 19, 21, 23, 25, 28, 31, 34, 37, 41, 45, 50, 55, 60, 66, 73, 80, 88, 97, 107, 118, 130, 143, 157, 173, 190, 209, 230, 253, 279, 307, 337, 371, 408, 449, 494,
+    544, 598, 658, 724, 796, 876, 963, 1060, 1166, 1282, 1411, 1552, 1707, 1878, 2066, 2272, 2499, 2749, 3024, 3327, 3660, 4026, 4428, 4871, 5358, 5894, 6484, 7132, 7845, 8630, 9493, 10442, 11487,
+    12635, 13899, 15289, 16818, 18500, 20350, 22385, 24623, 27086, 29794, 32767,
+];
+
+/// The state of one channel of an IMA ADPCM encoder or decoder, which is reset at the start of each block.
+#[derive(Debug, Clone, Copy, Default)]
+struct AdpcmChannel {
+    predictor: i16,
+    index: usize,
+}
+
+impl AdpcmChannel {
+    /// Read the state from a channel's 4-byte block header: the first sample and the step index.
+    fn from_header(header: &[u8]) -> Self {
+        Self {
+            predictor: i16::from_le_bytes([header[0], header[1]]),
+            index: usize::from(header[2]).min(STEP_TABLE.len() - 1),
+        }
+    }
+
+    fn header(self) -> [u8; 4] {
+        let [low, high] = self.predictor.to_le_bytes();
+        [low, high, u8::try_from(self.index).unwrap_or_default(), 0]
+    }
+
+    /// Decode a 4-bit sample, where the top bit is the sign and the rest are the difference from the previous sample in steps.
+    fn decode(&mut self, nibble: u8) -> i16 {
+        let step = STEP_TABLE[self.index];
+        let mut difference = step >> 3;
+        for (bit, shift) in [(4, 0), (2, 1), (1, 2)] {
+            if nibble & bit != 0 {
+                difference += step >> shift;
+            }
+        }
+        let predictor = if nibble & 8 == 0 {
+            i32::from(self.predictor) + difference
+        } else {
+            i32::from(self.predictor) - difference
+        };
+        #[allow(clippy::cast_possible_truncation, reason = "the predictor is clamped to the range of an `i16`")]
+        let predictor = predictor.clamp(i16::MIN.into(), i16::MAX.into()) as i16;
+        self.predictor = predictor;
+        self.index = self.index.saturating_add_signed(INDEX_TABLE[usize::from(nibble & 7)]).min(STEP_TABLE.len() - 1);
+        predictor
+    }
+
+    /// Encode a sample as the 4-bit difference that brings the decoder closest to it, updating the state to match the decoder's.
+    fn encode(&mut self, sample: i16) -> u8 {
+        let mut difference = i32::from(sample) - i32::from(self.predictor);
+        let mut nibble = 0;
+        if difference < 0 {
+            nibble = 8;
+            difference = -difference;
+        }
+        let mut step = STEP_TABLE[self.index];
+        for bit in [4, 2, 1] {
+            if difference >= step {
+                nibble |= bit;
+                difference -= step;
+            }
+            step >>= 1;
+        }
+        self.decode(nibble);
+        nibble
+    }
+}
+
+/// Decode an IMA ADPCM block into interleaved samples. Each channel has a 4-byte header, followed by groups of 4 bytes for each channel in turn, each holding 8 samples (low nibble
+/// first).
+pub(super) fn decode_adpcm_block(block: &[u8], channels: usize) -> Vec<i16> {
+    if block.len() < 4 * channels {
+        return Vec::new();
+    }
+    let mut states = (0..channels).map(|channel| AdpcmChannel::from_header(&block[channel * 4..])).collect_vec();
+    let groups = block[4 * channels..].chunks_exact(4 * channels);
+    let mut samples = vec![0; (1 + groups.len() * 8) * channels];
+    for (channel, state) in states.iter().enumerate() {
+        samples[channel] = state.predictor;
+    }
+    for (group_index, group) in groups.enumerate() {
+        for (channel, state) in states.iter_mut().enumerate() {
+            for (index, byte) in group[channel * 4..channel * 4 + 4].iter().enumerate() {
+                for (nibble_index, nibble) in [byte & 0xF, byte >> 4].into_iter().enumerate() {
+                    let frame = 1 + group_index * 8 + index * 2 + nibble_index;
+                    samples[frame * channels + channel] = state.decode(nibble);
+                }
+            }
+        }
+    }
+    samples
+}
+
+/// Encode frames as IMA ADPCM blocks, padding the last block with silence. The step index carries over from one block to the next, but the first sample of each block is stored whole.
+fn encode_adpcm<const N: usize>(frames: impl Iterator<Item = [i16; N]>, frames_per_block: usize, data: &mut Vec<u8>) -> u64 {
+    let mut channels = [AdpcmChannel::default(); N];
+    let mut total_frames = 0;
+    for block in &frames.chunks(frames_per_block) {
+        let mut block = block.collect_vec();
+        total_frames += block.len() as u64;
+        block.resize(frames_per_block, [0; N]);
+        for (channel, state) in channels.iter_mut().enumerate() {
+            state.predictor = block[0][channel];
+            data.extend(state.header());
+        }
+        for group in block[1..].chunks_exact(8) {
+            for (channel, state) in channels.iter_mut().enumerate() {
+                data.extend(group.chunks_exact(2).map(|pair| state.encode(pair[0][channel]) | state.encode(pair[1][channel]) << 4));
+            }
+        }
+    }
+    total_frames
+}
+
+impl WaveFile<'_> {
+    /// Create a new [`WaveFile`] from an iterable of samples and a sample rate, converting the samples to 16-bit and compressing them with a [`Codec`].
+    ///
+    /// For IMA ADPCM, the number of frames is stored in [`WaveFile::total_frames`], so that the silence padding the last block is not decoded.
+    /// # Errors
+    /// Returns [`WaveFileWriteError::InvalidChannels`] if `N` does not fit in a [`NonZeroU16`], or [`WaveFileWriteError::InvalidBlockAlign`] if the number of frames in each IMA ADPCM
+    /// block is less than 9, is not one more than a multiple of 8, or makes the blocks longer than [`u16::MAX`] bytes.
+    pub fn encode<T: Sample, const N: usize, S: Into<Block<T, N>>>(samples: impl IntoIterator<Item = S>, sample_rate: u32, codec: Codec) -> Result<Self, WaveFileWriteError>
+    where
+        i16: FromSample<T>,
+    {
+        let channels = u16::try_from(N).ok().and_then(NonZeroU16::new).ok_or(WaveFileWriteError::InvalidChannels)?;
+        let frames = samples.into_iter().map(|frame| {
+            let Block(samples) = frame.into();
+            samples.map(i16::from_sample)
+        });
+        let mut data = Vec::new();
+        let (format, bytes_per_sample, valid_bits_per_sample, total_frames) = match codec {
+            Codec::ALaw => {
+                data.extend(frames.flatten().map(linear_to_alaw));
+                (Format::ALaw, 1, 8, None)
+            }
+            Codec::MuLaw => {
+                data.extend(frames.flatten().map(linear_to_mulaw));
+                (Format::MuLaw, 1, 8, None)
+            }
+            Codec::ImaAdpcm { frames_per_block } => {
+                if frames_per_block < 9 || !(frames_per_block - 1).is_multiple_of(8) {
+                    return Err(WaveFileWriteError::InvalidBlockAlign);
+                }
+                let bytes_per_channel = 4 + (frames_per_block - 1) / 2;
+                bytes_per_channel.checked_mul(channels.get()).ok_or(WaveFileWriteError::InvalidBlockAlign)?;
+                debug_assert_eq!(adpcm_frames_per_block(bytes_per_channel), usize::from(frames_per_block));
+                let total_frames = encode_adpcm(frames, usize::from(frames_per_block), &mut data);
+                (Format::ImaAdpcm, bytes_per_channel, 4, Some(total_frames))
+            }
+        };
+        Ok(Self {
+            format,
+            channels,
+            sample_rate,
+            bytes_per_sample,
+            valid_bits_per_sample,
+            channel_mask: ChannelMask::default_for_channels(channels.get()),
+            metadata: Metadata::default(),
+            total_frames,
+            data: data.into(),
+        })
+    }
+}
@@ -0,0 +1,104 @@
+use std::array;
+
+use cpal::{FromSample, Sample, I24};
+use itertools::Either;
+
+use super::{
+    codec::{alaw_to_linear, decode_adpcm_block, mulaw_to_linear},
+    Format, WaveFile, WaveFileDecodeError,
+};
+use crate::Block;
+
+/// A sample type that every sample format stored in a [`WaveFile`] can be converted into.
+pub trait FromWaveSample: Sample + FromSample<u8> + FromSample<i16> + FromSample<I24> + FromSample<i32> + FromSample<f32> + FromSample<f64> {}
+
+impl<T: Sample + FromSample<u8> + FromSample<i16> + FromSample<I24> + FromSample<i32> + FromSample<f32> + FromSample<f64>> FromWaveSample for T {}
+
+/// Return a function that decodes a single little-endian sample of the given format into a `T`, or [`None`] if the format is not supported.
+fn decoder<T: FromWaveSample>(format: Format, bytes_per_sample: u16) -> Option<fn(&[u8]) -> T> {
+    Some(match (format, bytes_per_sample) {
+        (Format::PulseCodeModulation, 1) => |bytes| T::from_sample(bytes[0]),
+        (Format::PulseCodeModulation, 2) => |bytes| T::from_sample(i16::from_le_bytes([bytes[0], bytes[1]])),
+        // Shift the packed sample into the top of an `i32` and back down again to sign-extend it.
+        (Format::PulseCodeModulation, 3) => |bytes| T::from_sample(I24::new_unchecked(i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) >> 8)),
+        (Format::PulseCodeModulation, 4) => |bytes| T::from_sample(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+        (Format::FloatingPoint, 4) => |bytes| T::from_sample(f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+        (Format::ALaw, 1) => |bytes| T::from_sample(alaw_to_linear(bytes[0])),
+        (Format::MuLaw, 1) => |bytes| T::from_sample(mulaw_to_linear(bytes[0])),
+        (Format::FloatingPoint, 8) => |bytes| T::from_sample(f64::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7]])),
+        _ => return None,
+    })
+}
+
+impl WaveFile<'_> {
+    /// Return an iterator over the frames of the [`WaveFile`], converting each sample from the stored format into `T`.
+    ///
+    /// Any incomplete frame at the end of the data is ignored. G.711 and IMA ADPCM samples are decoded to 16-bit samples first, and IMA ADPCM data stops after
+    /// [`WaveFile::frame_count`] frames.
+    /// # Errors
+    /// Returns a [`WaveFileDecodeError::ChannelMismatch`] if `N` is not the number of channels in the file, or a [`WaveFileDecodeError::UnsupportedSampleFormat`] if the samples are not
+    /// 8-bit unsigned, 16/24/32-bit signed, 32/64-bit floating point, 8-bit G.711 or IMA ADPCM in blocks of whole groups of samples.
+    pub fn frames<'a, T: FromWaveSample + 'a, const N: usize>(&'a self) -> Result<impl Iterator<Item = Block<T, N>> + 'a, WaveFileDecodeError> {
+        if usize::from(self.channels.get()) != N {
+            return Err(WaveFileDecodeError::ChannelMismatch { expected: N, found: self.channels });
+        }
+        if self.format == Format::ImaAdpcm {
+            self.check_adpcm()?;
+            return Ok(Either::Right(
+                self.data
+                    .chunks(self.block_align())
+                    .flat_map(|block| {
+                        let samples = decode_adpcm_block(block, N);
+                        (0..samples.len() / N).map(move |frame| Block(array::from_fn(|channel| T::from_sample(samples[frame * N + channel]))))
+                    })
+                    .take(self.frame_count()),
+            ));
+        }
+        let decode = self.decoder()?;
+        let bytes_per_sample = usize::from(self.bytes_per_sample);
+        Ok(Either::Left(self.data.chunks_exact(bytes_per_sample * N).map(move |frame| {
+            Block(array::from_fn(|channel| decode(&frame[channel * bytes_per_sample..(channel + 1) * bytes_per_sample])))
+        })))
+    }
+
+    /// Return an iterator over the samples of every channel of the [`WaveFile`] in turn, frame by frame, converting each one into `T`.
+    ///
+    /// This is for when the number of channels is only known at runtime; otherwise, [`WaveFile::frames`] is easier to use. Any incomplete frame at the end of the data is ignored.
+    /// # Errors
+    /// Returns a [`WaveFileDecodeError::UnsupportedSampleFormat`] for the same formats as [`WaveFile::frames`].
+    pub fn samples<'a, T: FromWaveSample + 'a>(&'a self) -> Result<impl Iterator<Item = T> + 'a, WaveFileDecodeError> {
+        let channels = usize::from(self.channels.get());
+        if self.format == Format::ImaAdpcm {
+            self.check_adpcm()?;
+            return Ok(Either::Right(
+                self.data
+                    .chunks(self.block_align())
+                    .flat_map(move |block| decode_adpcm_block(block, channels))
+                    .take(self.frame_count() * channels)
+                    .map(T::from_sample),
+            ));
+        }
+        let decode = self.decoder()?;
+        Ok(Either::Left(
+            self.data[..self.frame_count() * self.block_align()].chunks_exact(usize::from(self.bytes_per_sample)).map(decode),
+        ))
+    }
+
+    const fn unsupported(&self) -> WaveFileDecodeError {
+        WaveFileDecodeError::UnsupportedSampleFormat {
+            format: self.format,
+            bytes_per_sample: self.bytes_per_sample,
+        }
+    }
+
+    fn decoder<T: FromWaveSample>(&self) -> Result<fn(&[u8]) -> T, WaveFileDecodeError> {
+        decoder::<T>(self.format, self.bytes_per_sample).ok_or_else(|| self.unsupported())
+    }
+
+    const fn check_adpcm(&self) -> Result<(), WaveFileDecodeError> {
+        if self.bytes_per_sample <= 4 || !self.bytes_per_sample.is_multiple_of(4) {
+            return Err(self.unsupported());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,250 @@
+use std::{borrow::Cow, num::NonZeroU16, ops::BitOr};
+
+use super::{
+    read::{u16_at, u32_at},
+    Format, Metadata, WaveFile, WaveFileReadError, WaveFileWriteError,
+};
+
+/// The format tag of a `fmt ` chunk that stores its real format in a sub-format GUID.
+pub const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+/// The last 14 bytes of every `KSDATAFORMAT_SUBTYPE_*` GUID that wraps a plain format tag.
+const SUB_FORMAT_GUID_SUFFIX: [u8; 14] = [0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71];
+
+/// The speaker positions of the channels in a [`WaveFile`], in the order that the channels appear in each frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChannelMask(pub u32);
+
+impl ChannelMask {
+    pub const UNASSIGNED: Self = Self(0);
+    pub const FRONT_LEFT: Self = Self(0x1);
+    pub const FRONT_RIGHT: Self = Self(0x2);
+    pub const FRONT_CENTER: Self = Self(0x4);
+    pub const LOW_FREQUENCY: Self = Self(0x8);
+    pub const BACK_LEFT: Self = Self(0x10);
+    pub const BACK_RIGHT: Self = Self(0x20);
+    pub const FRONT_LEFT_OF_CENTER: Self = Self(0x40);
+    pub const FRONT_RIGHT_OF_CENTER: Self = Self(0x80);
+    pub const BACK_CENTER: Self = Self(0x100);
+    pub const SIDE_LEFT: Self = Self(0x200);
+    pub const SIDE_RIGHT: Self = Self(0x400);
+    pub const TOP_CENTER: Self = Self(0x800);
+    pub const TOP_FRONT_LEFT: Self = Self(0x1000);
+    pub const TOP_FRONT_CENTER: Self = Self(0x2000);
+    pub const TOP_FRONT_RIGHT: Self = Self(0x4000);
+    pub const TOP_BACK_LEFT: Self = Self(0x8000);
+    pub const TOP_BACK_CENTER: Self = Self(0x10000);
+    pub const TOP_BACK_RIGHT: Self = Self(0x20000);
+
+    pub const MONO: Self = Self::FRONT_CENTER;
+    pub const STEREO: Self = Self(Self::FRONT_LEFT.0 | Self::FRONT_RIGHT.0);
+    pub const QUAD: Self = Self(Self::STEREO.0 | Self::BACK_LEFT.0 | Self::BACK_RIGHT.0);
+    pub const SURROUND_5_1: Self = Self(Self::QUAD.0 | Self::FRONT_CENTER.0 | Self::LOW_FREQUENCY.0);
+    pub const SURROUND_7_1: Self = Self(Self::SURROUND_5_1.0 | Self::SIDE_LEFT.0 | Self::SIDE_RIGHT.0);
+
+    /// Return the conventional layout for the given number of channels, or [`ChannelMask::UNASSIGNED`] if there isn't one.
+    #[must_use]
+    pub const fn default_for_channels(channels: u16) -> Self {
+        match channels {
+            1 => Self::MONO,
+            2 => Self::STEREO,
+            3 => Self(Self::STEREO.0 | Self::FRONT_CENTER.0),
+            4 => Self::QUAD,
+            5 => Self(Self::QUAD.0 | Self::FRONT_CENTER.0),
+            6 => Self::SURROUND_5_1,
+            7 => Self(Self::STEREO.0 | Self::FRONT_CENTER.0 | Self::LOW_FREQUENCY.0 | Self::BACK_CENTER.0 | Self::SIDE_LEFT.0 | Self::SIDE_RIGHT.0),
+            8 => Self::SURROUND_7_1,
+            _ => Self::UNASSIGNED,
+        }
+    }
+
+    /// Return the number of speaker positions in the mask.
+    #[must_use]
+    pub const fn count(self) -> u32 {
+        self.0.count_ones()
+    }
+}
+
+impl BitOr for ChannelMask {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// Return the number of frames in an IMA ADPCM block with `bytes_per_channel` bytes for each channel: one in the 4-byte header, and two in every other byte.
+pub(super) const fn adpcm_frames_per_block(bytes_per_channel: u16) -> usize {
+    bytes_per_channel.saturating_sub(4) as usize * 2 + 1
+}
+
+impl WaveFile<'_> {
+    /// Return whether the [`WaveFile`] needs a `WAVE_FORMAT_EXTENSIBLE` `fmt ` chunk, because it has more than 2 channels, more than 16 bits per sample, padding bits in each sample, or
+    /// a channel mask other than the default one. IMA ADPCM files never do, because the extensible chunk has no room for the number of frames in each block.
+    #[must_use]
+    pub fn is_extensible(&self) -> bool {
+        self.format != Format::ImaAdpcm
+            && (self.channels.get() > 2
+                || self.bytes_per_sample > 2
+                || self.valid_bits_per_sample != self.bytes_per_sample * 8
+                || self.channel_mask != ChannelMask::default_for_channels(self.channels.get()))
+    }
+
+    /// Return the size of each block of samples in the data, in bytes, which is the size of a frame for every format but IMA ADPCM.
+    #[must_use]
+    pub fn block_align(&self) -> usize {
+        usize::from(self.bytes_per_sample) * usize::from(self.channels.get())
+    }
+
+    /// Return the number of frames in the data, ignoring any incomplete frame at the end.
+    ///
+    /// For IMA ADPCM, this is limited to [`WaveFile::total_frames`] if there is one, because the last block is usually padded.
+    #[must_use]
+    pub fn frame_count(&self) -> usize {
+        let block_align = self.block_align();
+        if block_align == 0 {
+            return 0;
+        }
+        if self.format != Format::ImaAdpcm {
+            return self.data.len() / block_align;
+        }
+        // A shorter block at the end still has a header and whole groups of 8 frames.
+        let group = 4 * usize::from(self.channels.get());
+        let remainder = self.data.len() % block_align;
+        let frames = self.data.len() / block_align * adpcm_frames_per_block(self.bytes_per_sample) + if remainder >= group { 1 + (remainder - group) / group * 8 } else { 0 };
+        self.total_frames.and_then(|total| usize::try_from(total).ok()).map_or(frames, |total| total.min(frames))
+    }
+
+    /// Check that the sample size suits the format and that the data is made of whole blocks, where only the last IMA ADPCM block can be shorter than the others.
+    pub(super) fn check_block_align(&self) -> Result<(), WaveFileWriteError> {
+        let valid = match self.format {
+            Format::PulseCodeModulation | Format::FloatingPoint => self.bytes_per_sample > 0,
+            Format::ALaw | Format::MuLaw => self.bytes_per_sample == 1,
+            Format::ImaAdpcm => self.bytes_per_sample > 4 && self.bytes_per_sample.is_multiple_of(4),
+        };
+        if !valid || self.bytes_per_sample.checked_mul(self.channels.get()).is_none() {
+            return Err(WaveFileWriteError::InvalidBlockAlign);
+        }
+        let remainder = self.data.len() % self.block_align();
+        let whole = if self.format == Format::ImaAdpcm {
+            remainder.is_multiple_of(4 * usize::from(self.channels.get()))
+        } else {
+            remainder == 0
+        };
+        if whole {
+            Ok(())
+        } else {
+            Err(WaveFileWriteError::InvalidBlockAlign)
+        }
+    }
+
+    /// Return the body of the `fmt ` chunk for the [`WaveFile`].
+    pub(super) fn format_chunk(&self) -> Vec<u8> {
+        let block_align = self.bytes_per_sample * self.channels.get();
+        let extensible = self.is_extensible();
+        let (average_bytes_per_second, bits_per_sample) = if self.format == Format::ImaAdpcm {
+            let frames_per_block = adpcm_frames_per_block(self.bytes_per_sample) as u64;
+            let average = u64::from(self.sample_rate) * u64::from(block_align) / frames_per_block;
+            (u32::try_from(average).unwrap_or(u32::MAX), 4)
+        } else {
+            (self.sample_rate * u32::from(block_align), self.bytes_per_sample * 8)
+        };
+        let mut chunk = Vec::with_capacity(40);
+        chunk.extend(if extensible { WAVE_FORMAT_EXTENSIBLE } else { self.format as u16 }.to_le_bytes());
+        chunk.extend(self.channels.get().to_le_bytes());
+        chunk.extend(self.sample_rate.to_le_bytes());
+        chunk.extend(average_bytes_per_second.to_le_bytes());
+        chunk.extend(block_align.to_le_bytes());
+        chunk.extend(bits_per_sample.to_le_bytes());
+        if extensible {
+            chunk.extend(22_u16.to_le_bytes());
+            chunk.extend(self.valid_bits_per_sample.to_le_bytes());
+            chunk.extend(self.channel_mask.0.to_le_bytes());
+            chunk.extend((self.format as u16).to_le_bytes());
+            chunk.extend(SUB_FORMAT_GUID_SUFFIX);
+        } else if self.format == Format::ImaAdpcm {
+            chunk.extend(2_u16.to_le_bytes());
+            chunk.extend(u16::try_from(adpcm_frames_per_block(self.bytes_per_sample)).unwrap_or(u16::MAX).to_le_bytes());
+        } else if self.format != Format::PulseCodeModulation {
+            chunk.extend(0_u16.to_le_bytes());
+        }
+        chunk
+    }
+}
+
+const fn format_from_tag(tag: u16) -> Result<Format, WaveFileReadError> {
+    match tag {
+        1 => Ok(Format::PulseCodeModulation),
+        3 => Ok(Format::FloatingPoint),
+        6 => Ok(Format::ALaw),
+        7 => Ok(Format::MuLaw),
+        0x11 => Ok(Format::ImaAdpcm),
+        tag => Err(WaveFileReadError::UnsupportedFormat(tag)),
+    }
+}
+
+/// Parse the body of a `fmt ` chunk, returning a [`WaveFile`] with empty data.
+pub(super) fn parse_format(chunk: &[u8]) -> Result<WaveFile<'static>, WaveFileReadError> {
+    if chunk.len() < 16 {
+        return Err(WaveFileReadError::TruncatedChunk(*b"fmt "));
+    }
+    let tag = u16_at(chunk, 0);
+    let channels = NonZeroU16::new(u16_at(chunk, 2)).ok_or(WaveFileReadError::InvalidFormatChunk)?;
+    let sample_rate = u32_at(chunk, 4);
+    let block_align = u16_at(chunk, 12);
+    if block_align == 0 || !block_align.is_multiple_of(channels.get()) {
+        return Err(WaveFileReadError::InvalidBlockAlign(block_align));
+    }
+    let bytes_per_sample = block_align / channels.get();
+    let (format, valid_bits_per_sample, channel_mask) = if tag == WAVE_FORMAT_EXTENSIBLE {
+        if chunk.len() < 40 {
+            return Err(WaveFileReadError::TruncatedChunk(*b"fmt "));
+        }
+        if u16_at(chunk, 16) < 22 {
+            return Err(WaveFileReadError::InvalidFormatChunk);
+        }
+        let guid: [u8; 16] = chunk[24..40].try_into().expect("slice is 16 bytes long");
+        if guid[2..] != SUB_FORMAT_GUID_SUFFIX {
+            return Err(WaveFileReadError::UnsupportedSubFormat(guid));
+        }
+        // Some writers leave the valid bits as zero, which means that every bit is valid.
+        let valid_bits_per_sample = match u16_at(chunk, 18) {
+            0 => bytes_per_sample * 8,
+            bits if bits > bytes_per_sample * 8 => return Err(WaveFileReadError::InvalidFormatChunk),
+            bits => bits,
+        };
+        let format = format_from_tag(u16_at(&guid, 0))?;
+        if format == Format::ImaAdpcm {
+            return Err(WaveFileReadError::UnsupportedSubFormat(guid));
+        }
+        (format, valid_bits_per_sample, ChannelMask(u32_at(chunk, 20)))
+    } else {
+        (format_from_tag(tag)?, bytes_per_sample * 8, ChannelMask::default_for_channels(channels.get()))
+    };
+    let valid_bits_per_sample = match format {
+        Format::ALaw | Format::MuLaw if bytes_per_sample != 1 => return Err(WaveFileReadError::InvalidBlockAlign(block_align)),
+        Format::ImaAdpcm => {
+            // Each channel's part of a block is a 4-byte header followed by groups of 8 samples in 4 bytes.
+            if bytes_per_sample <= 4 || !bytes_per_sample.is_multiple_of(4) {
+                return Err(WaveFileReadError::InvalidBlockAlign(block_align));
+            }
+            // The number of frames in each block is redundant, but it must agree with the block alignment.
+            if chunk.len() >= 20 && u16_at(chunk, 16) >= 2 && usize::from(u16_at(chunk, 18)) != adpcm_frames_per_block(bytes_per_sample) {
+                return Err(WaveFileReadError::InvalidBlockAlign(block_align));
+            }
+            4
+        }
+        _ => valid_bits_per_sample,
+    };
+    Ok(WaveFile {
+        format,
+        channels,
+        sample_rate,
+        bytes_per_sample,
+        valid_bits_per_sample,
+        channel_mask,
+        metadata: Metadata::default(),
+        total_frames: None,
+        data: Cow::Borrowed(&[]),
+    })
+}
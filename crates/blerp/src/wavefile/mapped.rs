@@ -0,0 +1,47 @@
+use std::{borrow::Cow, fs::File, ops::Range, path::Path};
+
+use memmap2::Mmap;
+
+use super::{read::parse_header, WaveFile, WaveFileReadError};
+
+/// A RIFF/WAVE file that is memory-mapped instead of read into memory, so that opening it is instant no matter how big it is.
+///
+/// The header is parsed and validated when the file is opened, and [`MappedWaveFile::wave_file`] borrows the `data` chunk straight from the mapping.
+pub struct MappedWaveFile {
+    map: Mmap,
+    header: WaveFile<'static>,
+    data: Range<usize>,
+}
+
+impl MappedWaveFile {
+    /// Memory-map the file at `path` and parse its header.
+    /// # Safety
+    /// The file must not be modified or truncated (by this process or any other) while the [`MappedWaveFile`] exists, because the data returned from
+    /// [`MappedWaveFile::wave_file`] would change underneath it, which is undefined behaviour. See [`Mmap::map`].
+    /// # Errors
+    /// Returns a [`WaveFileReadError::Io`] if the file cannot be opened or mapped, or any other [`WaveFileReadError`] for the same reasons as [`WaveFile::parse`].
+    pub unsafe fn open(path: impl AsRef<Path>) -> Result<Self, WaveFileReadError> {
+        let file = File::open(path)?;
+        // SAFETY: The caller guarantees that the file is not modified while it is mapped.
+        let map = unsafe { Mmap::map(&file) }?;
+        let (header, data) = parse_header(&map)?;
+        Ok(Self { map, header, data })
+    }
+
+    /// Return the file's header, with empty data.
+    #[must_use]
+    pub const fn header(&self) -> &WaveFile<'static> {
+        &self.header
+    }
+
+    /// Return the file as a [`WaveFile`] that borrows its data from the mapping, without copying it.
+    ///
+    /// Only the [`Metadata`](super::Metadata) is cloned.
+    #[must_use]
+    pub fn wave_file(&self) -> WaveFile<'_> {
+        WaveFile {
+            data: Cow::Borrowed(&self.map[self.data.clone()]),
+            ..self.header.clone()
+        }
+    }
+}
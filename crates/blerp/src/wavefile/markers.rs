@@ -0,0 +1,311 @@
+use super::{
+    metadata::{push_chunk, text, Chunk},
+    read::{u16_at, u32_at, Chunks},
+    WaveFileReadError, WaveFileWriteError,
+};
+
+/// A point in the file from the `cue ` chunk, with the labels and region attached to it in the `LIST` chunk of type `adtl`.
+///
+/// A cue point without a region is a marker.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CuePoint {
+    /// The id that loops and labels use to refer to the cue point.
+    pub id: u32,
+    /// The position of the cue point, as a number of frames from the start of the data.
+    pub position: u32,
+    /// The text of the `labl` chunk.
+    pub label: Option<String>,
+    /// The text of the `note` chunk.
+    pub note: Option<String>,
+    /// The region from the `ltxt` chunk, which starts at the cue point.
+    pub region: Option<Region>,
+    /// Every other `adtl` chunk for the cue point (e.g. `file`), kept byte-for-byte apart from the cue point's id at the start.
+    pub other_chunks: Vec<Chunk>,
+}
+
+/// A region of a file, from an `ltxt` chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    /// The length of the region, in frames.
+    pub length: u32,
+    /// What the region is for, which is `rgn ` for plain regions.
+    pub purpose: [u8; 4],
+    pub country: u16,
+    pub language: u16,
+    pub dialect: u16,
+    pub code_page: u16,
+    /// The text of the `ltxt` chunk itself (most software puts the region's name in the `labl` chunk instead).
+    pub text: Option<String>,
+}
+
+impl Region {
+    /// Create a plain region with the given length, in frames.
+    #[must_use]
+    pub const fn new(length: u32) -> Self {
+        Self {
+            length,
+            purpose: *b"rgn ",
+            country: 0,
+            language: 0,
+            dialect: 0,
+            code_page: 0,
+            text: None,
+        }
+    }
+}
+
+/// The contents of a `smpl` chunk, which describes how a sampler should play the file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sampler {
+    /// The MMA manufacturer code of the sampler the chunk is for, or zero if it isn't for a specific one.
+    pub manufacturer: u32,
+    /// The manufacturer's product code of the sampler the chunk is for.
+    pub product: u32,
+    /// The length of one frame, in nanoseconds.
+    pub sample_period: u32,
+    /// The MIDI note that plays the file at its original pitch.
+    pub midi_unity_note: u32,
+    /// A fraction of a semitone to raise the unity note by, where `0x80000000` is half a semitone.
+    pub midi_pitch_fraction: u32,
+    pub smpte_format: u32,
+    pub smpte_offset: u32,
+    pub loops: Vec<SampleLoop>,
+    /// Sampler-specific data that follows the loops.
+    pub sampler_data: Vec<u8>,
+}
+
+impl Sampler {
+    /// Create a [`Sampler`] for a file with the given sample rate and unity note, with no loops.
+    #[must_use]
+    pub fn new(sample_rate: u32, midi_unity_note: u32) -> Self {
+        Self {
+            manufacturer: 0,
+            product: 0,
+            sample_period: 1_000_000_000 / sample_rate.max(1),
+            midi_unity_note,
+            midi_pitch_fraction: 0,
+            smpte_format: 0,
+            smpte_offset: 0,
+            loops: Vec::new(),
+            sampler_data: Vec::new(),
+        }
+    }
+}
+
+/// A loop in a [`Sampler`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SampleLoop {
+    /// The id of the [`CuePoint`] that names the loop, if there is one.
+    pub id: u32,
+    pub kind: LoopKind,
+    /// The first frame of the loop.
+    pub start: u32,
+    /// The last frame of the loop (inclusive).
+    pub end: u32,
+    /// A fraction of a frame to extend the loop by, where `0x80000000` is half a frame.
+    pub fraction: u32,
+    /// The number of times to play the loop, where zero means forever.
+    pub play_count: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoopKind {
+    Forward,
+    PingPong,
+    Backward,
+    Other(u32),
+}
+
+impl From<u32> for LoopKind {
+    fn from(value: u32) -> Self {
+        match value {
+            0 => Self::Forward,
+            1 => Self::PingPong,
+            2 => Self::Backward,
+            other => Self::Other(other),
+        }
+    }
+}
+
+impl From<LoopKind> for u32 {
+    fn from(value: LoopKind) -> Self {
+        match value {
+            LoopKind::Forward => 0,
+            LoopKind::PingPong => 1,
+            LoopKind::Backward => 2,
+            LoopKind::Other(other) => other,
+        }
+    }
+}
+
+/// Return the cue point with the given id, adding it if there isn't one yet (because the `LIST` chunk can come before the `cue ` chunk).
+fn cue_point(cue_points: &mut Vec<CuePoint>, id: u32) -> &mut CuePoint {
+    let index = cue_points.iter().position(|cue_point| cue_point.id == id).unwrap_or_else(|| {
+        cue_points.push(CuePoint { id, ..CuePoint::default() });
+        cue_points.len() - 1
+    });
+    &mut cue_points[index]
+}
+
+pub(super) fn parse_cue(chunk: &[u8], cue_points: &mut Vec<CuePoint>) -> Result<(), WaveFileReadError> {
+    let count = chunk.get(0..4).map(|_| u32_at(chunk, 0) as usize).ok_or(WaveFileReadError::TruncatedChunk(*b"cue "))?;
+    if chunk.len() < count.saturating_mul(24).saturating_add(4) {
+        return Err(WaveFileReadError::TruncatedChunk(*b"cue "));
+    }
+    for point in chunk[4..4 + count * 24].chunks_exact(24) {
+        cue_point(cue_points, u32_at(point, 0)).position = u32_at(point, 20);
+    }
+    Ok(())
+}
+
+pub(super) fn parse_adtl(list: &[u8], cue_points: &mut Vec<CuePoint>) -> Result<(), WaveFileReadError> {
+    for chunk in Chunks::new(list) {
+        let (id, range) = chunk?;
+        let chunk = &list[range];
+        if chunk.len() < if &id == b"ltxt" { 20 } else { 4 } {
+            return Err(WaveFileReadError::TruncatedChunk(id));
+        }
+        let cue_point = cue_point(cue_points, u32_at(chunk, 0));
+        match &id {
+            b"labl" => cue_point.label = Some(text(&chunk[4..])),
+            b"note" => cue_point.note = Some(text(&chunk[4..])),
+            b"ltxt" => {
+                cue_point.region = Some(Region {
+                    length: u32_at(chunk, 4),
+                    purpose: chunk[8..12].try_into().expect("slice is 4 bytes long"),
+                    country: u16_at(chunk, 12),
+                    language: u16_at(chunk, 14),
+                    dialect: u16_at(chunk, 16),
+                    code_page: u16_at(chunk, 18),
+                    text: (chunk.len() > 20).then(|| text(&chunk[20..])),
+                });
+            }
+            _ => cue_point.other_chunks.push(Chunk { id, data: chunk[4..].to_vec() }),
+        }
+    }
+    Ok(())
+}
+
+pub(super) fn parse_smpl(chunk: &[u8]) -> Result<Sampler, WaveFileReadError> {
+    if chunk.len() < 36 {
+        return Err(WaveFileReadError::TruncatedChunk(*b"smpl"));
+    }
+    let loop_count = u32_at(chunk, 28) as usize;
+    let loops_end = loop_count.saturating_mul(24).saturating_add(36);
+    let sampler_data_len = u32_at(chunk, 32) as usize;
+    if chunk.len() < loops_end.saturating_add(sampler_data_len) {
+        return Err(WaveFileReadError::TruncatedChunk(*b"smpl"));
+    }
+    Ok(Sampler {
+        manufacturer: u32_at(chunk, 0),
+        product: u32_at(chunk, 4),
+        sample_period: u32_at(chunk, 8),
+        midi_unity_note: u32_at(chunk, 12),
+        midi_pitch_fraction: u32_at(chunk, 16),
+        smpte_format: u32_at(chunk, 20),
+        smpte_offset: u32_at(chunk, 24),
+        loops: chunk[36..loops_end]
+            .chunks_exact(24)
+            .map(|sample_loop| SampleLoop {
+                id: u32_at(sample_loop, 0),
+                kind: u32_at(sample_loop, 4).into(),
+                start: u32_at(sample_loop, 8),
+                end: u32_at(sample_loop, 12),
+                fraction: u32_at(sample_loop, 16),
+                play_count: u32_at(sample_loop, 20),
+            })
+            .collect(),
+        sampler_data: chunk[loops_end..loops_end + sampler_data_len].to_vec(),
+    })
+}
+
+fn length(len: usize) -> Result<[u8; 4], WaveFileWriteError> {
+    Ok(u32::try_from(len).map_err(|_| WaveFileWriteError::DataTooLong)?.to_le_bytes())
+}
+
+/// Append the `cue ` and `LIST`/`adtl` chunks for the cue points to `out`.
+pub(super) fn push_cue_points(out: &mut Vec<u8>, cue_points: &[CuePoint]) -> Result<(), WaveFileWriteError> {
+    if cue_points.is_empty() {
+        return Ok(());
+    }
+    let mut cue = length(cue_points.len())?.to_vec();
+    let mut adtl = b"adtl".to_vec();
+    for CuePoint {
+        id,
+        position,
+        label,
+        note,
+        region,
+        other_chunks,
+    } in cue_points
+    {
+        cue.extend_from_slice(&id.to_le_bytes());
+        cue.extend_from_slice(&position.to_le_bytes());
+        cue.extend_from_slice(b"data");
+        cue.extend_from_slice(&[0; 8]);
+        cue.extend_from_slice(&position.to_le_bytes());
+        for (chunk_id, label) in [(*b"labl", label), (*b"note", note)] {
+            if let Some(label) = label {
+                let mut body = id.to_le_bytes().to_vec();
+                body.extend_from_slice(label.as_bytes());
+                body.push(0);
+                push_chunk(&mut adtl, chunk_id, &body)?;
+            }
+        }
+        if let Some(region) = region {
+            let mut body = id.to_le_bytes().to_vec();
+            body.extend_from_slice(&region.length.to_le_bytes());
+            body.extend_from_slice(&region.purpose);
+            for field in [region.country, region.language, region.dialect, region.code_page] {
+                body.extend_from_slice(&field.to_le_bytes());
+            }
+            if let Some(text) = &region.text {
+                body.extend_from_slice(text.as_bytes());
+                body.push(0);
+            }
+            push_chunk(&mut adtl, *b"ltxt", &body)?;
+        }
+        for Chunk { id: chunk_id, data } in other_chunks {
+            let mut body = id.to_le_bytes().to_vec();
+            body.extend_from_slice(data);
+            push_chunk(&mut adtl, *chunk_id, &body)?;
+        }
+    }
+    push_chunk(out, *b"cue ", &cue)?;
+    if adtl.len() > 4 {
+        push_chunk(out, *b"LIST", &adtl)?;
+    }
+    Ok(())
+}
+
+/// Append the `smpl` chunk for the sampler to `out`.
+pub(super) fn push_sampler(out: &mut Vec<u8>, sampler: &Sampler) -> Result<(), WaveFileWriteError> {
+    let mut body = Vec::with_capacity(36 + sampler.loops.len() * 24 + sampler.sampler_data.len());
+    for field in [
+        sampler.manufacturer,
+        sampler.product,
+        sampler.sample_period,
+        sampler.midi_unity_note,
+        sampler.midi_pitch_fraction,
+        sampler.smpte_format,
+        sampler.smpte_offset,
+    ] {
+        body.extend_from_slice(&field.to_le_bytes());
+    }
+    body.extend_from_slice(&length(sampler.loops.len())?);
+    body.extend_from_slice(&length(sampler.sampler_data.len())?);
+    for sample_loop in &sampler.loops {
+        for field in [
+            sample_loop.id,
+            sample_loop.kind.into(),
+            sample_loop.start,
+            sample_loop.end,
+            sample_loop.fraction,
+            sample_loop.play_count,
+        ] {
+            body.extend_from_slice(&field.to_le_bytes());
+        }
+    }
+    body.extend_from_slice(&sampler.sampler_data);
+    push_chunk(out, *b"smpl", &body)
+}
@@ -0,0 +1,254 @@
+use super::{
+    markers::{parse_adtl, parse_cue, parse_smpl, push_cue_points, push_sampler, CuePoint, Sampler},
+    read::{u16_at, u32_at, Chunks},
+    WaveFileReadError, WaveFileWriteError,
+};
+
+/// The metadata stored in the chunks of a [`WaveFile`](super::WaveFile) other than `fmt `, `fact` and `data`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metadata {
+    /// The Broadcast Wave Format `bext` chunk.
+    pub broadcast: Option<BroadcastExtension>,
+    /// The tags in the `LIST` chunk of type `INFO`.
+    pub info: Info,
+    /// The markers and regions from the `cue ` chunk and the `LIST` chunk of type `adtl`.
+    pub cue_points: Vec<CuePoint>,
+    /// The `smpl` chunk, which holds the loops.
+    pub sampler: Option<Sampler>,
+    /// Every other chunk, kept byte-for-byte in the order they appeared so that re-saving a file doesn't drop them. Padding (`JUNK` and `PAD ` chunks) is not kept.
+    pub unknown_chunks: Vec<Chunk>,
+}
+
+/// A RIFF chunk that is stored as-is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunk {
+    pub id: [u8; 4],
+    pub data: Vec<u8>,
+}
+
+/// The contents of a Broadcast Wave Format `bext` chunk (EBU Tech 3285).
+///
+/// Text fields are ASCII in the file, and are truncated to their field's length when written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BroadcastExtension {
+    /// A description of the sound, at most 256 bytes.
+    pub description: String,
+    /// The name of the originator, at most 32 bytes.
+    pub originator: String,
+    /// A reference assigned by the originator, at most 32 bytes.
+    pub originator_reference: String,
+    /// The date the sound was created, in the form `yyyy-mm-dd`.
+    pub origination_date: String,
+    /// The time the sound was created, in the form `hh:mm:ss`.
+    pub origination_time: String,
+    /// The position of the first sample, as a number of samples since midnight, used to place the sound on a timeline.
+    pub time_reference: u64,
+    pub version: u16,
+    /// A SMPTE UMID (ST 330), or all zeroes if there isn't one.
+    pub umid: [u8; 64],
+    /// The integrated loudness in LUFS, multiplied by 100.
+    pub loudness_value: i16,
+    /// The loudness range in LU, multiplied by 100.
+    pub loudness_range: i16,
+    /// The maximum true peak level in dBTP, multiplied by 100.
+    pub max_true_peak_level: i16,
+    /// The highest momentary loudness in LUFS, multiplied by 100.
+    pub max_momentary_loudness: i16,
+    /// The highest short-term loudness in LUFS, multiplied by 100.
+    pub max_short_term_loudness: i16,
+    /// A history of the coding processes applied to the sound, one per line.
+    pub coding_history: String,
+}
+
+impl Default for BroadcastExtension {
+    fn default() -> Self {
+        Self {
+            description: String::new(),
+            originator: String::new(),
+            originator_reference: String::new(),
+            origination_date: String::new(),
+            origination_time: String::new(),
+            time_reference: 0,
+            version: 2,
+            umid: [0; 64],
+            loudness_value: 0,
+            loudness_range: 0,
+            max_true_peak_level: 0,
+            max_momentary_loudness: 0,
+            max_short_term_loudness: 0,
+            coding_history: String::new(),
+        }
+    }
+}
+
+/// The tags in a `LIST` chunk of type `INFO`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Info {
+    /// `INAM`
+    pub title: Option<String>,
+    /// `IART`
+    pub artist: Option<String>,
+    /// `ICMT`
+    pub comment: Option<String>,
+    /// `ISFT`
+    pub software: Option<String>,
+    /// Every other tag, in the order they appeared.
+    pub other: Vec<([u8; 4], String)>,
+}
+
+impl Info {
+    fn tags(&self) -> impl Iterator<Item = ([u8; 4], &str)> {
+        [(*b"INAM", &self.title), (*b"IART", &self.artist), (*b"ICMT", &self.comment), (*b"ISFT", &self.software)]
+            .into_iter()
+            .filter_map(|(id, value)| Some((id, value.as_deref()?)))
+            .chain(self.other.iter().map(|(id, value)| (*id, value.as_str())))
+    }
+
+    /// Return whether there are no tags.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.tags().next().is_none()
+    }
+}
+
+const BEXT_LEN: usize = 256 + 32 + 32 + 10 + 8 + 8 + 2 + 64 + 2 * 5 + 180;
+
+/// Read a text field, which ends at the first NUL byte (if there is one).
+pub(super) fn text(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&byte| byte == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+/// Write a text field, truncating or padding it with NUL bytes to `len` bytes.
+fn push_text(out: &mut Vec<u8>, text: &str, len: usize) {
+    let bytes = &text.as_bytes()[..text.len().min(len)];
+    out.extend_from_slice(bytes);
+    out.resize(out.len() + len - bytes.len(), 0);
+}
+
+/// Append a chunk with the given id and body to `out`, followed by a padding byte if the body has an odd length.
+pub(super) fn push_chunk(out: &mut Vec<u8>, id: [u8; 4], body: &[u8]) -> Result<(), WaveFileWriteError> {
+    out.extend_from_slice(&id);
+    out.extend_from_slice(&u32::try_from(body.len()).map_err(|_| WaveFileWriteError::DataTooLong)?.to_le_bytes());
+    out.extend_from_slice(body);
+    if body.len() % 2 == 1 {
+        out.push(0);
+    }
+    Ok(())
+}
+
+impl Metadata {
+    /// Return the cue points that are markers, i.e. that don't have a region.
+    pub fn markers(&self) -> impl Iterator<Item = &CuePoint> {
+        self.cue_points.iter().filter(|cue_point| cue_point.region.is_none())
+    }
+
+    /// Return the cue points that start a region.
+    pub fn regions(&self) -> impl Iterator<Item = &CuePoint> {
+        self.cue_points.iter().filter(|cue_point| cue_point.region.is_some())
+    }
+
+    /// Parse a chunk into the metadata, returning `false` if the chunk is not metadata (i.e. it is `fmt `, `fact` or `data`).
+    pub(super) fn parse_chunk(&mut self, id: [u8; 4], chunk: &[u8]) -> Result<bool, WaveFileReadError> {
+        match &id {
+            b"fmt " | b"fact" | b"data" | b"ds64" => return Ok(false),
+            b"JUNK" | b"PAD " => {}
+            b"bext" => self.broadcast = Some(parse_bext(chunk)?),
+            b"LIST" if chunk.get(0..4) == Some(b"INFO") => parse_info(&chunk[4..], &mut self.info)?,
+            b"LIST" if chunk.get(0..4) == Some(b"adtl") => parse_adtl(&chunk[4..], &mut self.cue_points)?,
+            b"cue " => parse_cue(chunk, &mut self.cue_points)?,
+            b"smpl" => self.sampler = Some(parse_smpl(chunk)?),
+            _ => self.unknown_chunks.push(Chunk { id, data: chunk.to_vec() }),
+        }
+        Ok(true)
+    }
+
+    /// Return the metadata as a sequence of complete chunks, ready to be written between the `fmt ` and `data` chunks.
+    pub(super) fn to_chunks(&self) -> Result<Vec<u8>, WaveFileWriteError> {
+        let mut out = Vec::new();
+        if let Some(broadcast) = &self.broadcast {
+            push_chunk(&mut out, *b"bext", &broadcast.to_bytes())?;
+        }
+        if !self.info.is_empty() {
+            let mut list = b"INFO".to_vec();
+            for (id, value) in self.info.tags() {
+                let mut value = value.as_bytes().to_vec();
+                value.push(0);
+                push_chunk(&mut list, id, &value)?;
+            }
+            push_chunk(&mut out, *b"LIST", &list)?;
+        }
+        push_cue_points(&mut out, &self.cue_points)?;
+        if let Some(sampler) = &self.sampler {
+            push_sampler(&mut out, sampler)?;
+        }
+        for Chunk { id, data } in &self.unknown_chunks {
+            push_chunk(&mut out, *id, data)?;
+        }
+        Ok(out)
+    }
+}
+
+fn parse_bext(chunk: &[u8]) -> Result<BroadcastExtension, WaveFileReadError> {
+    if chunk.len() < BEXT_LEN {
+        return Err(WaveFileReadError::TruncatedChunk(*b"bext"));
+    }
+    let loudness = |index: usize| u16_at(chunk, 412 + index * 2).cast_signed();
+    Ok(BroadcastExtension {
+        description: text(&chunk[0..256]),
+        originator: text(&chunk[256..288]),
+        originator_reference: text(&chunk[288..320]),
+        origination_date: text(&chunk[320..330]),
+        origination_time: text(&chunk[330..338]),
+        time_reference: u64::from(u32_at(chunk, 338)) | u64::from(u32_at(chunk, 342)) << 32,
+        version: u16_at(chunk, 346),
+        umid: chunk[348..412].try_into().expect("slice is 64 bytes long"),
+        loudness_value: loudness(0),
+        loudness_range: loudness(1),
+        max_true_peak_level: loudness(2),
+        max_momentary_loudness: loudness(3),
+        max_short_term_loudness: loudness(4),
+        coding_history: text(&chunk[BEXT_LEN..]),
+    })
+}
+
+impl BroadcastExtension {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(BEXT_LEN + self.coding_history.len());
+        push_text(&mut out, &self.description, 256);
+        push_text(&mut out, &self.originator, 32);
+        push_text(&mut out, &self.originator_reference, 32);
+        push_text(&mut out, &self.origination_date, 10);
+        push_text(&mut out, &self.origination_time, 8);
+        out.extend_from_slice(&self.time_reference.to_le_bytes());
+        out.extend_from_slice(&self.version.to_le_bytes());
+        out.extend_from_slice(&self.umid);
+        for loudness in [
+            self.loudness_value,
+            self.loudness_range,
+            self.max_true_peak_level,
+            self.max_momentary_loudness,
+            self.max_short_term_loudness,
+        ] {
+            out.extend_from_slice(&loudness.to_le_bytes());
+        }
+        out.resize(BEXT_LEN, 0);
+        out.extend_from_slice(self.coding_history.as_bytes());
+        out
+    }
+}
+
+fn parse_info(list: &[u8], info: &mut Info) -> Result<(), WaveFileReadError> {
+    for chunk in Chunks::new(list) {
+        let (id, range) = chunk?;
+        let value = text(&list[range]);
+        match &id {
+            b"INAM" => info.title = Some(value),
+            b"IART" => info.artist = Some(value),
+            b"ICMT" => info.comment = Some(value),
+            b"ISFT" => info.software = Some(value),
+            _ => info.other.push((id, value)),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,141 @@
+use std::{borrow::Cow, io::Read, ops::Range};
+
+use super::{format::parse_format, Metadata, WaveFile, WaveFileReadError};
+
+/// An iterator over the RIFF chunks in `bytes`, yielding each chunk's id and body (without the padding byte).
+pub(super) struct Chunks<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    /// The real size of the `data` chunk from an RF64 `ds64` chunk, used when the `data` chunk's own size is `0xFFFFFFFF`.
+    data_size: Option<usize>,
+}
+
+impl<'a> Chunks<'a> {
+    pub(super) const fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, offset: 0, data_size: None }
+    }
+}
+
+impl Iterator for Chunks<'_> {
+    type Item = Result<([u8; 4], Range<usize>), WaveFileReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Anything shorter than a chunk header is trailing garbage (or a missing padding byte), so it is ignored.
+        let header = self.bytes.get(self.offset..self.offset + 8)?;
+        let id = [header[0], header[1], header[2], header[3]];
+        let size = match (u32::from_le_bytes([header[4], header[5], header[6], header[7]]), self.data_size) {
+            (u32::MAX, Some(data_size)) if &id == b"data" => data_size,
+            // A `data` chunk with a placeholder size was left by a streaming writer that never finished, so it continues until the end of the file.
+            (u32::MAX, None) if &id == b"data" => self.bytes.len() - self.offset - 8,
+            (size, _) => size as usize,
+        };
+        let start = self.offset + 8;
+        let end = start.saturating_add(size);
+        if end > self.bytes.len() {
+            self.offset = self.bytes.len();
+            return Some(Err(WaveFileReadError::TruncatedChunk(id)));
+        }
+        // Chunks are word-aligned, so odd-sized chunks are followed by a padding byte.
+        self.offset = (end + size % 2).min(self.bytes.len());
+        Some(Ok((id, start..end)))
+    }
+}
+
+pub(super) fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+pub(super) fn u64_at(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().expect("slice is 8 bytes long"))
+}
+
+pub(super) fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+}
+
+/// Parse everything but the sample data, returning a [`WaveFile`] with empty data and the range of the `data` chunk body in `bytes`.
+pub(super) fn parse_header(bytes: &[u8]) -> Result<(WaveFile<'static>, Range<usize>), WaveFileReadError> {
+    if bytes.len() < 12 || !matches!(&bytes[0..4], b"RIFF" | b"RF64" | b"BW64") || &bytes[8..12] != b"WAVE" {
+        return Err(WaveFileReadError::BadMagic);
+    }
+    let mut chunks = Chunks::new(&bytes[12..]);
+    let (riff_size, ds64_frames) = if &bytes[0..4] == b"RIFF" {
+        (u64::from(u32_at(bytes, 4)), None)
+    } else {
+        // RF64 and BW64 files have a `ds64` chunk first, holding the 64-bit sizes that don't fit in the RIFF and `data` chunk headers.
+        let (id, range) = chunks.next().ok_or(WaveFileReadError::MissingChunk(*b"ds64"))??;
+        if &id != b"ds64" {
+            return Err(WaveFileReadError::MissingChunk(*b"ds64"));
+        }
+        let ds64 = &bytes[12..][range];
+        if ds64.len() < 24 {
+            return Err(WaveFileReadError::TruncatedChunk(id));
+        }
+        chunks.data_size = Some(usize::try_from(u64_at(ds64, 8)).unwrap_or(usize::MAX));
+        (u64_at(ds64, 0), Some(u64_at(ds64, 16)))
+    };
+    // The RIFF size is often wrong in files written by other software, so trust the actual length if it is shorter.
+    let riff_end = usize::try_from(riff_size.saturating_add(8)).unwrap_or(usize::MAX).min(bytes.len());
+    chunks.bytes = &bytes[12..riff_end];
+    let body = chunks.bytes;
+
+    let mut format = None;
+    let mut data = None;
+    let mut metadata = Metadata::default();
+    let mut total_frames = None;
+    for chunk in chunks {
+        let (id, range) = chunk?;
+        let chunk = &body[range.clone()];
+        if metadata.parse_chunk(id, chunk)? {
+            continue;
+        }
+        match &id {
+            b"fmt " => format = Some(parse_format(chunk)?),
+            b"fact" if chunk.len() < 4 => return Err(WaveFileReadError::TruncatedChunk(id)),
+            // In an RF64 file, a length that doesn't fit in 32 bits is in the `ds64` chunk instead.
+            b"fact" => {
+                total_frames = Some(match (u32_at(chunk, 0), ds64_frames) {
+                    (u32::MAX, Some(frames)) => frames,
+                    (frames, _) => u64::from(frames),
+                });
+            }
+            b"data" => data = Some(range.start + 12..range.end + 12),
+            _ => {}
+        }
+    }
+    let file = WaveFile {
+        metadata,
+        total_frames,
+        ..format.ok_or(WaveFileReadError::MissingChunk(*b"fmt "))?
+    };
+    let data = data.ok_or(WaveFileReadError::MissingChunk(*b"data"))?;
+    Ok((file, data))
+}
+
+impl<'a> WaveFile<'a> {
+    /// Parse a [`WaveFile`] from the bytes of a RIFF/WAVE (or RF64/BW64) file, borrowing the sample data from `bytes`.
+    ///
+    /// The `ds64`, `fmt `, `fact` and `data` chunks are read, and every other chunk is read into the [`Metadata`].
+    /// # Errors
+    /// Returns a [`WaveFileReadError`] if `bytes` is not a valid RIFF/WAVE file, or if it uses a format (or `WAVE_FORMAT_EXTENSIBLE` sub-format) other than PCM, floating point,
+    /// G.711 or IMA ADPCM.
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, WaveFileReadError> {
+        let (file, data) = parse_header(bytes)?;
+        Ok(Self {
+            data: Cow::Borrowed(&bytes[data]),
+            ..file
+        })
+    }
+
+    /// Read a [`WaveFile`] from a reader, until the end of the reader.
+    /// # Errors
+    /// Returns a [`WaveFileReadError::Io`] if reading from the reader fails, or any other [`WaveFileReadError`] for the same reasons as [`WaveFile::parse`].
+    pub fn read(reader: &mut impl Read) -> Result<WaveFile<'static>, WaveFileReadError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let (file, data) = parse_header(&bytes)?;
+        bytes.truncate(data.end);
+        bytes.drain(..data.start);
+        Ok(WaveFile { data: Cow::Owned(bytes), ..file })
+    }
+}
@@ -0,0 +1,140 @@
+use std::{
+    io::{Seek, SeekFrom, Write},
+    marker::PhantomData,
+    num::NonZeroU16,
+};
+
+use super::{Format, SampleExt, WaveFile, WaveFileWriteError};
+use crate::Block;
+
+/// A writer that streams frames to a RIFF/WAVE file as they are produced, instead of collecting them into a [`WaveFile`] first.
+///
+/// The sizes in the RIFF and `data` chunk headers are written as `0xFFFFFFFF` placeholders, which [`WaveFile::parse`] reads as "until the end of the file", and are patched by
+/// [`WaveWriter::finalize`] (or when the writer is dropped). A `JUNK` chunk is reserved at the start of the file so that it can be turned into an RF64 file if it grows past 4 GiB.
+pub struct WaveWriter<W: Write + Seek, T: SampleExt, const N: usize> {
+    writer: Option<W>,
+    start: u64,
+    fact_offset: Option<u64>,
+    data_offset: u64,
+    data_len: u64,
+    buffer: Vec<u8>,
+    sample: PhantomData<T>,
+}
+
+impl<W: Write + Seek, T: SampleExt, const N: usize> WaveWriter<W, T, N> {
+    const BLOCK_ALIGN: u64 = T::BYTES_PER_SAMPLE as u64 * N as u64;
+
+    /// Create a new [`WaveWriter`], writing the header of the file to `writer` at its current position.
+    /// # Errors
+    /// Returns a [`WaveFileWriteError::Io`] if writing to the writer fails, or [`WaveFileWriteError::InvalidChannels`] if `N` does not fit in a [`NonZeroU16`].
+    pub fn new(mut writer: W, sample_rate: u32) -> Result<Self, WaveFileWriteError> {
+        let channels = u16::try_from(N).ok().and_then(NonZeroU16::new).ok_or(WaveFileWriteError::InvalidChannels)?;
+        let format_chunk = WaveFile::from_raw_data(&[], T::SAMPLE_FORMAT, channels, sample_rate, T::BYTES_PER_SAMPLE).format_chunk();
+        let start = writer.stream_position()?;
+        writer.write_all(b"RIFF")?;
+        writer.write_all(&u32::MAX.to_le_bytes())?;
+        writer.write_all(b"WAVE")?;
+        // Reserve space for a `ds64` chunk, in case the file needs to become an RF64 file.
+        writer.write_all(b"JUNK")?;
+        writer.write_all(&28_u32.to_le_bytes())?;
+        writer.write_all(&[0; 28])?;
+        writer.write_all(b"fmt ")?;
+        writer.write_all(&u32::try_from(format_chunk.len()).map_err(|_| WaveFileWriteError::DataTooLong)?.to_le_bytes())?;
+        writer.write_all(&format_chunk)?;
+        let fact_offset = if T::SAMPLE_FORMAT == Format::PulseCodeModulation {
+            None
+        } else {
+            writer.write_all(b"fact")?;
+            writer.write_all(&4_u32.to_le_bytes())?;
+            let fact_offset = writer.stream_position()?;
+            writer.write_all(&u32::MAX.to_le_bytes())?;
+            Some(fact_offset)
+        };
+        writer.write_all(b"data")?;
+        let data_offset = writer.stream_position()?;
+        writer.write_all(&u32::MAX.to_le_bytes())?;
+        Ok(Self {
+            writer: Some(writer),
+            start,
+            fact_offset,
+            data_offset,
+            data_len: 0,
+            buffer: Vec::new(),
+            sample: PhantomData,
+        })
+    }
+
+    /// Append frames to the `data` chunk.
+    /// # Errors
+    /// Returns a [`WaveFileWriteError::Io`] if writing to the writer fails.
+    #[allow(clippy::missing_panics_doc, reason = "the writer is only taken out when `self` is consumed")]
+    pub fn write_frames<S: Into<Block<T, N>>>(&mut self, frames: impl IntoIterator<Item = S>) -> Result<(), WaveFileWriteError> {
+        self.buffer.clear();
+        for Block(samples) in frames.into_iter().map(Into::into) {
+            for sample in samples {
+                self.buffer.extend_from_slice(sample.to_wav_bytes().as_ref());
+            }
+        }
+        self.writer.as_mut().expect("writer has not been finalized").write_all(&self.buffer)?;
+        self.data_len += self.buffer.len() as u64;
+        Ok(())
+    }
+
+    /// Return the number of frames written so far.
+    #[must_use]
+    pub const fn frames_written(&self) -> u64 {
+        self.data_len / Self::BLOCK_ALIGN
+    }
+
+    /// Patch the sizes in the headers, turning the file into an RF64 file if it is too big for a RIFF file, and return the underlying writer positioned at the end of the file.
+    /// # Errors
+    /// Returns a [`WaveFileWriteError::Io`] if writing to or seeking in the writer fails.
+    #[allow(clippy::missing_panics_doc, reason = "the writer is only taken out when `self` is consumed")]
+    pub fn finalize(mut self) -> Result<W, WaveFileWriteError> {
+        // Taking the writer out first keeps `drop` from patching the headers (and padding the data) a second time if this fails partway through.
+        let mut writer = self.writer.take().expect("writer has not been finalized");
+        self.patch_headers(&mut writer)?;
+        Ok(writer)
+    }
+
+    fn patch_headers(&self, writer: &mut W) -> Result<(), WaveFileWriteError> {
+        let padding = self.data_len % 2;
+        writer.write_all(&[0; 1][..usize::from(padding == 1)])?;
+        let end = writer.stream_position()?;
+        let riff_len = end - self.start - 8;
+        let frames = self.data_len / Self::BLOCK_ALIGN;
+        if let Ok(riff_len) = u32::try_from(riff_len) {
+            writer.seek(SeekFrom::Start(self.start + 4))?;
+            writer.write_all(&riff_len.to_le_bytes())?;
+            writer.seek(SeekFrom::Start(self.data_offset))?;
+            writer.write_all(&u32::try_from(self.data_len).expect("data is shorter than the RIFF chunk").to_le_bytes())?;
+            if let Some(fact_offset) = self.fact_offset {
+                writer.seek(SeekFrom::Start(fact_offset))?;
+                writer.write_all(&u32::try_from(frames).unwrap_or(u32::MAX).to_le_bytes())?;
+            }
+        } else {
+            // The 32-bit sizes stay as `0xFFFFFFFF`, and the `JUNK` chunk becomes the `ds64` chunk holding the real sizes.
+            writer.seek(SeekFrom::Start(self.start))?;
+            writer.write_all(b"RF64")?;
+            writer.seek(SeekFrom::Start(self.start + 12))?;
+            writer.write_all(b"ds64")?;
+            writer.write_all(&28_u32.to_le_bytes())?;
+            writer.write_all(&riff_len.to_le_bytes())?;
+            writer.write_all(&self.data_len.to_le_bytes())?;
+            writer.write_all(&frames.to_le_bytes())?;
+            writer.write_all(&0_u32.to_le_bytes())?;
+        }
+        writer.seek(SeekFrom::Start(end))?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+impl<W: Write + Seek, T: SampleExt, const N: usize> Drop for WaveWriter<W, T, N> {
+    fn drop(&mut self) {
+        if let Some(mut writer) = self.writer.take() {
+            // Errors can't be reported from `drop`, and the placeholder sizes still leave a readable file if patching fails.
+            let _ = self.patch_headers(&mut writer);
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use std::num::NonZeroU16;
+
+use blerp::{
+    aiff::{self, AiffReadError, AiffWriteError, Compression},
+    wavefile::{CuePoint, Format, WaveFile},
+};
+use common::sine;
+use cpal::I24;
+
+mod common;
+
+fn write_to_vec(file: &WaveFile, compression: Compression) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    aiff::write_with_compression(file, &mut bytes, compression).unwrap();
+    bytes
+}
+
+/// Build an AIFF or AIFF-C file from its chunks.
+fn form(kind: &[u8; 4], chunks: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+    let mut body = kind.to_vec();
+    for (id, chunk) in chunks {
+        body.extend_from_slice(*id);
+        body.extend_from_slice(&u32::try_from(chunk.len()).unwrap().to_be_bytes());
+        body.extend_from_slice(chunk);
+        if chunk.len() % 2 == 1 {
+            body.push(0);
+        }
+    }
+    let mut bytes = b"FORM".to_vec();
+    bytes.extend_from_slice(&u32::try_from(body.len()).unwrap().to_be_bytes());
+    bytes.extend_from_slice(&body);
+    bytes
+}
+
+/// The `COMM` chunk of a mono file with the given number of frames and bits per sample at 44.1 kHz.
+fn common(frames: u32, bits: u16, compression: Option<&[u8; 4]>) -> Vec<u8> {
+    let mut chunk = 1_u16.to_be_bytes().to_vec();
+    chunk.extend_from_slice(&frames.to_be_bytes());
+    chunk.extend_from_slice(&bits.to_be_bytes());
+    // 44100 as an 80-bit extended precision number.
+    chunk.extend_from_slice(&[0x40, 0x0E, 0xAC, 0x44, 0, 0, 0, 0, 0, 0]);
+    if let Some(compression) = compression {
+        chunk.extend_from_slice(compression);
+        chunk.extend_from_slice(&[0, 0]);
+    }
+    chunk
+}
+
+fn sound(data: &[u8]) -> Vec<u8> {
+    let mut chunk = vec![0; 8];
+    chunk.extend_from_slice(data);
+    chunk
+}
+
+#[test]
+fn round_trip() {
+    macro_rules! round_trip {
+        ($($ty:ty => $compression:ident),+) => {
+            $(
+                let file = WaveFile::from_samples(sine::<$ty>(), 44100).unwrap();
+                let parsed = aiff::parse(&write_to_vec(&file, Compression::$compression)).unwrap();
+                assert_eq!(parsed.format, file.format, "{}", stringify!($ty));
+                assert_eq!(parsed.channels, file.channels, "{}", stringify!($ty));
+                assert_eq!(parsed.sample_rate, 44100, "{}", stringify!($ty));
+                assert_eq!(parsed.bytes_per_sample, file.bytes_per_sample, "{}", stringify!($ty));
+                assert_eq!(parsed.valid_bits_per_sample, file.valid_bits_per_sample, "{}", stringify!($ty));
+                assert_eq!(parsed.data, file.data, "{}", stringify!($ty));
+            )+
+        };
+    }
+    round_trip!(u8 => None, i16 => None, I24 => None, i32 => None, u8 => Sowt, i16 => Sowt, I24 => Sowt, i32 => Sowt, f32 => Float32, f64 => Float64);
+
+    // `write` picks the compression type from the sample format.
+    for (file, kind) in [
+        (WaveFile::from_samples(sine::<i16>(), 44100).unwrap(), b"AIFF"),
+        (WaveFile::from_samples(sine::<f32>(), 44100).unwrap(), b"AIFC"),
+    ] {
+        let mut bytes = Vec::new();
+        aiff::write(&file, &mut bytes).unwrap();
+        assert_eq!(&bytes[8..12], kind);
+        assert_eq!(aiff::parse(&bytes).unwrap().data, file.data);
+    }
+}
+
+#[test]
+fn sample_rates() {
+    for sample_rate in [1, 8000, 11025, 22050, 44100, 48000, 88200, 96000, 176_400, 192_000, 384_000, u32::MAX] {
+        let file = WaveFile::from_raw_data(&[0, 0], Format::PulseCodeModulation, NonZeroU16::MIN, sample_rate, 2);
+        assert_eq!(aiff::parse(&write_to_vec(&file, Compression::None)).unwrap().sample_rate, sample_rate);
+    }
+}
+
+#[test]
+fn byte_order() {
+    let file = aiff::parse(&form(b"AIFF", &[(b"COMM", &common(2, 16, None)), (b"SSND", &sound(&[0x12, 0x34, 0x80, 0x00]))])).unwrap();
+    assert_eq!(file.sample_rate, 44100);
+    assert_eq!(file.frames::<i16, 1>().unwrap().map(<[i16; 1]>::from).collect::<Vec<_>>(), [[0x1234], [i16::MIN]]);
+
+    let file = aiff::parse(&form(b"AIFC", &[(b"COMM", &common(2, 16, Some(b"sowt"))), (b"SSND", &sound(&[0x34, 0x12, 0x00, 0x80]))])).unwrap();
+    assert_eq!(file.frames::<i16, 1>().unwrap().map(<[i16; 1]>::from).collect::<Vec<_>>(), [[0x1234], [i16::MIN]]);
+
+    let file = aiff::parse(&form(b"AIFC", &[(b"COMM", &common(1, 32, Some(b"fl32"))), (b"SSND", &sound(&0.5_f32.to_be_bytes()))])).unwrap();
+    assert_eq!(file.format, Format::FloatingPoint);
+    assert_eq!(file.frames::<f32, 1>().unwrap().map(<[f32; 1]>::from).collect::<Vec<_>>(), [[0.5]]);
+
+    // 8-bit AIFF samples are signed.
+    let file = aiff::parse(&form(b"AIFF", &[(b"COMM", &common(2, 8, None)), (b"SSND", &sound(&[0x7F, 0x80]))])).unwrap();
+    assert_eq!(file.frames::<i8, 1>().unwrap().map(<[i8; 1]>::from).collect::<Vec<_>>(), [[i8::MAX], [i8::MIN]]);
+
+    // 12-bit samples are stored left-justified in 16 bits.
+    let file = aiff::parse(&form(b"AIFF", &[(b"COMM", &common(1, 12, None)), (b"SSND", &sound(&[0x12, 0x30]))])).unwrap();
+    assert_eq!((file.bytes_per_sample, file.valid_bits_per_sample), (2, 12));
+    assert_eq!(&*file.data, [0x30, 0x12]);
+
+    // Writing puts the samples back in big-endian order.
+    let bytes = write_to_vec(&WaveFile::from_samples([0x1234_i16], 44100).unwrap(), Compression::None);
+    assert_eq!(&bytes[bytes.len() - 2..], [0x12, 0x34]);
+}
+
+#[test]
+fn metadata() {
+    let mut file = WaveFile::from_samples(sine::<i16>(), 44100).unwrap();
+    file.metadata.info.title = Some("Title".to_owned());
+    file.metadata.info.artist = Some("Artist".to_owned());
+    file.metadata.info.comment = Some("Comment".to_owned());
+    file.metadata.info.other.push((*b"ICOP", "Copyright".to_owned()));
+    file.metadata.cue_points = vec![
+        CuePoint {
+            id: 1,
+            position: 10,
+            label: Some("Odd".to_owned()),
+            ..CuePoint::default()
+        },
+        CuePoint {
+            id: 2,
+            position: 500,
+            label: Some("Even".to_owned()),
+            ..CuePoint::default()
+        },
+        CuePoint {
+            id: 3,
+            position: 999,
+            ..CuePoint::default()
+        },
+    ];
+    let parsed = aiff::parse(&write_to_vec(&file, Compression::None)).unwrap();
+    assert_eq!(parsed.metadata.info, file.metadata.info);
+    assert_eq!(parsed.metadata.cue_points, file.metadata.cue_points);
+    assert_eq!(parsed.data, file.data);
+}
+
+#[test]
+fn errors() {
+    assert!(matches!(aiff::parse(b"RIFF\0\0\0\0WAVE"), Err(AiffReadError::BadMagic)));
+    assert!(matches!(aiff::parse(&form(b"AIFF", &[(b"SSND", &sound(&[0, 0]))])), Err(AiffReadError::MissingChunk(id)) if &id == b"COMM"));
+    assert!(matches!(aiff::parse(&form(b"AIFF", &[(b"COMM", &common(1, 16, None))])), Err(AiffReadError::MissingChunk(id)) if &id == b"SSND"));
+    assert!(aiff::parse(&form(b"AIFF", &[(b"COMM", &common(0, 16, None))])).unwrap().data.is_empty());
+    assert!(matches!(
+        aiff::parse(&form(b"AIFC", &[(b"COMM", &common(1, 16, Some(b"ima4"))), (b"SSND", &sound(&[0; 34]))])),
+        Err(AiffReadError::UnsupportedCompression(id)) if &id == b"ima4"
+    ));
+    assert!(matches!(aiff::parse(&form(b"AIFF", &[(b"COMM", &common(1, 0, None))])), Err(AiffReadError::InvalidCommonChunk)));
+    assert!(matches!(aiff::parse(&form(b"AIFF", &[(b"COMM", &common(1, 16, None)[..10])])), Err(AiffReadError::TruncatedChunk(id)) if &id == b"COMM"));
+    let mut truncated = form(b"AIFF", &[(b"COMM", &common(4, 16, None)), (b"SSND", &sound(&[0; 8]))]);
+    truncated.truncate(truncated.len() - 4);
+    assert!(matches!(aiff::parse(&truncated), Err(AiffReadError::TruncatedChunk(id)) if &id == b"SSND"));
+
+    let mut bytes = Vec::new();
+    assert!(matches!(
+        aiff::write_with_compression(&WaveFile::from_samples(sine::<i16>(), 44100).unwrap(), &mut bytes, Compression::Float32),
+        Err(AiffWriteError::UnsupportedSampleFormat { .. })
+    ));
+}
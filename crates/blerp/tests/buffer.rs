@@ -0,0 +1,105 @@
+use std::num::NonZeroU16;
+
+use blerp::{
+    buffer::{AudioBuffer, Layout},
+    Block,
+};
+
+const STEREO: NonZeroU16 = NonZeroU16::new(2).unwrap();
+
+fn frames() -> Vec<[f32; 2]> {
+    (0..37).map(|frame| [frame as f32 / 64., -(frame as f32) / 128.]).collect()
+}
+
+#[test]
+fn layouts() {
+    let interleaved = AudioBuffer::from_blocks(frames(), Layout::Interleaved);
+    let planar = AudioBuffer::from_blocks(frames(), Layout::Planar);
+    assert_eq!((interleaved.channels(), interleaved.frames(), interleaved.layout()), (STEREO, 37, Layout::Interleaved));
+    assert_eq!(&interleaved.as_slice()[..4], [0., 0., 1. / 64., -1. / 128.]);
+    assert_eq!(&planar.as_slice()[..2], [0., 1. / 64.]);
+    assert_eq!(planar.as_slice()[37], 0.);
+    assert_eq!(planar.as_slice()[38], -1. / 128.);
+
+    for buffer in [&interleaved, &planar] {
+        assert_eq!(buffer.get(1, 3), Some(-3. / 128.));
+        assert_eq!(buffer.get(2, 0), None);
+        assert_eq!(buffer.get(0, 37), None);
+        assert_eq!(buffer.channel(0).collect::<Vec<_>>(), frames().iter().map(|frame| frame[0]).collect::<Vec<_>>());
+        assert_eq!(buffer.frame(5).collect::<Vec<_>>(), frames()[5]);
+        assert_eq!(buffer.blocks::<2>().map(<[f32; 2]>::from).collect::<Vec<_>>(), frames());
+    }
+    assert_eq!(interleaved.clone().into_layout(Layout::Planar), planar);
+    assert_eq!(planar.clone().into_layout(Layout::Interleaved), interleaved);
+
+    let mut buffer = AudioBuffer::<i16>::new(NonZeroU16::new(3).unwrap(), 4, Layout::Planar);
+    *buffer.get_mut(2, 1).unwrap() = 7;
+    assert!(buffer.get_mut(3, 0).is_none());
+    assert_eq!(buffer.as_slice(), [0, 0, 0, 0, 0, 0, 0, 0, 0, 7, 0, 0]);
+    assert_eq!(buffer.into_layout(Layout::Interleaved).into_samples(), [0, 0, 0, 0, 0, 7, 0, 0, 0, 0, 0, 0]);
+
+    // Empty buffers have empty channels, rather than panicking.
+    let empty = AudioBuffer::<f32>::new(STEREO, 0, Layout::Interleaved);
+    assert_eq!(empty.channel(1).count(), 0);
+    assert!(empty.into_layout(Layout::Planar).as_slice().is_empty());
+}
+
+#[test]
+fn arithmetic() {
+    for (layout, other_layout) in [(Layout::Interleaved, Layout::Interleaved), (Layout::Planar, Layout::Planar), (Layout::Interleaved, Layout::Planar)] {
+        let original = AudioBuffer::from_blocks(frames(), layout);
+        let other = AudioBuffer::from_blocks(frames().into_iter().map(|[left, right]| [right, left]), other_layout);
+        let expect = |buffer: &AudioBuffer<f32>, operation: fn([f32; 2], [f32; 2]) -> [f32; 2]| {
+            let expected = frames().into_iter().map(|frame| operation(frame, [frame[1], frame[0]]));
+            assert_eq!(
+                buffer.blocks::<2>().map(<[f32; 2]>::from).collect::<Vec<_>>(),
+                expected.collect::<Vec<_>>(),
+                "{layout:?} with {other_layout:?}"
+            );
+            assert_eq!(buffer.layout(), layout);
+        };
+
+        let mut buffer = original.clone();
+        buffer += &other;
+        expect(&buffer, |[a, b], [c, d]| [a + c, b + d]);
+        let mut buffer = original.clone();
+        buffer -= &other;
+        expect(&buffer, |[a, b], [c, d]| [a - c, b - d]);
+        let mut buffer = original.clone();
+        buffer *= &other;
+        expect(&buffer, |[a, b], [c, d]| [a * c, b * d]);
+        let mut buffer = original.clone();
+        buffer *= 0.5;
+        expect(&buffer, |[a, b], _| [a * 0.5, b * 0.5]);
+        let mut buffer = original.clone();
+        buffer.mix(&other, 0.25);
+        expect(&buffer, |[a, b], [c, d]| [c.mul_add(0.25, a), d.mul_add(0.25, b)]);
+    }
+}
+
+#[test]
+fn matches_blocks() {
+    // Mixing buffers gives the same result as summing blocks, without the round trip through `f64`.
+    let tracks: Vec<Vec<Block<f64, 2>>> = (0..5)
+        .map(|track| (0..100).map(|frame| Block::from([f64::from(track * frame) / 1000., -f64::from(frame) / 100.])).collect())
+        .collect();
+    let summed: Vec<Block<f64, 2>> = (0..100).map(|frame| tracks.iter().map(|track| track[frame]).sum()).collect();
+    let mut bus = AudioBuffer::new(STEREO, 100, Layout::Planar);
+    for track in &tracks {
+        bus += &AudioBuffer::from_blocks(track.iter().copied(), Layout::Interleaved);
+    }
+    assert_eq!(bus.blocks::<2>().collect::<Vec<_>>(), summed);
+
+    let block = Block::from([0.5, -0.25]);
+    assert_eq!(<[f64; 2]>::from(block + Block::from([0.25, 0.25])), [0.75, 0.]);
+    assert_eq!(<[f64; 2]>::from(block - Block::from([0.25, 0.25])), [0.25, -0.5]);
+    assert_eq!(<[f64; 2]>::from(block * 2.), [1., -0.5]);
+    assert_eq!(<[i16; 1]>::from(Block::from(1000_i16) + Block::from(-3000_i16)), [-2000]);
+}
+
+#[test]
+#[should_panic(expected = "same number of channels and frames")]
+fn mismatched_sizes() {
+    let mut buffer = AudioBuffer::<f32>::new(STEREO, 10, Layout::Interleaved);
+    buffer += &AudioBuffer::new(STEREO, 11, Layout::Interleaved);
+}
@@ -0,0 +1,87 @@
+use blerp::processing::channels::{fold_to_mono, mid_side_decode, mid_side_encode, Downmix, PanLaw, Panner};
+
+const LAWS: [PanLaw; 4] = [PanLaw::Minus3Db, PanLaw::Minus4_5Db, PanLaw::Minus6Db, PanLaw::Linear];
+
+fn decibels(gain: f64) -> f64 {
+    20. * gain.log10()
+}
+
+#[test]
+fn pan_laws() {
+    for (law, centre) in LAWS.into_iter().zip([-3.01, -4.52, -6.02, -6.02]) {
+        assert!((decibels(law.centre_gain()) - centre).abs() < 0.01, "{law:?}: {} dB", decibels(law.centre_gain()));
+        for (pan, expected) in [(-1., [1., 0.]), (1., [0., 1.]), (-2., [1., 0.]), (2., [0., 1.])] {
+            let gains = law.gains(pan);
+            assert!(gains.iter().zip(expected).all(|(gain, expected)| (gain - expected).abs() < 1e-12), "{law:?} at {pan}: {gains:?}");
+        }
+        // Panning the other way swaps the channels.
+        let [left, right] = law.gains(0.3);
+        let [mirrored_left, mirrored_right] = law.gains(-0.3);
+        assert!((left - mirrored_right).abs() < 1e-12 && (right - mirrored_left).abs() < 1e-12, "{law:?}");
+        assert!(right > left, "{law:?}");
+    }
+    // Only the constant power law keeps the total power the same across the stereo field.
+    for pan in [-0.7, -0.2, 0., 0.4, 0.9] {
+        let [left, right] = PanLaw::Minus3Db.gains(pan);
+        assert!((left.hypot(right) - 1.).abs() < 1e-12);
+        let [left, right] = PanLaw::Minus6Db.gains(pan);
+        assert!((left + right - 1.).abs() < 1e-12);
+    }
+}
+
+#[test]
+fn panning_and_folding() {
+    let panner = Panner::new(-1., PanLaw::default());
+    assert_eq!(<[f32; 2]>::from(panner.pan(0.5_f32)), [0.5, 0.]);
+    for law in LAWS {
+        let panner = Panner::new(0., law);
+        for sample in [-1., -0.25, 0., 0.5, 0.99] {
+            let [left, right] = <[f64; 2]>::from(panner.pan(sample));
+            assert_eq!(left, right);
+            assert!((left - sample * law.centre_gain()).abs() < 1e-12);
+            let [folded] = <[f64; 1]>::from(fold_to_mono([left, right], law));
+            assert!((folded - sample).abs() < 1e-12, "{law:?}: {folded} != {sample}");
+        }
+    }
+    // Integer samples are converted on the way through, which truncates them.
+    let [left, right] = <[i16; 2]>::from(Panner::new(0., PanLaw::Minus6Db).pan(-2000_i16));
+    assert_eq!(left, right);
+    assert!((-1000 - left).abs() <= 1);
+    assert!((-2000 - <[i16; 1]>::from(fold_to_mono([left, right], PanLaw::Minus6Db))[0]).abs() <= 2);
+}
+
+#[test]
+fn mid_side() {
+    assert_eq!(<[f64; 2]>::from(mid_side_encode([0.5, 0.5])), [0.5, 0.]);
+    assert_eq!(<[f64; 2]>::from(mid_side_encode([0.5, -0.5])), [0., 0.5]);
+    assert_eq!(<[f64; 2]>::from(mid_side_encode([1., 0.])), [0.5, 0.5]);
+    for frame in [[0.25, -0.75], [1., 1.], [-1., 1.], [0.3, 0.1]] {
+        let decoded = <[f64; 2]>::from(mid_side_decode(mid_side_encode(frame)));
+        assert!(decoded.iter().zip(frame).all(|(decoded, original)| (decoded - original).abs() < 1e-12), "{decoded:?} != {frame:?}");
+    }
+    assert_eq!(<[i16; 2]>::from(mid_side_decode(mid_side_encode([1000_i16, -2000]))), [1000, -2000]);
+}
+
+#[test]
+fn downmix() {
+    let itu = Downmix::default();
+    let [left, right] = <[f64; 2]>::from(itu.apply([0.5, -0.25, 0.4, 1., 0.2, -0.1]));
+    let centre = 0.4 * std::f64::consts::FRAC_1_SQRT_2;
+    assert!((left - (0.5 + centre + 0.2 * std::f64::consts::FRAC_1_SQRT_2)).abs() < 1e-12);
+    assert!((right - (-0.25 + centre - 0.1 * std::f64::consts::FRAC_1_SQRT_2)).abs() < 1e-12);
+    // Each channel ends up only where it belongs, and the LFE channel is left out.
+    assert_eq!(<[f64; 2]>::from(itu.apply([1., 0., 0., 0., 0., 0.])), [1., 0.]);
+    assert_eq!(<[f64; 2]>::from(itu.apply([0., 0., 0., 1., 0., 0.])), [0., 0.]);
+    assert_eq!(<[f64; 2]>::from(itu.apply([0., 0., 0., 0., 0., 1.])), [0., std::f64::consts::FRAC_1_SQRT_2]);
+
+    // Full-scale samples in every channel clip without normalization, but not with it.
+    let with_lfe = Downmix { lfe: 0.5, ..Downmix::default() };
+    assert!(<[f64; 2]>::from(with_lfe.apply([1.; 6]))[0] > 1.);
+    let normalized = with_lfe.normalized();
+    for frame in [[1.; 6], [-1.; 6], [1., -1., 1., 1., 1., -1.]] {
+        let [left, right] = <[f64; 2]>::from(normalized.apply(frame));
+        assert!(left.abs() <= 1. + 1e-12 && right.abs() <= 1. + 1e-12, "{left}, {right}");
+    }
+    assert!((<[f64; 2]>::from(normalized.apply([1.; 6]))[0] - 1.).abs() < 1e-12);
+    assert_eq!(<[i16; 2]>::from(normalized.apply([i16::MAX; 6])), [i16::MAX; 2]);
+}
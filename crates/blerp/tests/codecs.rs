@@ -0,0 +1,157 @@
+use std::{f64::consts::TAU, num::NonZeroU16};
+
+use blerp::wavefile::{Codec, Format, WaveFile, WaveFileReadError, WaveFileWriteError};
+use common::write_to_vec;
+use cpal::Sample;
+
+mod common;
+
+fn sine(len: usize) -> impl Iterator<Item = [f64; 2]> {
+    (0..len).map(|sample| {
+        let phase = TAU * 440. * sample as f64 / 44100.;
+        [phase.sin() * 0.8, phase.cos() * 0.3]
+    })
+}
+
+/// Build a mono WAV file from the body of its `fmt ` chunk and its sample data.
+fn wave(format_chunk: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut bytes = b"RIFF".to_vec();
+    bytes.extend_from_slice(&u32::try_from(4 + 8 + format_chunk.len() + 8 + data.len()).unwrap().to_le_bytes());
+    bytes.extend_from_slice(b"WAVE");
+    for (id, chunk) in [(b"fmt ", format_chunk), (b"data", data)] {
+        bytes.extend_from_slice(id);
+        bytes.extend_from_slice(&u32::try_from(chunk.len()).unwrap().to_le_bytes());
+        bytes.extend_from_slice(chunk);
+    }
+    bytes
+}
+
+fn format_chunk(tag: u16, block_align: u16, bits_per_sample: u16, extra: &[u8]) -> Vec<u8> {
+    let mut chunk = tag.to_le_bytes().to_vec();
+    chunk.extend_from_slice(&1_u16.to_le_bytes());
+    chunk.extend_from_slice(&8000_u32.to_le_bytes());
+    chunk.extend_from_slice(&(8000 * u32::from(block_align)).to_le_bytes());
+    chunk.extend_from_slice(&block_align.to_le_bytes());
+    chunk.extend_from_slice(&bits_per_sample.to_le_bytes());
+    chunk.extend_from_slice(extra);
+    chunk
+}
+
+#[test]
+fn g711() {
+    for (codec, format, silence, max, min) in [(Codec::ALaw, Format::ALaw, 0xD5, 0xAA, 0x2A), (Codec::MuLaw, Format::MuLaw, 0xFF, 0x80, 0x00)] {
+        let file = WaveFile::encode([0_i16, i16::MAX, i16::MIN], 8000, codec).unwrap();
+        assert_eq!((file.format, file.bytes_per_sample, file.valid_bits_per_sample), (format, 1, 8));
+        assert_eq!(&*file.data, [silence, max, min]);
+
+        // Every byte decodes to a sample that encodes back to the same byte, apart from µ-law's negative zero.
+        for byte in 0..=u8::MAX {
+            let decoded: [i16; 1] = WaveFile::from_raw_data(&[byte], format, NonZeroU16::MIN, 8000, 1).frames::<i16, 1>().unwrap().next().unwrap().into();
+            let encoded = WaveFile::encode(decoded, 8000, codec).unwrap();
+            assert_eq!(encoded.data[0], if format == Format::MuLaw && byte == 0x7F { 0xFF } else { byte }, "{format:?} {byte:#04x}");
+        }
+
+        // The error grows with the size of the sample, but stays within a few percent of it.
+        let file = WaveFile::encode(sine(1000), 44100, codec).unwrap();
+        let bytes = write_to_vec(&file);
+        let parsed = WaveFile::parse(&bytes).unwrap();
+        assert_eq!(parsed.format, format);
+        assert_eq!(parsed.frame_count(), 1000);
+        for (decoded, original) in parsed.frames::<f64, 2>().unwrap().zip(sine(1000)) {
+            for (decoded, original) in <[f64; 2]>::from(decoded).into_iter().zip(original) {
+                assert!((decoded - original).abs() <= original.abs() / 16. + 1. / 1024., "{format:?}: {decoded} != {original}");
+            }
+        }
+    }
+}
+
+#[test]
+fn ima_adpcm() {
+    // A block with a header of 0 at step index 0, then the largest positive step twice and six of the smallest.
+    let mut data = vec![0, 0, 0, 0, 0x77, 0, 0, 0];
+    let bytes = wave(&format_chunk(0x11, 8, 4, &[2, 0, 9, 0]), &data);
+    let file = WaveFile::parse(&bytes).unwrap();
+    assert_eq!((file.format, file.bytes_per_sample, file.valid_bits_per_sample), (Format::ImaAdpcm, 8, 4));
+    let frames = file.frames::<i16, 1>().unwrap().map(<[i16; 1]>::from).collect::<Vec<_>>();
+    assert_eq!(frames, [[0], [11], [41], [45], [48], [51], [54], [56], [58]]);
+    // The `fact` chunk cuts off the padding at the end of the last block.
+    data.splice(0..2, 100_i16.to_le_bytes());
+    let mut file = WaveFile::from_raw_data(&data, Format::ImaAdpcm, NonZeroU16::MIN, 8000, 8);
+    file.total_frames = Some(3);
+    let bytes = write_to_vec(&file);
+    let parsed = WaveFile::parse(&bytes).unwrap();
+    assert_eq!(parsed.total_frames, Some(3));
+    assert_eq!(parsed.frames::<i16, 1>().unwrap().map(<[i16; 1]>::from).collect::<Vec<_>>(), [[100], [111], [141]]);
+
+    let file = WaveFile::encode(sine(2000), 44100, Codec::ImaAdpcm { frames_per_block: 505 }).unwrap();
+    // 4 blocks, each with 256 bytes for each channel.
+    assert_eq!((file.bytes_per_sample, file.block_align(), file.data.len()), (256, 512, 4 * 512));
+    let bytes = write_to_vec(&file);
+    let format = &bytes[20..40];
+    assert_eq!(u16::from_le_bytes([format[14], format[15]]), 4, "bits per sample");
+    assert_eq!(u16::from_le_bytes([format[18], format[19]]), 505, "frames per block");
+    let parsed = WaveFile::parse(&bytes).unwrap();
+    assert_eq!(parsed.total_frames, Some(2000));
+    assert_eq!(parsed.frame_count(), 2000);
+    assert_eq!(parsed.data, file.data);
+
+    let frames = parsed.frames::<f64, 2>().unwrap().map(<[f64; 2]>::from).collect::<Vec<_>>();
+    assert_eq!(frames.len(), 2000);
+    // The same samples come out interleaved when the number of channels is only known at runtime.
+    assert_eq!(parsed.samples::<f64>().unwrap().collect::<Vec<_>>(), frames.concat());
+    // The first few samples of the first block are spent finding the step size.
+    for (decoded, original) in frames.iter().zip(sine(2000)).skip(20) {
+        for (decoded, original) in decoded.iter().zip(original) {
+            assert!((decoded - original).abs() < 0.02, "{decoded} != {original}");
+        }
+    }
+    // The first sample of each block is stored exactly.
+    for (index, original) in sine(2000).enumerate().step_by(505) {
+        assert_eq!(frames[index].map(i16::from_sample), original.map(i16::from_sample));
+    }
+}
+
+#[test]
+fn read_errors() {
+    let invalid = |chunk: Vec<u8>, data: &[u8]| WaveFile::parse(&wave(&chunk, data)).err();
+    assert!(matches!(invalid(format_chunk(6, 2, 16, &[0, 0]), &[0; 4]), Some(WaveFileReadError::InvalidBlockAlign(2))));
+    assert!(matches!(invalid(format_chunk(7, 0, 8, &[0, 0]), &[]), Some(WaveFileReadError::InvalidBlockAlign(0))));
+    // IMA ADPCM blocks need a header and at least one group of samples, in multiples of 4 bytes.
+    assert!(matches!(invalid(format_chunk(0x11, 4, 4, &[2, 0, 1, 0]), &[0; 4]), Some(WaveFileReadError::InvalidBlockAlign(4))));
+    assert!(matches!(invalid(format_chunk(0x11, 10, 4, &[2, 0, 13, 0]), &[0; 10]), Some(WaveFileReadError::InvalidBlockAlign(10))));
+    assert!(matches!(invalid(format_chunk(0x11, 8, 4, &[2, 0, 17, 0]), &[0; 8]), Some(WaveFileReadError::InvalidBlockAlign(8))));
+    let mut stereo = format_chunk(1, 3, 16, &[]);
+    stereo[2] = 2;
+    assert!(matches!(invalid(stereo, &[0; 6]), Some(WaveFileReadError::InvalidBlockAlign(3))));
+}
+
+#[test]
+fn write_errors() {
+    for frames_per_block in [0, 1, 500, 65529] {
+        assert!(matches!(
+            WaveFile::encode([[0_i16; 2]], 8000, Codec::ImaAdpcm { frames_per_block }),
+            Err(WaveFileWriteError::InvalidBlockAlign)
+        ));
+    }
+    assert!(matches!(WaveFile::encode([[0_i16; 0]], 8000, Codec::ALaw), Err(WaveFileWriteError::InvalidChannels)));
+
+    let write = |file: WaveFile| file.write(&mut Vec::new());
+    assert!(matches!(
+        write(WaveFile::from_raw_data(&[0; 4], Format::MuLaw, NonZeroU16::MIN, 8000, 2)),
+        Err(WaveFileWriteError::InvalidBlockAlign)
+    ));
+    assert!(matches!(
+        write(WaveFile::from_raw_data(&[0; 3], Format::PulseCodeModulation, NonZeroU16::MIN, 8000, 2)),
+        Err(WaveFileWriteError::InvalidBlockAlign)
+    ));
+    assert!(matches!(
+        write(WaveFile::from_raw_data(&[0; 6], Format::ImaAdpcm, NonZeroU16::MIN, 8000, 6)),
+        Err(WaveFileWriteError::InvalidBlockAlign)
+    ));
+    // A short last IMA ADPCM block is fine, as long as it is made of whole groups of samples.
+    assert!(write(WaveFile::from_raw_data(&[0; 12], Format::ImaAdpcm, NonZeroU16::MIN, 8000, 8)).is_ok());
+    assert!(matches!(
+        write(WaveFile::from_raw_data(&[0; 10], Format::ImaAdpcm, NonZeroU16::MIN, 8000, 8)),
+        Err(WaveFileWriteError::InvalidBlockAlign)
+    ));
+}
@@ -0,0 +1,83 @@
+//! Fixtures and helpers for rendering and measuring signals, shared by the integration tests.
+#![allow(dead_code, reason = "each test crate only uses some of the helpers")]
+
+use std::f64::consts::TAU;
+
+use blerp::{
+    processing::{envelope::Envelope, fm::FmVoice, modulation::Lfo, oscillator::Oscillator, wavetable::WavetableOscillator},
+    wavefile::WaveFile,
+};
+use cpal::{FromSample, Sample};
+
+/// The sample rate that the generators are tested at, unless a test needs a particular one.
+pub const SAMPLE_RATE: u32 = 44100;
+
+/// Write a [`WaveFile`] to a RIFF/WAVE file in memory.
+pub fn write_to_vec(file: &WaveFile) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    file.write(&mut bytes).unwrap();
+    bytes
+}
+
+/// 1000 frames of a 440 Hz stereo sine wave at 44.1 kHz, with the right channel a quarter of a cycle behind the left, for round-tripping through the file formats.
+pub fn sine<T: Sample + FromSample<f64>>() -> impl Iterator<Item = [T; 2]> {
+    (0..1000).map(|sample| {
+        let phase = TAU * 440. * f64::from(sample) / 44100.;
+        [T::from_sample(phase.sin() * 0.9), T::from_sample(-phase.cos() * 0.9)]
+    })
+}
+
+/// Something that makes one value at a time, like an oscillator or an envelope.
+pub trait Generator {
+    fn generate(&mut self) -> f64;
+}
+
+impl Generator for Oscillator {
+    fn generate(&mut self) -> f64 {
+        self.next_sample()
+    }
+}
+
+impl Generator for WavetableOscillator<'_> {
+    fn generate(&mut self) -> f64 {
+        self.next_sample()
+    }
+}
+
+impl Generator for FmVoice {
+    fn generate(&mut self) -> f64 {
+        self.next_sample()
+    }
+}
+
+impl Generator for Lfo {
+    fn generate(&mut self) -> f64 {
+        self.next_value()
+    }
+}
+
+impl Generator for Envelope {
+    fn generate(&mut self) -> f64 {
+        self.next_value()
+    }
+}
+
+/// Collect the next `len` values from a generator.
+pub fn render(generator: &mut impl Generator, len: usize) -> Vec<f64> {
+    (0..len).map(|_| generator.generate()).collect()
+}
+
+/// Return the magnitude of the DTFT of `signal` at `frequency`.
+pub fn magnitude(signal: &[f64], frequency: f64, sample_rate: u32) -> f64 {
+    let (real, imaginary) = signal.iter().enumerate().fold((0., 0.), |(real, imaginary), (index, sample)| {
+        let phase = TAU * frequency * index as f64 / f64::from(sample_rate);
+        (sample.mul_add(phase.cos(), real), sample.mul_add(phase.sin(), imaginary))
+    });
+    real.hypot(imaginary)
+}
+
+/// Measure the amplitude of the component at `frequency` (a whole number of hertz) in one second of `signal` at [`SAMPLE_RATE`], where every such frequency falls exactly on a
+/// DFT bin.
+pub fn amplitude(signal: &[f64], frequency: f64) -> f64 {
+    2. * magnitude(signal, frequency, SAMPLE_RATE) / signal.len() as f64
+}
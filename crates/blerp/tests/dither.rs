@@ -0,0 +1,108 @@
+use std::f64::consts::TAU;
+
+use blerp::{
+    processing::dither::{Dither, Quantizer},
+    wavefile::WaveFile,
+};
+use cpal::I24;
+
+const SAMPLE_RATE: f64 = 44100.;
+
+/// A quiet 1 kHz sine wave, a few LSBs of 16-bit audio in amplitude.
+fn quiet_sine(len: usize) -> Vec<f64> {
+    (0..len).map(|sample| (TAU * 1000. * sample as f64 / SAMPLE_RATE).sin() * 3.3 / 32768.).collect()
+}
+
+/// Quantize to 16 bits and return the error of each sample, in LSBs.
+fn errors(dither: Dither, input: &[f64]) -> Vec<f64> {
+    let mut quantizer = Quantizer::<1>::new(16, dither, 1);
+    input
+        .iter()
+        .map(|&sample| {
+            let [quantized] = quantizer.quantize::<f64, i16>(sample).into();
+            f64::from(quantized) - sample * 32768.
+        })
+        .collect()
+}
+
+fn mean_square(samples: impl IntoIterator<Item = f64>) -> f64 {
+    let (sum, count) = samples.into_iter().fold((0., 0), |(sum, count), sample| (sum + sample * sample, count + 1));
+    sum / f64::from(count)
+}
+
+#[test]
+fn deterministic() {
+    let input = quiet_sine(1000);
+    for dither in [Dither::Rectangular, Dither::Triangular, Dither::NoiseShaped] {
+        assert_eq!(errors(dither, &input), errors(dither, &input), "{dither:?}");
+        let mut other_seed = Quantizer::<1>::new(16, dither, 2);
+        let other: Vec<f64> = input
+            .iter()
+            .map(|&sample| f64::from(<[i16; 1]>::from(other_seed.quantize::<f64, i16>(sample))[0]) - sample * 32768.)
+            .collect();
+        assert_ne!(errors(dither, &input), other, "{dither:?}");
+    }
+}
+
+#[test]
+fn error_levels() {
+    let input = quiet_sine(44100);
+    // Rounding never moves a sample by more than half a step.
+    assert!(errors(Dither::None, &input).iter().all(|error| error.abs() <= 0.5));
+    // Rectangular dither adds ±0.5 LSB and triangular dither ±1 LSB of noise on top of the rounding.
+    assert!(errors(Dither::Rectangular, &input).iter().all(|error| error.abs() <= 1.));
+    assert!(errors(Dither::Triangular, &input).iter().all(|error| error.abs() <= 1.5));
+    // The total noise power of TPDF dither is 1/12 (rounding) + 1/6 (dither) = 1/4 LSB².
+    let power = mean_square(errors(Dither::Triangular, &input));
+    assert!((power - 0.25).abs() < 0.01, "{power}");
+    // With dither, the error is independent of the signal, so it averages out.
+    let mean = errors(Dither::Triangular, &input).iter().sum::<f64>() / 44100.;
+    assert!(mean.abs() < 0.01, "{mean}");
+}
+
+#[test]
+fn noise_shaping() {
+    let input = quiet_sine(44100);
+    // Estimate the low-frequency noise with a moving average, which only keeps frequencies below about 1 kHz.
+    let low_frequency_power = |errors: Vec<f64>| mean_square(errors.windows(32).map(|window| window.iter().sum::<f64>() / 32.));
+    let triangular = low_frequency_power(errors(Dither::Triangular, &input));
+    let shaped = low_frequency_power(errors(Dither::NoiseShaped, &input));
+    assert!(shaped < triangular / 4., "noise shaped: {shaped}, triangular: {triangular}");
+    // The noise moves to higher frequencies instead of disappearing.
+    assert!(mean_square(errors(Dither::NoiseShaped, &input)) > mean_square(errors(Dither::Triangular, &input)));
+}
+
+#[test]
+fn clipping() {
+    let mut quantizer = Quantizer::<2>::new(16, Dither::NoiseShaped, 0);
+    for _ in 0..1000 {
+        let [left, right] = quantizer.quantize::<f64, i16>([2., -2.]).into();
+        assert_eq!((left, right), (i16::MAX, i16::MIN));
+    }
+    // The noise shaping filter recovers straight away once the signal is back in range.
+    for _ in 0..100 {
+        let [left, right]: [i16; 2] = quantizer.quantize::<f64, i16>([0., 0.]).into();
+        assert!(left.abs() <= 8 && right.abs() <= 8, "{left}, {right}");
+    }
+}
+
+#[test]
+fn export() {
+    let input = quiet_sine(4410);
+    let file = WaveFile::from_samples(Quantizer::<1>::new(24, Dither::Triangular, 0).quantize_all::<f64, I24, _>(input.iter().copied()), 44100).unwrap();
+    assert_eq!(file.bytes_per_sample, 3);
+    for (frame, sample) in file.frames::<f64, 1>().unwrap().zip(input) {
+        let [frame] = frame.into();
+        assert!((frame - sample).abs() <= 1.5 / 8_388_608., "{frame}, {sample}");
+    }
+}
+
+#[test]
+fn full_depth() {
+    // Quantizing to 32 bits without dither leaves 32-bit samples exactly as they were.
+    let mut quantizer = Quantizer::<1>::new(32, Dither::None, 1);
+    assert_eq!(<[i32; 1]>::from(quantizer.quantize::<f32, i32>([0.5_f32])), [1 << 30]);
+    for sample in [i32::MIN, i32::MIN + 1, -65536, -1, 0, 1, 12_345_678, i32::MAX] {
+        assert_eq!(<[i32; 1]>::from(quantizer.quantize::<i32, i32>([sample])), [sample]);
+    }
+}
@@ -0,0 +1,147 @@
+use blerp::{
+    processing::{
+        envelope::{Adsr, Curve, Envelope, Mode, Segment},
+        generation::sine_wave,
+    },
+    Block,
+};
+use common::render;
+
+mod common;
+
+/// A low sample rate, so that a millisecond is one sample.
+const SAMPLE_RATE: u32 = 1000;
+
+fn assert_close(actual: &[f64], expected: &[f64]) {
+    assert_eq!(actual.len(), expected.len());
+    for (index, (actual, expected)) in actual.iter().zip(expected).enumerate() {
+        assert!((actual - expected).abs() < 1e-9, "sample {index}: {actual} != {expected}");
+    }
+}
+
+#[test]
+fn adsr() {
+    let mut envelope = Adsr::new(0.004, 0.002, 0.5, 0.005, Curve::Linear).envelope(SAMPLE_RATE);
+    assert!(!envelope.is_active());
+    assert_eq!(envelope.next_value(), 0.);
+
+    envelope.note_on();
+    assert!(envelope.is_active());
+    assert_close(&render(&mut envelope, 10), &[0.25, 0.5, 0.75, 1., 0.75, 0.5, 0.5, 0.5, 0.5, 0.5]);
+    envelope.note_off();
+    assert_close(&render(&mut envelope, 7), &[0.4, 0.3, 0.2, 0.1, 0., 0., 0.]);
+    assert!(!envelope.is_active());
+
+    // Releasing during the attack falls from wherever the envelope got to, taking the whole release time.
+    envelope.note_on();
+    render(&mut envelope, 2);
+    envelope.note_off();
+    assert_close(&render(&mut envelope, 5), &[0.4, 0.3, 0.2, 0.1, 0.]);
+}
+
+#[test]
+fn exponential() {
+    let mut envelope = Adsr::new(0.1, 0.1, 0.2, 0.1, Curve::Exponential).envelope(SAMPLE_RATE);
+    envelope.note_on();
+    let attack = render(&mut envelope, 100);
+    let decay = render(&mut envelope, 100);
+    // The attack rises quickly at first, so it is above a straight line, and the decay falls quickly at first, so it is below one. Both end exactly on their targets.
+    for (index, (rise, fall)) in attack.iter().zip(&decay).enumerate().take(99) {
+        let progress = (index + 1) as f64 / 100.;
+        assert!(*rise > progress, "attack at {progress}: {rise}");
+        assert!(*fall < 0.8f64.mul_add(-progress, 1.), "decay at {progress}: {fall}");
+    }
+    assert!(attack.windows(2).all(|pair| pair[1] > pair[0]));
+    assert!(decay.windows(2).all(|pair| pair[1] < pair[0]));
+    assert_eq!(attack[99], 1.);
+    assert_eq!(decay[99], 0.2);
+    assert!((attack[9] - 0.4).abs() < 0.01);
+}
+
+#[test]
+fn modes() {
+    let adsr = Adsr::new(0.004, 0.004, 0.5, 0.004, Curve::Linear);
+    // Retriggering during the decay starts the attack again from the current level, so there is no jump.
+    let mut envelope = adsr.envelope(SAMPLE_RATE);
+    envelope.note_on();
+    render(&mut envelope, 6);
+    assert_eq!(envelope.level(), 0.75);
+    envelope.note_on();
+    assert_close(&render(&mut envelope, 4), &[0.8125, 0.875, 0.9375, 1.]);
+
+    // Legato carries on with the decay.
+    let mut envelope = adsr.envelope(SAMPLE_RATE);
+    envelope.set_mode(Mode::Legato);
+    envelope.note_on();
+    render(&mut envelope, 6);
+    envelope.note_on();
+    assert_close(&render(&mut envelope, 4), &[0.625, 0.5, 0.5, 0.5]);
+    // After a release, even legato notes start again.
+    envelope.note_off();
+    render(&mut envelope, 2);
+    envelope.note_on();
+    assert_close(&render(&mut envelope, 2), &[0.4375, 0.625]);
+}
+
+#[test]
+fn breakpoints() {
+    // A one-shot envelope with no sustain segment plays all the way through, even if the note is released early, and holds its last level.
+    let mut envelope = Envelope::new(
+        vec![Segment::new(1., 0.002, Curve::Linear), Segment::new(-0.5, 0., Curve::Linear), Segment::new(0.5, 0.001, Curve::Linear)],
+        None,
+        SAMPLE_RATE,
+    );
+    envelope.note_on();
+    envelope.note_off();
+    assert_close(&render(&mut envelope, 5), &[0.5, 1., 0.5, 0.5, 0.5]);
+    assert!(!envelope.is_active());
+
+    // A sustain segment in the middle of several, where the release has more than one segment.
+    let mut envelope = Envelope::new(
+        vec![
+            Segment::new(0.2, 0.002, Curve::Linear),
+            Segment::new(0.8, 0.003, Curve::Linear),
+            Segment::new(0.4, 0.002, Curve::Linear),
+            Segment::new(0., 0.004, Curve::Linear),
+        ],
+        Some(1),
+        SAMPLE_RATE,
+    );
+    envelope.note_on();
+    assert_close(&render(&mut envelope, 8), &[0.1, 0.2, 0.4, 0.6, 0.8, 0.8, 0.8, 0.8]);
+    envelope.note_off();
+    assert_close(&render(&mut envelope, 7), &[0.6, 0.4, 0.3, 0.2, 0.1, 0., 0.]);
+
+    envelope.note_on();
+    render(&mut envelope, 3);
+    envelope.reset();
+    assert!(!envelope.is_active());
+    assert_eq!(envelope.next_value(), 0.);
+}
+
+#[test]
+#[should_panic = "sustain segment"]
+fn missing_sustain() {
+    let _ = Envelope::new(vec![Segment::new(1., 0.1, Curve::Linear)], Some(1), SAMPLE_RATE);
+}
+
+#[test]
+fn gain() {
+    let mut envelope = Adsr::new(0.002, 0., 1., 0.002, Curve::Linear).envelope(SAMPLE_RATE);
+    envelope.note_on();
+    let mut blocks = [Block::from([16000_i16, -16000]); 4];
+    envelope.apply(&mut blocks[..2]);
+    envelope.note_off();
+    envelope.apply(&mut blocks[2..]);
+    let blocks = blocks.map(<[i16; 2]>::from);
+    assert_eq!(blocks, [[8000, -8000], [16000, -16000], [8000, -8000], [0, 0]]);
+
+    // Shaping a wave from `generation` releases the note at the given time, after which it falls silent.
+    let mut wave = Adsr::new(0.01, 0.01, 0.5, 0.01, Curve::Exponential).envelope(44100).modulate(sine_wave::<f32, 2>(440., 1.), 0.1);
+    let samples: Vec<[f32; 2]> = (0..44100 / 5).map(|sample| wave(f64::from(sample) / 44100.).into()).collect();
+    assert_eq!(samples[0], [0.; 2]);
+    let peak = |range: std::ops::Range<usize>| samples[range].iter().map(|[left, _]| left.abs()).fold(0., f32::max);
+    assert!((peak(3000..4000) - 0.5).abs() < 0.01);
+    assert!((peak(4300..4410) - 0.5).abs() < 0.01);
+    assert_eq!(peak(4860..8820), 0.);
+}
@@ -0,0 +1,261 @@
+use std::{
+    f64::consts::TAU,
+    io::{self, Cursor, Seek, SeekFrom, Write},
+};
+
+use blerp::{
+    flac::{FlacOptions, FlacSample, FlacWriteError, FlacWriter},
+    processing::random::Rng,
+};
+use claxon::FlacReader;
+use cpal::{Sample, I24};
+
+fn encode<T: FlacSample, const N: usize>(frames: &[[T; N]], options: FlacOptions) -> Vec<u8> {
+    let mut writer = FlacWriter::<_, T, N>::new(Cursor::new(Vec::new()), 44100, options).unwrap();
+    // Write in pieces that don't line up with the blocks, to check that frames are buffered across calls.
+    for chunk in frames.chunks(1000) {
+        writer.write_frames(chunk.iter().copied()).unwrap();
+    }
+    assert_eq!(writer.frames_written(), frames.len() as u64);
+    writer.finalize().unwrap().into_inner()
+}
+
+/// Encode the frames, decode them with an independent decoder, and check that the samples and the `STREAMINFO` block match, returning the size of the encoded stream.
+fn round_trip<T: FlacSample, const N: usize>(frames: &[[T; N]], options: FlacOptions) -> usize {
+    let bytes = encode(frames, options.clone());
+    let mut reader = FlacReader::new(&bytes[..]).unwrap();
+    let info = reader.streaminfo();
+    assert_eq!(info.channels as usize, N);
+    assert_eq!(info.bits_per_sample, T::BITS_PER_SAMPLE);
+    assert_eq!(info.sample_rate, 44100);
+    // A length of zero means that the length is unknown.
+    assert_eq!(info.samples, Some(frames.len() as u64).filter(|&len| len > 0));
+
+    let expected: Vec<i32> = frames.iter().flatten().map(|&sample| sample.to_flac()).collect();
+    let decoded: Vec<i32> = reader.samples().collect::<Result<_, _>>().unwrap();
+    assert_eq!(decoded, expected);
+
+    let bytes_per_sample = T::BITS_PER_SAMPLE as usize / 8;
+    let md5 = md5::compute(expected.iter().flat_map(|sample| sample.to_le_bytes()[..bytes_per_sample].to_vec()).collect::<Vec<_>>());
+    assert_eq!(info.md5sum, md5.0);
+
+    assert_eq!(reader.tags().map(|(name, value)| (name.to_owned(), value.to_owned())).collect::<Vec<_>>(), options.comments);
+    bytes.len()
+}
+
+fn sine(frequency: f64, len: usize) -> impl Iterator<Item = f64> {
+    (0..len).map(move |sample| (TAU * frequency * sample as f64 / 44100.).sin())
+}
+
+#[test]
+fn signals() {
+    // A pure tone is predicted almost perfectly, so it should compress well.
+    let tone: Vec<[i16; 1]> = sine(440., 44100).map(|sample| [i16::from_sample(sample * 0.8)]).collect();
+    let len = round_trip(&tone, FlacOptions::default());
+    assert!(len < tone.len() * 2 / 3, "{len} bytes");
+
+    // Similar channels should be stored as mid and side.
+    let stereo: Vec<[i16; 2]> = sine(440., 44100)
+        .zip(sine(3000., 44100))
+        .map(|(a, b)| [i16::from_sample(a * 0.5 + b * 0.1), i16::from_sample(a * 0.5 - b * 0.1)])
+        .collect();
+    let len = round_trip(&stereo, FlacOptions::default());
+    assert!(len < stereo.len() * 4 / 2, "{len} bytes");
+
+    // White noise can't be predicted, so it ends up verbatim.
+    let mut rng = Rng::new(0);
+    let noise: Vec<[i16; 2]> = (0..20000).map(|_| [i16::from_sample(rng.bipolar()), i16::from_sample(rng.bipolar())]).collect();
+    round_trip(&noise, FlacOptions::default());
+
+    // Full-scale square waves in opposite phases make the side channel as wide as it can be.
+    let square: Vec<[i16; 2]> = (0..10000).map(|sample| if sample / 50 % 2 == 0 { [i16::MAX, i16::MIN] } else { [i16::MIN, i16::MAX] }).collect();
+    round_trip(&square, FlacOptions::default());
+
+    // A mix of tones is predicted better by linear predictors than by the fixed ones alone.
+    let chord: Vec<[i16; 1]> = sine(220., 44100)
+        .zip(sine(277., 44100))
+        .zip(sine(330., 44100))
+        .map(|((a, b), c)| [i16::from_sample((a + b + c) * 0.3)])
+        .collect();
+    let fixed_only = round_trip(
+        &chord,
+        FlacOptions {
+            max_lpc_order: 0,
+            ..FlacOptions::default()
+        },
+    );
+    let lpc = round_trip(&chord, FlacOptions::default());
+    assert!(lpc < fixed_only, "LPC: {lpc} bytes, fixed: {fixed_only} bytes");
+
+    let silence = vec![[0_i16; 2]; 10000];
+    assert!(round_trip(&silence, FlacOptions::default()) < 200);
+}
+
+#[test]
+fn sample_types() {
+    round_trip(&sine(440., 10000).map(|sample| [i8::from_sample(sample)]).collect::<Vec<_>>(), FlacOptions::default());
+    round_trip(&sine(440., 10000).map(|sample| [u8::from_sample(sample)]).collect::<Vec<_>>(), FlacOptions::default());
+    round_trip(&sine(440., 10000).map(|sample| [u16::from_sample(sample)]).collect::<Vec<_>>(), FlacOptions::default());
+    let mut rng = Rng::new(1);
+    let noisy_sine: Vec<[I24; 2]> = sine(440., 10000)
+        .map(|sample| [I24::from_sample(sample * 0.9 + rng.bipolar() * 0.01), I24::from_sample(-sample)])
+        .collect();
+    round_trip(&noisy_sine, FlacOptions::default());
+    // 20-bit samples in a 24-bit container have 4 wasted bits.
+    let padded: Vec<[I24; 1]> = sine(440., 10000).map(|sample| [I24::new(I24::from_sample(sample).inner() & !0xF).unwrap()]).collect();
+    let unpadded: Vec<[I24; 1]> = sine(440., 10000).map(|sample| [I24::from_sample(sample)]).collect();
+    assert!(round_trip(&padded, FlacOptions::default()) < round_trip(&unpadded, FlacOptions::default()));
+}
+
+#[test]
+fn options() {
+    let mut rng = Rng::new(2);
+    let frames: Vec<[i16; 3]> = sine(100., 30000)
+        .map(|sample| {
+            [
+                i16::from_sample(sample * 0.5),
+                i16::from_sample(rng.bipolar() * 0.1),
+                i16::from_sample(sample * 0.25 + rng.bipolar() * 0.01),
+            ]
+        })
+        .collect();
+    for (block_size, max_lpc_order, max_partition_order) in [(4096, 8, 6), (16, 8, 6), (17, 4, 8), (1000, 0, 0), (1152, 12, 4), (65535, 32, 15), (4608, 1, 2)] {
+        round_trip(
+            &frames,
+            FlacOptions {
+                block_size,
+                max_lpc_order,
+                max_partition_order,
+                ..FlacOptions::default()
+            },
+        );
+    }
+    // Streams shorter than a block, and a single frame.
+    round_trip(&frames[..100], FlacOptions::default());
+    round_trip(&frames[..1], FlacOptions::default());
+    round_trip::<i16, 3>(&[], FlacOptions::default());
+    // Every number of channels that FLAC supports.
+    round_trip(&[[1_i16, -2, 3, -4, 5, -6, 7, -8]; 100], FlacOptions::default());
+}
+
+#[test]
+fn comments() {
+    let frames: Vec<[i16; 1]> = sine(440., 1000).map(|sample| [i16::from_sample(sample)]).collect();
+    round_trip(
+        &frames,
+        FlacOptions {
+            comments: vec![("TITLE".to_owned(), "Drums".to_owned()), ("ARTIST".to_owned(), "Someone = Something".to_owned())],
+            ..FlacOptions::default()
+        },
+    );
+    let bytes = encode(
+        &frames,
+        FlacOptions {
+            comments: vec![("TITLE".to_owned(), "Drums".to_owned())],
+            ..FlacOptions::default()
+        },
+    );
+    assert_eq!(FlacReader::new(&bytes[..]).unwrap().vendor(), Some(concat!("blerp ", env!("CARGO_PKG_VERSION"))));
+}
+
+#[test]
+fn errors() {
+    let writer = || Cursor::new(Vec::new());
+    assert!(matches!(FlacWriter::<_, i16, 9>::new(writer(), 44100, FlacOptions::default()), Err(FlacWriteError::InvalidChannels)));
+    assert!(matches!(FlacWriter::<_, i16, 0>::new(writer(), 44100, FlacOptions::default()), Err(FlacWriteError::InvalidChannels)));
+    assert!(matches!(FlacWriter::<_, i16, 1>::new(writer(), 0, FlacOptions::default()), Err(FlacWriteError::InvalidSampleRate)));
+    assert!(matches!(
+        FlacWriter::<_, i16, 1>::new(writer(), 1_000_000, FlacOptions::default()),
+        Err(FlacWriteError::InvalidSampleRate)
+    ));
+    assert!(matches!(
+        FlacWriter::<_, i16, 1>::new(
+            writer(),
+            44100,
+            FlacOptions {
+                block_size: 15,
+                ..FlacOptions::default()
+            }
+        ),
+        Err(FlacWriteError::InvalidBlockSize)
+    ));
+}
+
+#[test]
+fn drop_finalizes() {
+    let mut bytes = Vec::new();
+    {
+        let mut writer = FlacWriter::<_, i16, 1>::new(Cursor::new(&mut bytes), 44100, FlacOptions::default()).unwrap();
+        writer.write_frames((0..5000).map(|sample| i16::try_from(sample).unwrap())).unwrap();
+    }
+    let mut reader = FlacReader::new(&bytes[..]).unwrap();
+    assert_eq!(reader.streaminfo().samples, Some(5000));
+    assert_eq!(reader.samples().collect::<Result<Vec<_>, _>>().unwrap(), (0..5000).collect::<Vec<_>>());
+}
+
+/// A writer that fails once, partway through a write, when it reaches `limit` bytes, and works again after that.
+struct Flaky {
+    inner: Cursor<Vec<u8>>,
+    limit: u64,
+    failed: bool,
+}
+
+impl Write for Flaky {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.failed {
+            return self.inner.write(buf);
+        }
+        let room = usize::try_from(self.limit - self.inner.position()).unwrap();
+        if room == 0 {
+            self.failed = true;
+            return Err(io::ErrorKind::StorageFull.into());
+        }
+        self.inner.write(&buf[..buf.len().min(room)])
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl Seek for Flaky {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(position)
+    }
+}
+
+#[test]
+fn failed_writes() {
+    let frames: Vec<i16> = (0..5000).map(|sample| i16::try_from(sample % 1000).unwrap()).collect();
+    let flaky = |limit| Flaky {
+        inner: Cursor::new(Vec::new()),
+        limit,
+        failed: false,
+    };
+    let header_len = FlacWriter::<_, i16, 1>::new(Cursor::new(Vec::new()), 44100, FlacOptions::default())
+        .unwrap()
+        .finalize()
+        .unwrap()
+        .into_inner()
+        .len();
+    let complete = encode(&frames.iter().map(|&sample| [sample]).collect::<Vec<_>>(), FlacOptions::default());
+
+    // If `finalize` fails partway through the last frame, dropping the writer doesn't write it again.
+    let mut bytes = flaky(complete.len() as u64 - 10);
+    let mut writer = FlacWriter::<_, i16, 1>::new(&mut bytes, 44100, FlacOptions::default()).unwrap();
+    writer.write_frames(frames.iter().copied()).unwrap();
+    assert!(matches!(writer.finalize(), Err(FlacWriteError::Io(_))));
+    assert_eq!(bytes.inner.into_inner().len(), complete.len() - 10);
+
+    // A frame that fails to be written stays buffered, and is only counted once it has been written, so writing it again gives the same stream after the torn frame.
+    let mut bytes = flaky(header_len as u64 + 100);
+    let mut writer = FlacWriter::<_, i16, 1>::new(&mut bytes, 44100, FlacOptions::default()).unwrap();
+    assert!(matches!(writer.write_frames(frames.iter().copied()), Err(FlacWriteError::Io(_))));
+    let written = usize::try_from(writer.frames_written()).unwrap();
+    assert_eq!(written, usize::from(FlacOptions::default().block_size));
+    writer.write_frames(frames[written..].iter().copied()).unwrap();
+    writer.finalize().unwrap();
+    let mut bytes = bytes.inner.into_inner();
+    bytes.drain(header_len..header_len + 100);
+    assert_eq!(bytes, complete);
+}
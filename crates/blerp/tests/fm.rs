@@ -0,0 +1,152 @@
+use std::f64::consts::TAU;
+
+use blerp::{
+    processing::{
+        envelope::{Adsr, Curve, Envelope},
+        fm::{Algorithm, FmVoice, Operator},
+        generation::{harmonics, Harmonic},
+    },
+    Block,
+};
+use common::{amplitude, render, SAMPLE_RATE};
+
+mod common;
+
+/// An envelope that is at full level for as long as the note is held, and stops straight away when it is released.
+fn gate() -> Envelope {
+    Adsr::new(0., 0., 1., 0., Curve::Linear).envelope(SAMPLE_RATE)
+}
+
+/// The Bessel function of the first kind, from its power series.
+fn bessel(order: u32, x: f64) -> f64 {
+    let mut term = (x / 2.).powi(order as i32) / (1..=order).map(f64::from).product::<f64>();
+    let mut sum = term;
+    for k in 1..40 {
+        term *= -(x / 2.).powi(2) / (f64::from(k) * f64::from(k + order));
+        sum += term;
+    }
+    sum
+}
+
+#[test]
+fn bessel_sidebands() {
+    // A 1 kHz carrier modulated at 100 Hz has sidebands every 100 Hz, each with the amplitude of a Bessel function of the modulation index.
+    for index in [0.5, 2., 2.404_825_557_695_773] {
+        let mut voice = FmVoice::new(vec![Operator::new(10., 1., gate()), Operator::new(1., index, gate())], Algorithm::stack(2), 100., 1., SAMPLE_RATE);
+        voice.note_on();
+        let signal = render(&mut voice, SAMPLE_RATE as usize);
+        for sideband in -6_i32..=6 {
+            let expected = bessel(sideband.unsigned_abs(), index).abs();
+            let measured = amplitude(&signal, f64::from(sideband).mul_add(100., 1000.));
+            assert!((measured - expected).abs() < 1e-4, "index {index}, sideband {sideband}: {measured} != {expected}");
+        }
+    }
+}
+
+#[test]
+fn feedback() {
+    // An operator modulating itself by β radians follows Kepler's equation, so its harmonics have amplitudes of 2Jₙ(nβ) / nβ.
+    let beta = 1.;
+    let mut operator = Operator::new(1., 1., gate());
+    operator.set_feedback(beta);
+    let mut voice = FmVoice::new(vec![operator], Algorithm::stack(1), 100., 1., SAMPLE_RATE);
+    voice.note_on();
+    let signal = render(&mut voice, SAMPLE_RATE as usize);
+    for harmonic in 1..=6 {
+        let n = f64::from(harmonic);
+        let expected = 2. * bessel(harmonic, n * beta) / (n * beta);
+        let measured = amplitude(&signal, 100. * n);
+        assert!((measured - expected).abs() < 0.01, "harmonic {harmonic}: {measured} != {expected}");
+    }
+}
+
+#[test]
+fn additive() {
+    // Operators made from harmonics and all heard at once are the same as `harmonics`.
+    let partials = [Harmonic::new(1., 0), Harmonic::new(0.5, 2), Harmonic::new(0.25, 5)];
+    let operators = partials.iter().map(|&harmonic| Operator::from_harmonic(harmonic, gate())).collect();
+    let mut voice = FmVoice::new(operators, Algorithm::parallel(3), 220., 0.5, SAMPLE_RATE);
+    voice.note_on();
+    let mut reference = harmonics::<f64, 1>(220., &partials);
+    for (index, sample) in render(&mut voice, 4410).into_iter().enumerate() {
+        let [expected] = <[f64; 1]>::from(reference(index as f64 / f64::from(SAMPLE_RATE)));
+        assert!((sample - expected * 0.5).abs() < 1e-9, "sample {index}: {sample} != {expected}");
+    }
+}
+
+#[test]
+fn algorithms() {
+    let operators = || {
+        vec![
+            Operator::new(1., 0.5, gate()),
+            Operator::new(2., 1.5, gate()),
+            Operator::new(3., 0.5, gate()),
+            Operator::new(0.5, 3., gate()),
+        ]
+    };
+    // Two pairs are the same as two separate voices of one pair each.
+    let mut pairs = FmVoice::new(operators(), Algorithm::pairs(4), 300., 1., SAMPLE_RATE);
+    let [first, second, third, fourth] = <[Operator; 4]>::try_from(operators()).unwrap();
+    let mut left = FmVoice::new(vec![first, second], Algorithm::stack(2), 300., 1., SAMPLE_RATE);
+    let mut right = FmVoice::new(vec![third, fourth], Algorithm::stack(2), 300., 1., SAMPLE_RATE);
+    for voice in [&mut pairs, &mut left, &mut right] {
+        voice.note_on();
+    }
+    for _ in 0..1000 {
+        let expected = left.next_sample() + right.next_sample();
+        assert!((pairs.next_sample() - expected).abs() < 1e-12);
+    }
+
+    // A branch has every modulator on the carrier, adding their modulation together.
+    let mut branch = FmVoice::new(operators()[..3].to_vec(), Algorithm::branch(3), 100., 1., SAMPLE_RATE);
+    branch.note_on();
+    for (index, sample) in render(&mut branch, 1000).into_iter().enumerate() {
+        let time = index as f64 / f64::from(SAMPLE_RATE);
+        let modulation = 1.5f64.mul_add((TAU * 200. * time).sin(), 0.5 * (TAU * 300. * time).sin());
+        let expected = 0.5 * (TAU * 100. * time + modulation).sin();
+        assert!((sample - expected).abs() < 1e-9);
+    }
+
+    let custom = Algorithm::new(vec![(3, 1), (2, 1), (1, 0)], vec![0, 2]);
+    let mut voice = FmVoice::new(operators(), custom, 100., 1., SAMPLE_RATE);
+    voice.operator_mut(3).unwrap().set_level(0.);
+    assert!(voice.operator_mut(4).is_none());
+}
+
+#[test]
+fn envelopes() {
+    let envelope = Adsr::new(0.01, 0.05, 0.5, 0.01, Curve::Exponential).envelope(SAMPLE_RATE);
+    let mut voice = FmVoice::new(vec![Operator::new(1., 1., envelope), Operator::new(2., 1., gate())], Algorithm::stack(2), 440., 1., SAMPLE_RATE);
+    let mut blocks = [Block::from([0_f32; 2]); 4410];
+    voice.fill(&mut blocks);
+    assert!(blocks.iter().all(|block| <[f32; 2]>::from(*block) == [0.; 2]));
+    assert!(!voice.is_active());
+
+    voice.note_on();
+    assert!(voice.is_active());
+    voice.fill(&mut blocks);
+    let peak = |blocks: &[Block<f32, 2>]| blocks.iter().map(|block| <[f32; 2]>::from(*block)[0].abs()).fold(0., f32::max);
+    assert!(blocks.iter().all(|block| {
+        let [left, right] = <[f32; 2]>::from(*block);
+        left == right
+    }));
+    assert!((peak(&blocks[..441]) - 1.).abs() < 0.01);
+    assert!((peak(&blocks[4000..]) - 0.5).abs() < 0.01);
+
+    voice.note_off();
+    voice.fill(&mut blocks);
+    assert!(!voice.is_active());
+    assert_eq!(peak(&blocks[441..]), 0.);
+}
+
+#[test]
+#[should_panic = "lower numbers"]
+fn backwards_modulation() {
+    let _ = Algorithm::new(vec![(0, 1)], vec![1]);
+}
+
+#[test]
+#[should_panic = "more operators"]
+fn missing_operators() {
+    let _ = FmVoice::new(vec![Operator::new(1., 1., gate())], Algorithm::stack(3), 440., 1., SAMPLE_RATE);
+}
@@ -0,0 +1,91 @@
+use blerp::processing::gain::{db_to_gain, gain_to_db, Clip, GainStage, Smoother};
+
+const SOFT: [Clip; 3] = [Clip::Tanh, Clip::Cubic, Clip::Arctangent];
+
+#[test]
+fn decibels() {
+    assert_eq!(db_to_gain(0.), 1.);
+    assert_eq!(db_to_gain(f64::NEG_INFINITY), 0.);
+    assert!((db_to_gain(-6.0206) - 0.5).abs() < 1e-5);
+    assert!((db_to_gain(20.) - 10.).abs() < 1e-12);
+    assert_eq!(gain_to_db(1.), 0.);
+    assert_eq!(gain_to_db(0.), f64::NEG_INFINITY);
+    assert_eq!(gain_to_db(-0.1), -20.);
+    for db in [-96., -12.5, -3., 0., 4.5, 24.] {
+        assert!((gain_to_db(db_to_gain(db)) - db).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn clip_curves() {
+    assert_eq!(Clip::Hard.shape(0.7), 0.7);
+    assert_eq!(Clip::Hard.shape(-3.), -1.);
+    assert_eq!(Clip::None.shape(3.), 3.);
+    assert_eq!(Clip::Cubic.shape(1.5), 1.);
+    assert_eq!(Clip::Cubic.shape(-10.), -1.);
+    for clip in SOFT {
+        // Quiet samples pass through almost unchanged.
+        assert!((clip.shape(0.001) - 0.001).abs() < 1e-8, "{clip:?}");
+        let mut previous = -1.;
+        for step in -1000..=1000 {
+            let sample = f64::from(step) / 100.;
+            let shaped = clip.shape(sample);
+            // The curves never pass the threshold, never decrease, and are symmetric.
+            assert!(shaped.abs() <= 1., "{clip:?}: {sample} -> {shaped}");
+            assert!(shaped >= previous, "{clip:?}: {sample} -> {shaped}");
+            assert_eq!(clip.shape(-sample), -shaped, "{clip:?}");
+            previous = shaped;
+        }
+        assert!(clip.shape(10.) > 0.9, "{clip:?}");
+    }
+
+    // Every sample type works, relative to the threshold.
+    assert_eq!(Clip::Hard.apply(0.9_f32, 0.5), 0.5);
+    assert_eq!(Clip::Hard.apply(i16::MIN, 0.5), i16::MIN / 2);
+    assert_eq!(Clip::Hard.apply(100_i16, 0.5), 100);
+    assert_eq!(Clip::Hard.apply(u8::MAX, 0.5), 128 + 64);
+    assert!(Clip::Tanh.apply(f64::MAX, 0.25) <= 0.25);
+}
+
+#[test]
+fn smoothing() {
+    let mut smoother = Smoother::new(0., 0.01, 1000);
+    smoother.set_target(1.);
+    let values: Vec<f64> = (0..100).map(|_| smoother.next_value()).collect();
+    // After one time constant, 63% of the way there.
+    assert!((values[9] - (1. - (-1_f64).exp())).abs() < 1e-12, "{}", values[9]);
+    assert!(values.windows(2).all(|pair| pair[1] > pair[0] && pair[1] - pair[0] < 0.1));
+    // It eventually lands exactly on the target.
+    assert_eq!((0..1000).map(|_| smoother.next_value()).last(), Some(1.));
+    smoother.reset(0.25);
+    assert_eq!((smoother.value(), smoother.next_value()), (0.25, 0.25));
+
+    let mut instant = Smoother::new(0., 0., 48000);
+    instant.set_target(0.5);
+    assert_eq!(instant.next_value(), 0.5);
+}
+
+#[test]
+fn gain_stage() {
+    let mut stage = GainStage::<2>::new(-6.0206, Clip::None, 0., 48000);
+    let [left, right] = <[f32; 2]>::from(stage.process([1., -0.5]));
+    assert!((left - 0.5).abs() < 1e-5 && (right + 0.25).abs() < 1e-5);
+
+    // Without smoothing, a change of gain is a jump; with it, the output moves gradually.
+    let mut stage = GainStage::<1>::new(0., Clip::Hard, 0.005, 48000);
+    stage.set_gain_db(-20.);
+    assert_eq!(stage.gain_db(), -20.);
+    let outputs: Vec<f64> = (0..2000).map(|_| <[f64; 1]>::from(stage.process(1.))[0]).collect();
+    assert!(outputs.windows(2).all(|pair| (pair[0] - pair[1]).abs() < 0.005));
+    assert!((outputs[1999] - 0.1).abs() < 1e-3);
+
+    // Boosting past the ceiling is limited by the curve.
+    let mut stage = GainStage::<2>::new(12., Clip::Hard, 0., 48000);
+    stage.set_ceiling_db(-6.0206);
+    let [left, right] = <[i16; 2]>::from(stage.process([i16::MAX, 1000]));
+    assert!((i32::from(left) - 16384).abs() <= 1, "{left}");
+    assert!((i32::from(right) - 3981).abs() <= 1, "{right}");
+    stage.set_clip(Clip::Tanh);
+    let [left, _] = <[f64; 2]>::from(stage.process([0.9, 0.]));
+    assert!(left < 0.5 && left > 0.49, "{left}");
+}
@@ -1,46 +1,102 @@
 use cpal::Sample;
 use std::any::type_name_of_val;
-use std::fs::{create_dir, remove_dir_all, File};
+use std::f64::consts::TAU;
+use std::fs::{create_dir_all, remove_dir_all, write};
+use std::path::Path;
 
 use blerp::{
     processing::generation::{harmonics, sawtooth_wave, sine_wave, square_wave, triangle_wave, Harmonic},
-    wavefile::WaveFile,
+    wavefile::{Format, WaveFile},
 };
 
-/// FIXME: this test produces some files which either
-/// - cannot be played (some of the `i64`/`u64` files (does wave support 64-bit samples?))
-/// - play the wrong sound (some of the `i8`/`u16`/`u32` files)
-/// - completely kill macOS's audio system (coreaudiod) (some of the square wave files)
+/// FIXME: some of the square wave files completely kill macOS's audio system (coreaudiod)
 #[test]
 fn main() {
     const MIDDLE_C: f64 = 261.63;
     const SAMPLE_RATE: u32 = 44100;
-    remove_dir_all(env!("CARGO_TARGET_TMPDIR")).unwrap();
-    create_dir(env!("CARGO_TARGET_TMPDIR")).unwrap();
+    // Other tests use the temporary directory too, so only this test's own directory is cleared.
+    let directory = format!("{}/generate_waves", env!("CARGO_TARGET_TMPDIR"));
+    if Path::new(&directory).exists() {
+        remove_dir_all(&directory).unwrap();
+    }
+    create_dir_all(&directory).unwrap();
+    // Write each wave as every sample type, and check that the bytes on disk are in the format that WAV defines for it.
     macro_rules! test {
-        ($fn:ident $($ty:ty)+) => {
+        ($fn:ident $($ty:ty => $format:ident $bits:literal),+) => {
             $(
+                let mut bytes = Vec::new();
                 WaveFile::from_samples((0..44100).map(|sample| $fn::<$ty, 1>(MIDDLE_C, <$ty>::from_sample(1.))(f64::from(sample) / f64::from(SAMPLE_RATE))), SAMPLE_RATE)
                     .unwrap()
-                    .write(&mut File::create(format!("{}{}{}{}", env!("CARGO_TARGET_TMPDIR"), "/", type_name_of_val(&$fn::<$ty, 1>), ".wav")).unwrap())
+                    .write(&mut bytes)
                     .unwrap();
+                write(format!("{directory}/{}.wav", type_name_of_val(&$fn::<$ty, 1>)), &bytes).unwrap();
+
+                let format_tag = u16::from_le_bytes([bytes[20], bytes[21]]);
+                let bits_per_sample = u16::from_le_bytes([bytes[34], bytes[35]]);
+                assert_eq!(bits_per_sample, $bits, "{}", stringify!($fn::<$ty>));
+                if $bits > 16 {
+                    // Samples wider than 16 bits are written with `WAVE_FORMAT_EXTENSIBLE`, with the real format tag at the start of the sub-format GUID.
+                    assert_eq!(format_tag, 0xFFFE, "{}", stringify!($fn::<$ty>));
+                    assert_eq!(u16::from_le_bytes([bytes[44], bytes[45]]), Format::$format as u16, "{}", stringify!($fn::<$ty>));
+                } else {
+                    assert_eq!(format_tag, Format::$format as u16, "{}", stringify!($fn::<$ty>));
+                }
+
+                let file = WaveFile::parse(&bytes).unwrap();
+                assert_eq!(file.data.len(), 44100 * $bits / 8, "{}", stringify!($fn::<$ty>));
+                // Silence is the first sample of every wave, which is 0x80 for unsigned 8-bit samples and zero for everything else.
+                let silence = if $bits == 8 { 0x80 } else { 0 };
+                if stringify!($fn) != "square_wave" {
+                    assert!(file.data[..$bits / 8].iter().all(|&byte| byte == silence), "{}", stringify!($fn::<$ty>));
+                }
+            )+
+        };
+    }
+    macro_rules! test_all_types {
+        ($($fn:ident)+) => {
+            $(
+                test!($fn
+                    f32 => FloatingPoint 32, f64 => FloatingPoint 64,
+                    i8 => PulseCodeModulation 8, i16 => PulseCodeModulation 16, i32 => PulseCodeModulation 32, i64 => PulseCodeModulation 32,
+                    u8 => PulseCodeModulation 8, u16 => PulseCodeModulation 16, u32 => PulseCodeModulation 32, u64 => PulseCodeModulation 32
+                );
             )+
         };
     }
-    test!(sine_wave f32 f64 i8 i16 i32 i64 u8 u16 u32 u64);
-    test!(square_wave f32 f64 i8 i16 i32 i64 u8 u16 u32 u64);
-    test!(triangle_wave f32 f64 i8 i16 i32 i64 u8 u16 u32 u64);
-    test!(sawtooth_wave f32 f64 i8 i16 i32 i64 u8 u16 u32 u64);
+    test_all_types!(sine_wave square_wave triangle_wave sawtooth_wave);
+
+    // Signed and unsigned inputs of the same width must produce the same wave, so decode a sine wave from each and compare it to the real thing.
+    macro_rules! test_sine {
+        ($($ty:ty, $tolerance:literal;)+) => {
+            $(
+                let file = WaveFile::from_samples((0..4410).map(|sample| sine_wave::<$ty, 1>(MIDDLE_C, <$ty>::from_sample(1.))(f64::from(sample) / f64::from(SAMPLE_RATE))), SAMPLE_RATE).unwrap();
+                for (sample, frame) in file.frames::<f64, 1>().unwrap().enumerate() {
+                    let [actual] = frame.into();
+                    let expected = (TAU * MIDDLE_C * sample as f64 / f64::from(SAMPLE_RATE)).sin();
+                    assert!((actual - expected).abs() <= $tolerance, "{}: sample {sample} is {actual}, expected {expected}", stringify!($ty));
+                }
+            )+
+        };
+    }
+    test_sine! {
+        i8, 0.02;
+        u8, 0.02;
+        i16, 0.0001;
+        u16, 0.0001;
+        i32, 0.0001;
+        u32, 0.0001;
+        i64, 0.0001;
+        u64, 0.0001;
+    }
 
     // TODO test harmonic generation
+    let mut bytes = Vec::new();
     WaveFile::from_samples(
         (0..44100).map(|sample| harmonics::<f64, 1>(MIDDLE_C, &[Harmonic::new(1., 0), Harmonic::new(1., 1)])(f64::from(sample) / f64::from(SAMPLE_RATE))),
         SAMPLE_RATE,
     )
     .unwrap()
-    .write(&mut File::create(format!("{}/harmonic_sin(x)+sin(2x)/2.wav", env!("CARGO_TARGET_TMPDIR"))).unwrap())
+    .write(&mut bytes)
     .unwrap();
-
-    // panic to make the test fail (because it does) - see FIXME above
-    panic!()
+    write(format!("{directory}/harmonic_sin(x)+sin(2x)÷2.wav"), &bytes).unwrap();
 }
@@ -0,0 +1,155 @@
+use blerp::processing::{
+    envelope::{Adsr, Curve},
+    gain::{Clip, GainStage},
+    modulation::{GainParameter, Lfo, LfoShape, ModulationMatrix, OscillatorParameter, Polarity, Rate},
+    oscillator::{Oscillator, Waveform},
+};
+use common::render;
+
+mod common;
+
+const SAMPLE_RATE: u32 = 1000;
+
+#[test]
+fn shapes() {
+    // Four samples per cycle land on the corners of every shape.
+    let expected = [
+        (LfoShape::Sine, [0., 1., 0., -1.]),
+        (LfoShape::Triangle, [0., 1., 0., -1.]),
+        (LfoShape::Sawtooth, [0., 0.5, -1., -0.5]),
+        (LfoShape::Square, [1., 1., -1., -1.]),
+    ];
+    for (shape, expected) in expected {
+        let values = render(&mut Lfo::new(shape, Rate::Hertz(250.), SAMPLE_RATE), 8);
+        for (value, expected) in values.iter().zip(expected.iter().cycle()) {
+            assert!((value - expected).abs() < 1e-9, "{shape:?}: {values:?}");
+        }
+    }
+
+    // Sample and hold keeps each random value for a whole cycle, and the same seed gives the same values.
+    let mut lfo = Lfo::new(LfoShape::SampleAndHold, Rate::Hertz(125.), SAMPLE_RATE);
+    lfo.set_seed(3);
+    let values = render(&mut lfo, 100);
+    for cycle in values.chunks(8) {
+        assert!(cycle.iter().all(|value| *value == cycle[0] && (-1. ..1.).contains(value)));
+    }
+    assert!(values.chunks(8).zip(values.chunks(8).skip(1)).all(|(first, second)| first[0] != second[0]));
+    let mut again = Lfo::new(LfoShape::SampleAndHold, Rate::Hertz(125.), SAMPLE_RATE);
+    again.set_seed(3);
+    assert_eq!(render(&mut again, 100), values);
+}
+
+#[test]
+fn tempo_sync() {
+    // Half a beat at 120 beats per minute is a quarter of a second.
+    let mut lfo = Lfo::new(LfoShape::Square, Rate::Beats(0.5), SAMPLE_RATE);
+    assert_eq!(lfo.frequency(), 4.);
+    let values = render(&mut lfo, 250);
+    assert!(values[..125].iter().all(|value| *value == 1.));
+    assert!(values[125..].iter().all(|value| *value == -1.));
+    assert!(lfo.phase() < 1e-9 || lfo.phase() > 1. - 1e-9);
+
+    lfo.set_tempo(90.);
+    assert_eq!(lfo.frequency(), 3.);
+    lfo.set_rate(Rate::Hertz(0.5));
+    assert_eq!(lfo.frequency(), 0.5);
+    lfo.set_phase(-0.25);
+    assert_eq!(lfo.phase(), 0.75);
+}
+
+#[test]
+fn vibrato_and_tremolo() {
+    let mut oscillator = Oscillator::new(Waveform::Sine, 0., 0., SAMPLE_RATE);
+    let mut matrix = ModulationMatrix::new();
+    let vibrato = matrix.add_source(Lfo::new(LfoShape::Sine, Rate::Hertz(5.), SAMPLE_RATE));
+    let tremolo = matrix.add_source(Lfo::new(LfoShape::Triangle, Rate::Hertz(2.), SAMPLE_RATE));
+    matrix.set_base(OscillatorParameter::Frequency, 440.);
+    matrix.set_base(OscillatorParameter::Amplitude, 1.);
+    matrix.route(vibrato, OscillatorParameter::Frequency, 10., Polarity::Bipolar);
+    // A unipolar route with a negative depth only ever turns the amplitude down, here to half at most.
+    matrix.route(tremolo, OscillatorParameter::Amplitude, -0.5, Polarity::Unipolar);
+
+    let mut frequencies = Vec::new();
+    for _ in 0..SAMPLE_RATE {
+        matrix.apply(&mut oscillator);
+        frequencies.push(oscillator.frequency());
+    }
+    assert_eq!(frequencies[0], 440.);
+    assert!((frequencies[50] - 450.).abs() < 1e-9);
+    assert!((frequencies[150] - 430.).abs() < 1e-9);
+
+    // A square wave at 0 Hz stays at its amplitude, which shows where the tremolo has moved it to.
+    let mut oscillator = Oscillator::new(Waveform::Square, 0., 0., SAMPLE_RATE);
+    let mut matrix = ModulationMatrix::new();
+    let tremolo = matrix.add_source(Lfo::new(LfoShape::Triangle, Rate::Hertz(2.), SAMPLE_RATE));
+    matrix.set_base(OscillatorParameter::Amplitude, 1.);
+    let route = matrix.route(tremolo, OscillatorParameter::Amplitude, -0.5, Polarity::Unipolar);
+    let amplitudes: Vec<f64> = (0..500)
+        .map(|_| {
+            matrix.apply(&mut oscillator);
+            oscillator.next_sample()
+        })
+        .collect();
+    assert!((amplitudes[0] - 0.75).abs() < 1e-9);
+    assert!((amplitudes[125] - 0.5).abs() < 1e-9);
+    assert!((amplitudes[375] - 1.).abs() < 1e-9);
+    assert!(amplitudes.iter().all(|amplitude| (0.5..=1.).contains(amplitude)));
+
+    // Routes can be changed while the matrix runs.
+    matrix.route_mut(route).unwrap().depth = 0.;
+    matrix.apply(&mut oscillator);
+    assert_eq!(oscillator.next_sample(), 1.);
+}
+
+#[test]
+fn envelopes() {
+    // An envelope routed to the gain of a gain stage, started and released through the matrix.
+    let mut stage = GainStage::<1>::new(-60., Clip::None, 0., SAMPLE_RATE);
+    let mut matrix = ModulationMatrix::new();
+    let envelope = matrix.add_source(Adsr::new(0.01, 0., 1., 0.01, Curve::Linear).envelope(SAMPLE_RATE));
+    matrix.set_base(GainParameter::Gain, -60.);
+    matrix.route(envelope, GainParameter::Gain, 60., Polarity::Unipolar);
+    let mut run = |matrix: &mut ModulationMatrix<GainParameter>, len| {
+        (0..len)
+            .map(|_| {
+                matrix.apply(&mut stage);
+                stage.gain_db()
+            })
+            .collect::<Vec<_>>()
+    };
+    assert!(run(&mut matrix, 10).iter().all(|gain| *gain == -60.));
+    matrix.note_on();
+    let attack = run(&mut matrix, 20);
+    assert!((attack[4] + 30.).abs() < 1e-9);
+    assert!(attack[9..].iter().all(|gain| gain.abs() < 1e-9));
+    matrix.note_off();
+    let release = run(&mut matrix, 20);
+    assert!((release[4] + 30.).abs() < 1e-9);
+    assert!(release[9..].iter().all(|gain| *gain == -60.));
+
+    // As a bipolar source, an envelope moves from -1 to 1 instead.
+    let mut oscillator = Oscillator::new(Waveform::Sine, 0., 0., SAMPLE_RATE);
+    let mut matrix = ModulationMatrix::new();
+    let envelope = matrix.add_source(Adsr::new(0.01, 0., 1., 0.01, Curve::Linear).envelope(SAMPLE_RATE));
+    matrix.set_base(OscillatorParameter::Frequency, 440.);
+    matrix.route(envelope, OscillatorParameter::Frequency, 100., Polarity::Bipolar);
+    matrix.apply(&mut oscillator);
+    assert_eq!(oscillator.frequency(), 340.);
+    assert!(matrix.source_mut(envelope).is_some_and(|source| !source.bipolar()));
+    assert!(matrix.source_mut(1).is_none());
+}
+
+#[test]
+#[should_panic = "no source"]
+fn missing_source() {
+    ModulationMatrix::new().route(0, GainParameter::Ceiling, 1., Polarity::Bipolar);
+}
+
+#[test]
+#[should_panic = "base value"]
+fn missing_base() {
+    // Without a base, vibrato would move the frequency around 0 Hz instead of where the oscillator has it.
+    let mut matrix = ModulationMatrix::new();
+    let vibrato = matrix.add_source(Lfo::new(LfoShape::Sine, Rate::Hertz(5.), SAMPLE_RATE));
+    matrix.route(vibrato, OscillatorParameter::Frequency, 10., Polarity::Bipolar);
+}
@@ -0,0 +1,114 @@
+use std::f64::consts::TAU;
+
+use blerp::{
+    processing::noise::{Noise, NoiseKind},
+    Block,
+};
+
+const SAMPLE_RATE: u32 = 44100;
+const SEGMENT_LEN: usize = 4096;
+const SEGMENTS: usize = 40;
+
+fn render<const N: usize>(noise: &mut Noise<N>, len: usize) -> Vec<[f64; N]> {
+    (0..len).map(|_| noise.next_block::<f64>().into()).collect()
+}
+
+/// Measure the power around `frequency` in decibels, averaged over the DFT bins either side of it and over Hann-windowed segments of `signal` (Welch's method).
+fn band_level(signal: &[f64], frequency: f64) -> f64 {
+    let centre = (frequency * SEGMENT_LEN as f64 / f64::from(SAMPLE_RATE)).round() as usize;
+    let window: Vec<f64> = (0..SEGMENT_LEN).map(|index| 0.5 - 0.5 * (TAU * index as f64 / SEGMENT_LEN as f64).cos()).collect();
+    let mut power = 0.;
+    for segment in signal.chunks_exact(SEGMENT_LEN).take(SEGMENTS) {
+        for bin in centre - 3..=centre + 3 {
+            let (real, imaginary) = segment.iter().zip(&window).enumerate().fold((0., 0.), |(real, imaginary), (index, (sample, window))| {
+                let phase = TAU * (bin * index % SEGMENT_LEN) as f64 / SEGMENT_LEN as f64;
+                let sample = sample * window;
+                (sample.mul_add(phase.cos(), real), sample.mul_add(phase.sin(), imaginary))
+            });
+            power += real.mul_add(real, imaginary * imaginary);
+        }
+    }
+    10. * power.log10()
+}
+
+/// Return the average change in power per octave from 100 Hz to 6400 Hz.
+fn slope(signal: &[f64]) -> f64 {
+    (band_level(signal, 6400.) - band_level(signal, 100.)) / 6.
+}
+
+#[test]
+fn spectral_slopes() {
+    for (kind, expected) in [(NoiseKind::White, 0.), (NoiseKind::Pink, -3.), (NoiseKind::Brown, -6.), (NoiseKind::Velvet { density: 2000. }, 0.)] {
+        let signal: Vec<f64> = render(&mut Noise::<1>::new(kind, 1., 42, SAMPLE_RATE), SEGMENT_LEN * SEGMENTS)
+            .into_iter()
+            .map(|[sample]| sample)
+            .collect();
+        let slope = slope(&signal);
+        assert!((slope - expected).abs() < 0.5, "{kind:?} falls by {slope} dB per octave");
+        assert!(signal.iter().all(|sample| (-1. ..=1.).contains(sample)), "{kind:?} goes past full scale");
+        let mean = signal.iter().sum::<f64>() / signal.len() as f64;
+        assert!(mean.abs() < 0.05, "{kind:?} has an offset of {mean}");
+    }
+}
+
+#[test]
+fn decorrelated_channels() {
+    for kind in [NoiseKind::White, NoiseKind::Pink, NoiseKind::Brown, NoiseKind::Velvet { density: 2000. }] {
+        // The slowest parts of pink and brown noise change too rarely to measure in a few seconds, so compare how each sample changes from the one before instead.
+        let samples = render(&mut Noise::<4>::new(kind, 1., 7, SAMPLE_RATE), SAMPLE_RATE as usize * 2);
+        let blocks: Vec<[f64; 4]> = samples.windows(2).map(|pair| std::array::from_fn(|channel| pair[1][channel] - pair[0][channel])).collect();
+        for first in 0..4 {
+            for second in first + 1..4 {
+                let (product, first_power, second_power) = blocks.iter().fold((0., 0., 0.), |(product, first_power, second_power), block| {
+                    (
+                        block[first].mul_add(block[second], product),
+                        block[first].mul_add(block[first], first_power),
+                        block[second].mul_add(block[second], second_power),
+                    )
+                });
+                let correlation = product / (first_power * second_power).sqrt();
+                assert!(correlation.abs() < 0.05, "{kind:?}: channels {first} and {second} have a correlation of {correlation}");
+            }
+        }
+    }
+}
+
+#[test]
+fn seeds() {
+    // The same seed always gives the same noise, and another seed gives different noise, even in the first channel.
+    let mut first = [Block::from([0_f32; 2]); 1000];
+    let mut second = [Block::from([0_f32; 2]); 1000];
+    Noise::new(NoiseKind::Pink, 0.5, 1234, SAMPLE_RATE).fill(&mut first);
+    let mut noise = Noise::new(NoiseKind::Pink, 0.5, 1234, SAMPLE_RATE);
+    for chunk in second.chunks_mut(64) {
+        noise.fill(chunk);
+    }
+    assert_eq!(first, second);
+    Noise::new(NoiseKind::Pink, 0.5, 1235, SAMPLE_RATE).fill(&mut second);
+    assert!(first.iter().zip(&second).all(|(first, second)| <[f32; 2]>::from(*first)[0] != <[f32; 2]>::from(*second)[0]));
+}
+
+#[test]
+fn velvet() {
+    // There is exactly one impulse, of either sign, in every period, each of which starts on the first whole sample in it.
+    let density = 1000.;
+    let period = f64::from(SAMPLE_RATE) / density;
+    let samples: Vec<i16> = render(&mut Noise::<1>::new(NoiseKind::Velvet { density }, 0.5, 99, SAMPLE_RATE), SAMPLE_RATE as usize)
+        .into_iter()
+        .map(|[sample]| (sample * 2.) as i16)
+        .collect();
+    assert!(samples.iter().all(|sample| [-1, 0, 1].contains(sample)));
+    let impulses: Vec<usize> = samples.iter().enumerate().filter(|(_, sample)| **sample != 0).map(|(index, _)| index).collect();
+    assert_eq!(impulses.len(), density as usize);
+    for (number, position) in impulses.iter().enumerate() {
+        let start = (number as f64 * period).ceil() as usize;
+        let end = ((number + 1) as f64 * period).ceil() as usize;
+        assert!((start..end).contains(position), "impulse {number} at {position}");
+    }
+    let positive = samples.iter().filter(|sample| **sample == 1).count();
+    assert!((400..600).contains(&positive), "{positive} positive impulses");
+
+    let mut blocks = [Block::from(0_i16); 100];
+    Noise::new(NoiseKind::Velvet { density: f64::from(SAMPLE_RATE) * 2. }, 1., 5, SAMPLE_RATE).fill(&mut blocks);
+    assert!(blocks.iter().all(|block| <[i16; 1]>::from(*block)[0].unsigned_abs() >= i16::MAX.unsigned_abs()));
+}
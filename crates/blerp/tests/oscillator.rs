@@ -0,0 +1,141 @@
+use std::f64::consts::TAU;
+
+use blerp::{
+    processing::{
+        generation::{sawtooth_wave, square_wave},
+        oscillator::{Oscillator, Waveform},
+    },
+    Block,
+};
+use common::{amplitude, render, SAMPLE_RATE};
+
+mod common;
+
+const WAVEFORMS: [Waveform; 4] = [Waveform::Sine, Waveform::Square, Waveform::Triangle, Waveform::Sawtooth];
+
+/// Return the frequency that a harmonic above the Nyquist frequency folds back to.
+fn alias(frequency: f64) -> f64 {
+    let rate = f64::from(SAMPLE_RATE);
+    let folded = frequency.rem_euclid(rate);
+    folded.min(rate - folded)
+}
+
+#[test]
+fn naive_square_wave() {
+    // The square wave used to raise -1 to a fractional power, which is NaN.
+    let mut wave = square_wave::<f64, 1>(261.63, 1.);
+    for sample in 0..1000 {
+        let [value] = <[f64; 1]>::from(wave(f64::from(sample) / f64::from(SAMPLE_RATE)));
+        assert!(value == 1. || value == -1., "sample {sample} is {value}");
+    }
+    assert_eq!(<[f64; 1]>::from(wave(0.75 / 261.63)), [-1.]);
+}
+
+#[test]
+fn shapes() {
+    // At a low frequency, the corrections only touch the samples right next to each corner, so everything else is the ideal wave.
+    let frequency = 100.;
+    for waveform in WAVEFORMS {
+        let samples = render(&mut Oscillator::new(waveform, frequency, 0.5, SAMPLE_RATE), 441);
+        for (index, sample) in samples.iter().enumerate() {
+            let phase = index as f64 * frequency / f64::from(SAMPLE_RATE);
+            let ideal = match waveform {
+                Waveform::Sine => (TAU * phase).sin(),
+                Waveform::Square => {
+                    if phase.fract() < 0.5 {
+                        1.
+                    } else {
+                        -1.
+                    }
+                }
+                Waveform::Triangle => 2. / std::f64::consts::PI * (TAU * phase).sin().asin(),
+                Waveform::Sawtooth => 2. * (phase - (phase + 0.5).floor()),
+            } * 0.5;
+            let near_corner = [0., 0.25, 0.5, 0.75, 1.].iter().any(|corner| (phase.fract() - corner).abs() < 0.01);
+            if !near_corner {
+                assert!((sample - ideal).abs() < 1e-9, "{waveform:?} at {phase}: {sample} != {ideal}");
+            }
+        }
+        let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+        assert!(mean.abs() < 1e-3, "{waveform:?} has an offset of {mean}");
+    }
+}
+
+#[test]
+fn aliasing() {
+    // Compare the harmonics above the Nyquist frequency, which fold back down into the audible range, with those of a naive wave.
+    let nyquist = f64::from(SAMPLE_RATE) / 2.;
+    for (waveform, frequency) in [(Waveform::Sawtooth, 5000.), (Waveform::Square, 3000.), (Waveform::Triangle, 3000.)] {
+        let band_limited = render(&mut Oscillator::new(waveform, frequency, 1., SAMPLE_RATE), SAMPLE_RATE as usize);
+        let naive: Vec<f64> = (0..SAMPLE_RATE)
+            .map(|sample| {
+                let phase = (f64::from(sample) * frequency / f64::from(SAMPLE_RATE)).fract();
+                match waveform {
+                    Waveform::Square => <[f64; 1]>::from(square_wave::<f64, 1>(frequency, 1.)(f64::from(sample) / f64::from(SAMPLE_RATE)))[0],
+                    Waveform::Triangle => 1. - 4. * (phase - 0.25).rem_euclid(1.).min(1. - (phase - 0.25).rem_euclid(1.)),
+                    _ => <[f64; 1]>::from(sawtooth_wave::<f64, 1>(frequency, 0.5)(f64::from(sample) / f64::from(SAMPLE_RATE)))[0],
+                }
+            })
+            .collect();
+        let fundamental = amplitude(&band_limited, frequency);
+        for harmonic in 1..40 {
+            let harmonic = frequency * f64::from(harmonic);
+            if harmonic < nyquist {
+                continue;
+            }
+            let alias = alias(harmonic);
+            let level = 20. * (amplitude(&band_limited, alias) / fundamental).log10();
+            let naive_level = 20. * (amplitude(&naive, alias) / fundamental).log10();
+            // PolyBLEP helps least close to the Nyquist frequency, but a lot where hearing is most sensitive.
+            let improvement = if alias < 5000. { 25. } else { 3. };
+            assert!(
+                level < naive_level - improvement,
+                "{waveform:?}: {harmonic} Hz folds to {alias} Hz at {level} dB, against {naive_level} dB"
+            );
+        }
+    }
+}
+
+#[test]
+fn continuity() {
+    // Changing the frequency carries on from the same phase, so a sine wave never jumps further than its steepest slope allows.
+    let mut oscillator = Oscillator::new(Waveform::Sine, 440., 1., SAMPLE_RATE);
+    let mut previous = oscillator.next_sample();
+    for step in 0..2000 {
+        if step % 100 == 0 {
+            oscillator.set_frequency(440. + f64::from(step));
+        }
+        let sample = oscillator.next_sample();
+        assert!((sample - previous).abs() <= TAU * oscillator.frequency() / f64::from(SAMPLE_RATE), "step {step}");
+        previous = sample;
+    }
+    // The phase stays in range however long the oscillator runs.
+    let mut oscillator = Oscillator::new(Waveform::Sawtooth, 12345.678, 1., SAMPLE_RATE);
+    for _ in 0..1_000_000 {
+        oscillator.next_sample();
+    }
+    assert!((0. ..1.).contains(&oscillator.phase()));
+    oscillator.set_phase(-0.25);
+    assert_eq!(oscillator.phase(), 0.75);
+}
+
+#[test]
+fn fill() {
+    let mut whole = [Block::from([0_f32; 2]); 300];
+    Oscillator::new(Waveform::Triangle, 1000., 0.8, SAMPLE_RATE).fill(&mut whole);
+    assert!(whole.iter().all(|&block| {
+        let [left, right] = block.into();
+        left == right
+    }));
+    // Filling in chunks, as an audio callback does, gives the same samples as filling all at once.
+    let mut chunked = [Block::from([0_f32; 2]); 300];
+    let mut oscillator = Oscillator::new(Waveform::Triangle, 1000., 0.8, SAMPLE_RATE);
+    for chunk in chunked.chunks_mut(64) {
+        oscillator.fill(chunk);
+    }
+    assert_eq!(chunked, whole);
+
+    let mut blocks = [Block::from(0_i16); 4];
+    Oscillator::new(Waveform::Square, 0., 0.5, SAMPLE_RATE).fill(&mut blocks);
+    assert_eq!(blocks.map(|block| <[i16; 1]>::from(block)[0]), [16384; 4]);
+}
@@ -0,0 +1,121 @@
+use std::{borrow::Cow, io::Cursor, num::NonZeroU16};
+
+use blerp::{
+    raw::{self, ByteOrder, RawOptions, RawReadError, RawWriteError},
+    wavefile::{Format, WaveFile},
+};
+use common::sine;
+use cpal::I24;
+
+mod common;
+
+const STEREO: NonZeroU16 = NonZeroU16::new(2).unwrap();
+
+fn write_to_vec(file: &WaveFile, options: &RawOptions) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    raw::write(file, &mut bytes, options).unwrap();
+    bytes
+}
+
+fn options(byte_order: ByteOrder, signed: bool, planar: bool, offset: usize) -> RawOptions {
+    RawOptions { byte_order, signed, planar, offset }
+}
+
+#[test]
+fn round_trip() {
+    macro_rules! round_trip {
+        ($($ty:ty),+) => {
+            $(
+                let file = WaveFile::from_samples(sine::<$ty>(), 44100).unwrap();
+                for byte_order in [ByteOrder::LittleEndian, ByteOrder::BigEndian] {
+                    for signed in [false, true] {
+                        for planar in [false, true] {
+                            for offset in [0, 7] {
+                                let options = options(byte_order, signed, planar, offset);
+                                let bytes = write_to_vec(&file, &options);
+                                assert_eq!(bytes.len(), offset + file.data.len(), "{} {options:?}", stringify!($ty));
+                                let parsed = raw::parse(&bytes, file.format, file.channels, 44100, file.bytes_per_sample, &options).unwrap();
+                                assert_eq!(parsed.data, file.data, "{} {options:?}", stringify!($ty));
+                                let read = raw::read(&mut Cursor::new(&bytes), file.format, file.channels, 44100, file.bytes_per_sample, &options).unwrap();
+                                assert_eq!(read.data, file.data, "{} {options:?}", stringify!($ty));
+                            }
+                        }
+                    }
+                }
+            )+
+        };
+    }
+    round_trip!(u8, i16, I24, i32, f32, f64);
+}
+
+#[test]
+fn layouts() {
+    let frames = |file: &WaveFile| file.frames::<i16, 2>().unwrap().map(<[i16; 2]>::from).collect::<Vec<_>>();
+    // Big-endian planar samples, with all of the left channel before the right channel.
+    let bytes = [0x12, 0x34, 0x00, 0x01, 0xFF, 0xFF, 0x80, 0x00];
+    let file = raw::parse(&bytes, Format::PulseCodeModulation, STEREO, 8000, 2, &options(ByteOrder::BigEndian, true, true, 0)).unwrap();
+    assert_eq!(frames(&file), [[0x1234, -1], [1, i16::MIN]]);
+    // Unsigned 16-bit samples are centred on 0x8000.
+    let file = raw::parse(
+        &[0x00, 0x80, 0xFF, 0xFF],
+        Format::PulseCodeModulation,
+        STEREO,
+        8000,
+        2,
+        &options(ByteOrder::LittleEndian, false, false, 0),
+    )
+    .unwrap();
+    assert_eq!(frames(&file), [[0, i16::MAX]]);
+    // Signed 8-bit samples become unsigned ones.
+    let file = raw::parse(&[0x7F, 0x80], Format::PulseCodeModulation, NonZeroU16::MIN, 8000, 1, &RawOptions::default()).unwrap();
+    assert_eq!(&*file.data, [0xFF, 0x00]);
+
+    // Samples that are already laid out as in a WAV file are borrowed, skipping the offset and the incomplete frame at the end.
+    let bytes = [0xAA, 0xBB, 0xCC, 1, 0, 2, 0, 3, 0, 4, 0, 5];
+    let file = raw::parse(&bytes, Format::PulseCodeModulation, STEREO, 8000, 2, &options(ByteOrder::LittleEndian, true, false, 3)).unwrap();
+    assert!(matches!(file.data, Cow::Borrowed(_)));
+    assert_eq!(frames(&file), [[1, 2], [3, 4]]);
+    // Bytes after the offset that don't make a whole frame in every channel are left out of a planar file too.
+    let file = raw::parse(&bytes, Format::PulseCodeModulation, STEREO, 8000, 2, &options(ByteOrder::LittleEndian, true, true, 3)).unwrap();
+    assert_eq!(frames(&file), [[1, 3], [2, 4]]);
+}
+
+#[test]
+fn to_wav() {
+    let source = WaveFile::from_samples(sine::<f32>(), 48000).unwrap();
+    let options = options(ByteOrder::BigEndian, true, true, 64);
+    let bytes = write_to_vec(&source, &options);
+    let file = raw::parse(&bytes, Format::FloatingPoint, STEREO, 48000, 4, &options).unwrap();
+    let mut wav = Vec::new();
+    file.write(&mut wav).unwrap();
+    let parsed = WaveFile::parse(&wav).unwrap();
+    assert_eq!((parsed.format, parsed.channels, parsed.sample_rate), (Format::FloatingPoint, STEREO, 48000));
+    assert!(parsed.frames::<f32, 2>().unwrap().map(<[f32; 2]>::from).eq(sine::<f32>()));
+
+    // G.711 dumps have no byte order or sign to convert.
+    let file = raw::parse(&[0xD5, 0x2A], Format::ALaw, NonZeroU16::MIN, 8000, 1, &RawOptions::default()).unwrap();
+    assert_eq!(file.frames::<i16, 1>().unwrap().map(<[i16; 1]>::from).collect::<Vec<_>>(), [[8], [-32256]]);
+}
+
+#[test]
+fn errors() {
+    let parse = |format, bytes_per_sample, offset| raw::parse(&[0; 16], format, NonZeroU16::MIN, 8000, bytes_per_sample, &options(ByteOrder::LittleEndian, true, false, offset)).err();
+    assert!(matches!(parse(Format::PulseCodeModulation, 2, 17), Some(RawReadError::OffsetOutOfRange { offset: 17, len: 16 })));
+    assert!(parse(Format::PulseCodeModulation, 2, 16).is_none());
+    for (format, bytes_per_sample) in [
+        (Format::PulseCodeModulation, 0),
+        (Format::PulseCodeModulation, 5),
+        (Format::FloatingPoint, 2),
+        (Format::MuLaw, 2),
+        (Format::ImaAdpcm, 8),
+    ] {
+        assert!(
+            matches!(parse(format, bytes_per_sample, 0), Some(RawReadError::UnsupportedSampleFormat { .. })),
+            "{format:?} {bytes_per_sample}"
+        );
+    }
+    assert!(matches!(
+        raw::write(&WaveFile::from_raw_data(&[0; 8], Format::ImaAdpcm, NonZeroU16::MIN, 8000, 8), &mut Vec::new(), &RawOptions::default()),
+        Err(RawWriteError::UnsupportedSampleFormat { .. })
+    ));
+}
@@ -0,0 +1,186 @@
+use std::f64::consts::{PI, TAU};
+
+use blerp::{
+    processing::{
+        random::Rng,
+        resample::{resample, resample_wave_file, Quality, Resampler},
+    },
+    wavefile::{Codec, CuePoint, Format, WaveFile},
+    Block,
+};
+
+const QUALITIES: [Quality; 4] = [Quality::Fast, Quality::Medium, Quality::High, Quality::Best];
+
+fn sine(frequency: f64, rate: u32, len: usize) -> impl Iterator<Item = f64> {
+    (0..len).map(move |sample| (TAU * frequency * sample as f64 / f64::from(rate)).sin() * 0.5)
+}
+
+fn resample_mono(input: impl IntoIterator<Item = f64>, input_rate: u32, output_rate: u32, quality: Quality) -> Vec<f64> {
+    resample::<f64, 1, _>(input, input_rate, output_rate, quality)
+        .into_iter()
+        .map(|block| <[f64; 1]>::from(block)[0])
+        .collect()
+}
+
+/// The zeroth-order modified Bessel function of the first kind.
+fn bessel_i0(x: f64) -> f64 {
+    let mut term = 1.;
+    1. + (1..100)
+        .map(|k| {
+            term *= (x / (2. * f64::from(k))).powi(2);
+            term
+        })
+        .sum::<f64>()
+}
+
+/// Measure the amplitude of the tone at `frequency` in `signal`, with a Kaiser window whose sidelobes are far below anything the resampler is expected to let through.
+///
+/// The start and end of the signal are skipped, so that the filter's transients at the edges don't count.
+fn amplitude(signal: &[f64], frequency: f64, rate: u32) -> f64 {
+    let signal = &signal[1000..signal.len() - 1000];
+    let len = signal.len() as f64;
+    let (mut real, mut imaginary, mut weights) = (0., 0., 0.);
+    for (index, sample) in signal.iter().enumerate() {
+        let position = 2. * index as f64 / (len - 1.) - 1.;
+        let weight = bessel_i0(30. * (1. - position * position).sqrt()) / bessel_i0(30.);
+        let phase = TAU * frequency * index as f64 / f64::from(rate);
+        real += sample * weight * phase.cos();
+        imaginary += sample * weight * phase.sin();
+        weights += weight;
+    }
+    2. * real.hypot(imaginary) / weights
+}
+
+fn decibels(gain: f64) -> f64 {
+    20. * gain.log10()
+}
+
+/// The highest frequency that each quality keeps flat (as a fraction of the lower Nyquist frequency), the largest ripple below it in dB, and the minimum rejection in dB of anything
+/// above the lower Nyquist frequency.
+fn specification(quality: Quality) -> (f64, f64, f64) {
+    match quality {
+        Quality::Fast => (0.55, 0.05, 60.),
+        Quality::Medium => (0.8, 0.05, 80.),
+        Quality::High => (0.87, 0.005, 100.),
+        Quality::Best => (0.92, 0.0005, 120.),
+    }
+}
+
+#[test]
+fn passband_ripple() {
+    // 44101 Hz has no common factors with 44100 Hz, so the filter has to be interpolated between phases.
+    for (input_rate, output_rate) in [(48000, 44100), (44100, 48000), (44100, 96000), (96000, 44100), (44100, 44101)] {
+        for quality in QUALITIES {
+            let (edge, ripple, _) = specification(quality);
+            let nyquist = f64::from(input_rate.min(output_rate)) / 2.;
+            let mut worst: f64 = 0.;
+            for fraction in [0.01, 0.1, 0.25, 0.4, 0.5, 0.6, 0.7, 0.8, 0.87, 0.92] {
+                if fraction > edge {
+                    continue;
+                }
+                let frequency = nyquist * fraction;
+                let output = resample_mono(sine(frequency, input_rate, 8192), input_rate, output_rate, quality);
+                worst = worst.max(decibels(amplitude(&output, frequency, output_rate) / 0.5).abs());
+            }
+            assert!(worst < ripple, "{input_rate} -> {output_rate} Hz, {quality:?}: {worst} dB of ripple");
+        }
+    }
+}
+
+#[test]
+fn aliasing_rejection() {
+    for quality in QUALITIES {
+        let (_, _, rejection) = specification(quality);
+        // A tone above the output's Nyquist frequency would fold back down to 44100 - 23000 = 21100 Hz.
+        let output = resample_mono(sine(23000., 48000, 16384), 48000, 44100, quality);
+        let alias = decibels(amplitude(&output, 21100., 44100) / 0.5);
+        assert!(alias < -rejection, "{quality:?}: alias at {alias} dB");
+        // Far above the output's Nyquist frequency, there is even less left.
+        let output = resample_mono(sine(40000., 96000, 16384), 96000, 44100, quality);
+        let alias = decibels(amplitude(&output, 4100., 44100) / 0.5);
+        assert!(alias < -rejection, "{quality:?}: alias at {alias} dB");
+        // Upsampling has to remove the images of the input's spectrum above its Nyquist frequency.
+        let output = resample_mono(sine(10000., 44100, 16384), 44100, 96000, quality);
+        let image = decibels(amplitude(&output, 34100., 96000) / 0.5);
+        assert!(image < -rejection, "{quality:?}: image at {image} dB");
+    }
+}
+
+#[test]
+fn streaming() {
+    let mut rng = Rng::new(0);
+    let input: Vec<[f32; 2]> = (0..5000).map(|_| [rng.bipolar() as f32, rng.bipolar() as f32]).collect();
+    for (input_rate, output_rate) in [(48000, 44100), (22050, 48000), (44100, 44101), (8000, 8000)] {
+        let offline = resample(input.iter().copied(), input_rate, output_rate, Quality::Medium);
+        assert_eq!(offline.len(), (input.len() * output_rate as usize).div_ceil(input_rate as usize));
+
+        let mut resampler = Resampler::<2>::new(input_rate, output_rate, Quality::Medium);
+        // Feed chunks of every size from 0 to 99 frames, including ones shorter than the filter.
+        let mut streamed = Vec::new();
+        let mut remaining = &input[..];
+        for size in (0..100).cycle() {
+            let (chunk, rest) = remaining.split_at(size.min(remaining.len()));
+            streamed.extend(resampler.process::<f32, _>(chunk.iter().copied()));
+            remaining = rest;
+            if remaining.is_empty() {
+                break;
+            }
+        }
+        streamed.extend(resampler.finish::<f32>());
+        assert_eq!(streamed, offline, "{input_rate} -> {output_rate} Hz");
+
+        // The resampler starts again from scratch after finishing.
+        let mut again = resampler.process::<f32, _>(input.iter().copied());
+        again.extend(resampler.finish::<f32>());
+        assert_eq!(again, offline, "{input_rate} -> {output_rate} Hz");
+    }
+}
+
+#[test]
+fn alignment() {
+    // A band-limited signal at the same rate comes out unchanged, without any delay, apart from where it starts and stops suddenly.
+    let input: Vec<f64> = sine(1000., 44100, 2000).collect();
+    let output = resample_mono(input.iter().copied(), 44100, 44100, Quality::High);
+    assert_eq!(output.len(), input.len());
+    for (output, input) in output.iter().zip(&input).skip(100).take(1800) {
+        assert!((output - input).abs() < 1e-5, "{output} != {input}");
+    }
+    // Doubling the rate keeps every input sample, and puts a new one halfway between each pair.
+    let output = resample_mono(input.iter().copied(), 44100, 88200, Quality::High);
+    assert_eq!(output.len(), input.len() * 2);
+    for (index, output) in output.iter().enumerate().skip(200).take(3000) {
+        let expected = (PI * 1000. * index as f64 / 44100.).sin() * 0.5;
+        assert!((output - expected).abs() < 1e-5, "{index}: {output} != {expected}");
+    }
+
+    let resampler = Resampler::<1>::new(48000, 44100, Quality::High);
+    assert!(resampler.latency() > 48);
+    assert!(resample::<f64, 1, Block<f64, 1>>([], 48000, 44100, Quality::High).is_empty());
+}
+
+#[test]
+fn wave_files() {
+    let mut file = WaveFile::from_samples(sine(1000., 48000, 48000).map(|sample| [sample, -sample]), 48000).unwrap();
+    file.metadata.cue_points = vec![CuePoint {
+        id: 1,
+        position: 24000,
+        ..CuePoint::default()
+    }];
+    let resampled = resample_wave_file(&file, 44100, Quality::High).unwrap();
+    assert_eq!(
+        (resampled.format, resampled.bytes_per_sample, resampled.channels, resampled.sample_rate),
+        (Format::FloatingPoint, 8, file.channels, 44100)
+    );
+    assert_eq!(resampled.frame_count(), 44100);
+    assert_eq!(resampled.metadata.cue_points[0].position, 22050);
+    let left: Vec<f64> = resampled.frames::<f64, 2>().unwrap().map(|frame| <[f64; 2]>::from(frame)[0]).collect();
+    assert!((amplitude(&left, 1000., 44100) - 0.5).abs() < 1e-4);
+
+    // Integer samples keep their format, and compressed ones become 16-bit PCM.
+    let pcm = WaveFile::from_samples(sine(1000., 8000, 800).map(cpal::Sample::from_sample::<f64>).collect::<Vec<i16>>(), 8000).unwrap();
+    let resampled = resample_wave_file(&pcm, 16000, Quality::Fast).unwrap();
+    assert_eq!((resampled.format, resampled.bytes_per_sample, resampled.frame_count()), (Format::PulseCodeModulation, 2, 1600));
+    let adpcm = WaveFile::encode(sine(1000., 8000, 800), 8000, Codec::ImaAdpcm { frames_per_block: 505 }).unwrap();
+    let resampled = resample_wave_file(&adpcm, 16000, Quality::Fast).unwrap();
+    assert_eq!((resampled.format, resampled.bytes_per_sample, resampled.frame_count()), (Format::PulseCodeModulation, 2, 1600));
+}
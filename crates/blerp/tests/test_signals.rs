@@ -0,0 +1,159 @@
+use std::f64::consts::TAU;
+
+use blerp::{
+    processing::{
+        export::write_wave,
+        generation::{dc_offset, impulse, inverse_sweep, itu_four_tone, pink_noise_bursts, schroeder_multi_tone, sine_wave, sweep, Sweep, ISO_OCTAVE_BANDS, ITU_FOUR_TONES},
+    },
+    wavefile::{WaveFile, WaveFileWriteError},
+    Block,
+};
+use common::magnitude;
+
+mod common;
+
+fn render<const N: usize>(mut wave: impl FnMut(f64) -> Block<f64, N>, len: usize, sample_rate: u32) -> Vec<[f64; N]> {
+    (0..len).map(|sample| wave(sample as f64 / f64::from(sample_rate)).into()).collect()
+}
+
+fn mono(wave: impl FnMut(f64) -> Block<f64, 1>, len: usize, sample_rate: u32) -> Vec<f64> {
+    render(wave, len, sample_rate).into_iter().map(|[sample]| sample).collect()
+}
+
+#[test]
+fn sweeps() {
+    // The frequency, measured from the time between zero crossings, follows each kind of sweep from 100 Hz to 1.6 kHz over 4 seconds.
+    const SAMPLE_RATE: u32 = 48000;
+    for kind in [Sweep::Linear, Sweep::Logarithmic] {
+        let signal = mono(sweep(kind, 100., 1600., 4., 1.), SAMPLE_RATE as usize * 5, SAMPLE_RATE);
+        for second in 0..4 {
+            let start = second * SAMPLE_RATE as usize;
+            // The frequency in the middle of the 2000 samples that are measured.
+            let time = second as f64 + 1000. / f64::from(SAMPLE_RATE);
+            let expected = match kind {
+                Sweep::Linear => 375_f64.mul_add(time, 100.),
+                Sweep::Logarithmic => 100. * 16_f64.powf(time / 4.),
+            };
+            let crossings: Vec<usize> = (start..start + 2000).filter(|&index| signal[index] <= 0. && signal[index + 1] > 0.).collect();
+            let period = (crossings[crossings.len() - 1] - crossings[0]) as f64 / (crossings.len() - 1) as f64;
+            let frequency = f64::from(SAMPLE_RATE) / period;
+            assert!((frequency / expected - 1.).abs() < 0.03, "{kind:?} at {second} s: {frequency} Hz");
+        }
+        assert!(signal[..SAMPLE_RATE as usize * 4].iter().any(|sample| *sample > 0.99));
+        assert!(signal[SAMPLE_RATE as usize * 4..].iter().all(|sample| *sample == 0.));
+    }
+}
+
+#[test]
+fn deconvolution() {
+    // Play each kind of sweep through a known system (half the level after 20 samples, plus an echo at a quarter of the level after 100), and recover its response.
+    const SAMPLE_RATE: u32 = 8000;
+    let response = |frequency: f64| {
+        let omega = TAU * frequency / f64::from(SAMPLE_RATE);
+        (0.5 * (20. * omega).cos() + 0.25 * (100. * omega).cos()).hypot(0.5 * (20. * omega).sin() + 0.25 * (100. * omega).sin())
+    };
+    for kind in [Sweep::Linear, Sweep::Logarithmic] {
+        let (start, end, duration, amplitude) = (50., 3000., 1., 0.5);
+        let signal = mono(sweep(kind, start, end, duration, amplitude), SAMPLE_RATE as usize, SAMPLE_RATE);
+        let recording: Vec<f64> = (0..signal.len() + 100)
+            .map(|index| 0.5 * index.checked_sub(20).and_then(|index| signal.get(index)).unwrap_or(&0.) + 0.25 * index.checked_sub(100).and_then(|index| signal.get(index)).unwrap_or(&0.))
+            .collect();
+        let inverse = inverse_sweep(kind, start, end, duration, amplitude, SAMPLE_RATE);
+        assert_eq!(inverse.len(), signal.len());
+        // Only the part of the convolution around the impulse response is needed.
+        let impulse_response: Vec<f64> = (inverse.len() - 1 - 512..inverse.len() - 1 + 1024)
+            .map(|lag| (0..inverse.len()).filter_map(|index| Some(recording.get(lag.checked_sub(index)?)? * inverse[index])).sum())
+            .collect();
+        let (peak, _) = impulse_response
+            .iter()
+            .enumerate()
+            .fold((0, 0.), |(peak, level), (index, sample)| if sample.abs() > level { (index, sample.abs()) } else { (peak, level) });
+        assert_eq!(peak, 512 + 20, "{kind:?}");
+        for frequency in [200., 500., 1000., 1500., 2000., 2500.] {
+            let measured = 20. * (magnitude(&impulse_response, frequency, SAMPLE_RATE) / response(frequency)).log10();
+            assert!(measured.abs() < 1., "{kind:?} at {frequency} Hz is off by {measured} dB");
+        }
+    }
+}
+
+#[test]
+fn impulses_and_offsets() {
+    let signal = mono(impulse(0.01, 0.5, 1000), 100, 1000);
+    assert_eq!(signal.iter().position(|sample| *sample != 0.), Some(10));
+    assert_eq!(signal.iter().filter(|sample| **sample != 0.).count(), 1);
+    assert_eq!(signal[10], 0.5);
+
+    let signal = render(dc_offset::<f64, 2>(-0.25), 100, 1000);
+    assert!(signal.iter().all(|block| *block == [-0.25; 2]));
+}
+
+#[test]
+fn noise_bursts() {
+    // 50 ms of noise, then 150 ms of silence, over and over, with different noise in each channel. The samples right on the edges could go either way.
+    let signal = render(pink_noise_bursts::<f64, 2>(0.05, 0.15, 1., 9, 1000), 1000, 1000);
+    for (index, [left, right]) in signal.iter().enumerate().filter(|(index, _)| index % 200 != 0 && index % 200 != 50) {
+        if index % 200 < 50 {
+            assert_ne!(left, right, "sample {index}");
+        } else {
+            assert_eq!([*left, *right], [0.; 2], "sample {index}");
+        }
+    }
+}
+
+#[test]
+fn multi_tones() {
+    const SAMPLE_RATE: u32 = 44100;
+    let frequencies = [100., 300., 1000., 3000., 9000.];
+    let signal = mono(schroeder_multi_tone(&frequencies, 0.8), SAMPLE_RATE as usize, SAMPLE_RATE);
+    for frequency in frequencies {
+        let level = 2. * magnitude(&signal, frequency, SAMPLE_RATE) / f64::from(SAMPLE_RATE);
+        assert!((level - 0.16).abs() < 1e-6, "{frequency} Hz at {level}");
+    }
+
+    let peak = |signal: &[f64]| signal.iter().fold(0., |peak: f64, sample| peak.max(sample.abs()));
+    assert!(peak(&mono(schroeder_multi_tone(&ISO_OCTAVE_BANDS, 1.), SAMPLE_RATE as usize, SAMPLE_RATE)) <= 1.);
+
+    // For harmonics, Schroeder phases keep the peak well below that of tones that all start together, which have a crest factor of √(2 * 10).
+    let harmonics: Vec<f64> = (1..=10).map(|harmonic| 100. * f64::from(harmonic)).collect();
+    let signal = mono(schroeder_multi_tone(&harmonics, 1.), SAMPLE_RATE as usize / 10, SAMPLE_RATE);
+    let rms = (signal.iter().map(|sample| sample * sample).sum::<f64>() / signal.len() as f64).sqrt();
+    assert!(peak(&signal) / rms < 2., "crest factor of {}", peak(&signal) / rms);
+}
+
+#[test]
+fn four_tones() {
+    const SAMPLE_RATE: u32 = 44100;
+    let signal = mono(itu_four_tone(0.8), SAMPLE_RATE as usize, SAMPLE_RATE);
+    let level = |signal: &[f64], frequency| 2. * magnitude(signal, frequency, SAMPLE_RATE) / f64::from(SAMPLE_RATE);
+    for frequency in ITU_FOUR_TONES {
+        assert!((level(&signal, frequency) - 0.2).abs() < 1e-6, "{frequency} Hz");
+    }
+    assert!(signal.iter().all(|sample| sample.abs() <= 0.8));
+
+    // Squaring the signal, as a system with second-order distortion does, puts products at the differences and sums of the tones, but nothing appears at the third-order ones.
+    let distorted: Vec<f64> = signal.iter().map(|sample| 0.1_f64.mul_add(sample * sample, *sample)).collect();
+    for frequency in [509., 515., 525., 531., 2229., 2235., 2245., 2251.] {
+        assert!(level(&distorted, frequency) > 1e-3, "{frequency} Hz");
+    }
+    for frequency in [1881., 1887., 1897., 1903.] {
+        assert!(level(&distorted, frequency) < 1e-9, "{frequency} Hz");
+    }
+}
+
+#[test]
+fn wave_files() {
+    let mut bytes = Vec::new();
+    write_wave(sine_wave::<i16, 2>(1000., i16::MAX), 0.5, 48000, &mut bytes).unwrap();
+    let file = WaveFile::parse(&bytes).unwrap();
+    assert_eq!(file.channels.get(), 2);
+    assert_eq!(file.frame_count(), 24000);
+    let samples: Vec<i16> = file.samples::<i16>().unwrap().collect();
+    assert_eq!(&samples[..4], &[0, 0, 4276, 4276]);
+
+    let mut bytes = Vec::new();
+    write_wave(sweep::<f32, 1>(Sweep::Logarithmic, 20., 20000., 0.1, 0.5), 0.2, 44100, &mut bytes).unwrap();
+    let file = WaveFile::parse(&bytes).unwrap();
+    assert_eq!(file.frame_count(), 8820);
+
+    assert!(matches!(write_wave(dc_offset::<f32, 0>(0.), 1., 44100, &mut Vec::new()), Err(WaveFileWriteError::InvalidChannels)));
+}
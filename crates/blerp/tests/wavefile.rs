@@ -0,0 +1,310 @@
+use std::{
+    borrow::Cow,
+    fs::{create_dir_all, remove_dir_all, File},
+    io::{self, Cursor, Seek, SeekFrom, Write},
+    mem::forget,
+    num::NonZeroU16,
+    process,
+};
+
+use blerp::{
+    wavefile::{BroadcastExtension, ChannelMask, Chunk, CuePoint, Format, Info, LoopKind, MappedWaveFile, Region, SampleLoop, Sampler, WaveFile, WaveFileDecodeError, WaveFileReadError, WaveWriter},
+    Block,
+};
+use common::write_to_vec;
+
+mod common;
+
+#[test]
+fn round_trip() {
+    let pcm = WaveFile::from_samples((0..1000_i16).map(|sample| [sample, -sample]), 48000).unwrap();
+    let float = WaveFile::from_samples((0..1000).map(|sample| f32::from(sample as u8) / 255.), 44100).unwrap();
+    for file in [pcm, float] {
+        let bytes = write_to_vec(&file);
+        for read in [WaveFile::parse(&bytes).unwrap(), WaveFile::read(&mut Cursor::new(&bytes)).unwrap()] {
+            assert_eq!(read.format, file.format);
+            assert_eq!(read.channels, file.channels);
+            assert_eq!(read.sample_rate, file.sample_rate);
+            assert_eq!(read.bytes_per_sample, file.bytes_per_sample);
+            assert_eq!(read.valid_bits_per_sample, file.valid_bits_per_sample);
+            assert_eq!(read.channel_mask, file.channel_mask);
+            assert_eq!(read.data, file.data);
+        }
+    }
+}
+
+#[test]
+fn skips_unknown_chunks() {
+    let file = WaveFile::from_samples([1_u8, 2, 3], 8000).unwrap();
+    let mut bytes = write_to_vec(&file);
+    // Insert an odd-sized (and therefore padded) chunk between `fmt ` and `data`.
+    let data_offset = bytes.windows(4).position(|window| window == b"data").unwrap();
+    bytes.splice(data_offset..data_offset, *b"junk\x03\x00\x00\x00abc\x00");
+    let riff_size = u32::try_from(bytes.len() - 8).unwrap();
+    bytes[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    let read = WaveFile::parse(&bytes).unwrap();
+    assert_eq!(read.format, Format::PulseCodeModulation);
+    assert_eq!(&*read.data, &[1, 2, 3]);
+}
+
+#[test]
+fn errors() {
+    let bytes = write_to_vec(&WaveFile::from_samples([0_i16; 16], 8000).unwrap());
+    assert!(matches!(WaveFile::parse(b"RIFX\0\0\0\0WAVE"), Err(WaveFileReadError::BadMagic)));
+    assert!(matches!(WaveFile::parse(&bytes[..bytes.len() - 1]), Err(WaveFileReadError::TruncatedChunk(id)) if &id == b"data"));
+    assert!(matches!(WaveFile::parse(&bytes[..36]), Err(WaveFileReadError::MissingChunk(id)) if &id == b"data"));
+
+    let mut unsupported = bytes;
+    unsupported[20..22].copy_from_slice(&0x55_u16.to_le_bytes());
+    assert!(matches!(WaveFile::parse(&unsupported), Err(WaveFileReadError::UnsupportedFormat(0x55))));
+}
+
+#[test]
+fn frames() {
+    let stereo = WaveFile::from_samples([[i16::MIN, i16::MAX], [0, -1]], 44100).unwrap();
+    let frames: Vec<[i16; 2]> = stereo.frames::<i16, 2>().unwrap().map(Into::into).collect();
+    assert_eq!(frames, [[i16::MIN, i16::MAX], [0, -1]]);
+    let frames: Vec<[f32; 2]> = stereo.frames::<f32, 2>().unwrap().map(Into::into).collect();
+    assert_eq!(frames[0], [-1., 32767. / 32768.]);
+    assert!(matches!(stereo.frames::<f32, 1>(), Err(WaveFileDecodeError::ChannelMismatch { expected: 1, .. })));
+
+    let unsigned = WaveFile::from_samples([0_u8, 128, 255], 8000).unwrap();
+    let frames: Vec<Block<i16, 1>> = unsigned.frames().unwrap().collect();
+    assert_eq!(frames, [Block::from(i16::MIN), Block::from(0), Block::from(i16::MAX - 255)]);
+
+    let float = WaveFile::from_samples([0.5_f64, -0.25], 8000).unwrap();
+    let frames: Vec<Block<f32, 1>> = float.frames().unwrap().collect();
+    assert_eq!(frames, [Block::from(0.5), Block::from(-0.25)]);
+
+    let packed = [0x00, 0x00, 0x80, 0xff, 0xff, 0x7f, 0x01, 0x00, 0x00];
+    let packed = WaveFile::from_raw_data(&packed, Format::PulseCodeModulation, NonZeroU16::MIN, 48000, 3);
+    let frames: Vec<Block<i32, 1>> = packed.frames().unwrap().collect();
+    assert_eq!(frames, [Block::from(i32::MIN), Block::from(0x7fff_ff00), Block::from(0x100)]);
+
+    let unsupported = WaveFile::from_raw_data(&[0; 4], Format::FloatingPoint, NonZeroU16::MIN, 48000, 2);
+    assert!(matches!(unsupported.frames::<f32, 1>(), Err(WaveFileDecodeError::UnsupportedSampleFormat { .. })));
+}
+
+#[test]
+fn extensible() {
+    let surround = WaveFile::from_samples((0..100_i16).map(|sample| [sample; 6]), 48000).unwrap();
+    assert!(surround.is_extensible());
+    let bytes = write_to_vec(&surround);
+    assert_eq!(&bytes[20..22], &0xFFFE_u16.to_le_bytes());
+    let read = WaveFile::parse(&bytes).unwrap();
+    assert_eq!(read.format, Format::PulseCodeModulation);
+    assert_eq!(read.channel_mask, ChannelMask::SURROUND_5_1);
+    assert_eq!(read.data, surround.data);
+
+    let mut padded = WaveFile::from_raw_data(&[0, 0x56, 0x34, 0x12], Format::PulseCodeModulation, NonZeroU16::MIN, 96000, 4);
+    padded.valid_bits_per_sample = 24;
+    padded.channel_mask = ChannelMask::FRONT_LEFT;
+    let read = WaveFile::read(&mut Cursor::new(write_to_vec(&padded))).unwrap();
+    assert_eq!(read.valid_bits_per_sample, 24);
+    assert_eq!(read.channel_mask, ChannelMask::FRONT_LEFT);
+    let frames: Vec<Block<i32, 1>> = read.frames().unwrap().collect();
+    assert_eq!(frames, [Block::from(0x1234_5600)]);
+
+    let stereo = WaveFile::from_samples([[0_i16; 2]], 44100).unwrap();
+    assert!(!stereo.is_extensible());
+    let mut unknown = write_to_vec(&WaveFile::from_samples([0_f32], 44100).unwrap());
+    unknown[50] = 0x42;
+    assert!(matches!(WaveFile::parse(&unknown), Err(WaveFileReadError::UnsupportedSubFormat(_))));
+}
+
+#[test]
+fn rf64() {
+    let file = WaveFile::from_samples((0..999).map(|sample| [f64::from(sample) / 999.; 2]), 96000).unwrap();
+    let mut bytes = Vec::new();
+    file.write_rf64(&mut bytes).unwrap();
+    assert_eq!(&bytes[0..4], b"RF64");
+    assert_eq!(&bytes[12..16], b"ds64");
+    let read = WaveFile::parse(&bytes).unwrap();
+    assert_eq!(read.format, Format::FloatingPoint);
+    assert_eq!(read.data, file.data);
+
+    let mut bw64 = bytes;
+    bw64[0..4].copy_from_slice(b"BW64");
+    assert_eq!(WaveFile::parse(&bw64).unwrap().data, file.data);
+    bw64[12..16].copy_from_slice(b"junk");
+    assert!(matches!(WaveFile::parse(&bw64), Err(WaveFileReadError::MissingChunk(id)) if &id == b"ds64"));
+}
+
+#[test]
+fn streaming_writer() {
+    let frames = (0..1001_i16).map(|sample| [sample, sample / 2]).collect::<Vec<_>>();
+    let expected = WaveFile::from_samples(frames.iter().copied(), 44100).unwrap();
+
+    let mut writer = WaveWriter::<_, i16, 2>::new(Cursor::new(Vec::new()), 44100).unwrap();
+    for chunk in frames.chunks(100) {
+        writer.write_frames(chunk.iter().copied()).unwrap();
+    }
+    assert_eq!(writer.frames_written(), 1001);
+    let bytes = writer.finalize().unwrap().into_inner();
+    let read = WaveFile::parse(&bytes).unwrap();
+    assert_eq!(read.channels.get(), 2);
+    assert_eq!(read.data, expected.data);
+
+    // Dropping the writer patches the headers too.
+    let mut bytes = Cursor::new(Vec::new());
+    let mut writer = WaveWriter::<_, f32, 1>::new(&mut bytes, 48000).unwrap();
+    writer.write_frames([0.5, -0.5, 0.25]).unwrap();
+    drop(writer);
+    let bytes = bytes.into_inner();
+    assert_eq!(WaveFile::parse(&bytes).unwrap().frames::<f32, 1>().unwrap().count(), 3);
+
+    // A writer that never gets to patch the headers (e.g. because the process crashed) still leaves a readable file.
+    let mut bytes = Cursor::new(Vec::new());
+    let mut writer = WaveWriter::<_, i16, 2>::new(&mut bytes, 44100).unwrap();
+    writer.write_frames(frames.iter().copied()).unwrap();
+    forget(writer);
+    let bytes = bytes.into_inner();
+    assert_eq!(WaveFile::parse(&bytes).unwrap().data, expected.data);
+
+    // If patching fails partway through `finalize`, dropping the writer doesn't pad the data a second time.
+    struct NoSeeking(Cursor<Vec<u8>>);
+    impl Write for NoSeeking {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.0.flush()
+        }
+    }
+    impl Seek for NoSeeking {
+        fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+            match position {
+                SeekFrom::Current(0) => self.0.seek(position),
+                _ => Err(io::ErrorKind::Unsupported.into()),
+            }
+        }
+    }
+    let mut bytes = NoSeeking(Cursor::new(Vec::new()));
+    let mut writer = WaveWriter::<_, u8, 1>::new(&mut bytes, 8000).unwrap();
+    writer.write_frames([1, 2, 3]).unwrap();
+    assert!(writer.finalize().is_err());
+    let bytes = bytes.0.into_inner();
+    assert_eq!(&bytes[bytes.len() - 4..], [1, 2, 3, 0]);
+}
+
+#[test]
+fn metadata() {
+    let mut file = WaveFile::from_samples([0_i16, 1, 2, 3], 48000).unwrap();
+    file.metadata.broadcast = Some(BroadcastExtension {
+        description: "Take 3".into(),
+        originator: "Volt".into(),
+        origination_date: "2024-06-01".into(),
+        origination_time: "12:34:56".into(),
+        time_reference: 48000 * 60 * 60 * 10,
+        loudness_value: -2300,
+        coding_history: "A=PCM,F=48000,W=16,M=mono,T=Volt\r\n".into(),
+        ..BroadcastExtension::default()
+    });
+    file.metadata.info = Info {
+        title: Some("Room tone".into()),
+        artist: Some("Someone".into()),
+        comment: None,
+        software: Some("Volt".into()),
+        other: vec![(*b"ICRD", "2024".into())],
+    };
+    file.metadata.unknown_chunks.push(Chunk {
+        id: *b"iXML",
+        data: b"<BWFXML/>".to_vec(),
+    });
+
+    let bytes = write_to_vec(&file);
+    let read = WaveFile::parse(&bytes).unwrap();
+    assert_eq!(read.metadata, file.metadata);
+    assert_eq!(read.data, file.data);
+    // Re-saving the file must not change or drop anything.
+    assert_eq!(write_to_vec(&read), bytes);
+}
+
+#[test]
+fn markers_and_loops() {
+    let mut file = WaveFile::from_samples((0..48000).map(|sample| f32::from(sample as u16) / 65535.), 48000).unwrap();
+    file.metadata.cue_points = vec![
+        CuePoint {
+            id: 1,
+            position: 1000,
+            label: Some("Hit".into()),
+            ..CuePoint::default()
+        },
+        CuePoint {
+            id: 2,
+            position: 12000,
+            label: Some("Sustain".into()),
+            note: Some("Loop this".into()),
+            region: Some(Region {
+                country: 44,
+                language: 9,
+                dialect: 1,
+                code_page: 1252,
+                text: Some("Chorus".into()),
+                ..Region::new(24000)
+            }),
+            // An embedded file, which isn't parsed but must be written back as it was.
+            other_chunks: vec![Chunk {
+                id: *b"file",
+                data: b"MIDI\x01\x02\x03".to_vec(),
+            }],
+        },
+    ];
+    let mut sampler = Sampler::new(48000, 60);
+    sampler.loops.push(SampleLoop {
+        id: 2,
+        kind: LoopKind::PingPong,
+        start: 12000,
+        end: 35999,
+        fraction: 0,
+        play_count: 0,
+    });
+    file.metadata.sampler = Some(sampler);
+
+    let bytes = write_to_vec(&file);
+    let read = WaveFile::parse(&bytes).unwrap();
+    assert_eq!(read.metadata, file.metadata);
+    assert_eq!(read.metadata.markers().map(|marker| marker.position).collect::<Vec<_>>(), [1000]);
+    assert_eq!(read.metadata.regions().map(|region| region.label.as_deref()).collect::<Vec<_>>(), [Some("Sustain")]);
+    assert_eq!(write_to_vec(&read), bytes);
+
+    // The `adtl` list can come before the `cue ` chunk.
+    let chunk = |id: &[u8; 4]| {
+        let start = bytes.windows(4).position(|window| window == id).unwrap();
+        let len = u32::from_le_bytes(bytes[start + 4..start + 8].try_into().unwrap()) as usize;
+        Chunk {
+            id: *id,
+            data: bytes[start + 8..start + 8 + len].to_vec(),
+        }
+    };
+    let mut reordered = file.clone();
+    reordered.metadata.cue_points.clear();
+    reordered.metadata.unknown_chunks = vec![chunk(b"LIST"), chunk(b"cue ")];
+    let read = WaveFile::read(&mut Cursor::new(write_to_vec(&reordered))).unwrap();
+    assert_eq!(read.metadata.cue_points, file.metadata.cue_points);
+    assert!(read.metadata.unknown_chunks.is_empty());
+}
+
+#[test]
+fn memory_mapped() {
+    let file = WaveFile::from_samples((0..10000_i32).map(|sample| [sample << 16, -sample << 16]), 96000).unwrap();
+    // Each test that writes files works in its own directory inside the shared temporary one, so that test binaries running in parallel don't clear each other's files.
+    let directory = format!("{}/memory_mapped-{}", env!("CARGO_TARGET_TMPDIR"), process::id());
+    create_dir_all(&directory).unwrap();
+    let path = format!("{directory}/memory_mapped.wav");
+    file.write(&mut File::create(&path).unwrap()).unwrap();
+
+    // SAFETY: Nothing else knows about this file, so it isn't modified while it is mapped.
+    let mapped = unsafe { MappedWaveFile::open(&path) }.unwrap();
+    assert!(mapped.header().data.is_empty());
+    let read = mapped.wave_file();
+    assert!(matches!(read.data, Cow::Borrowed(_)));
+    assert_eq!(read.data, file.data);
+    assert_eq!(read.frames::<i32, 2>().unwrap().count(), 10000);
+
+    // SAFETY: The file doesn't exist, so there is nothing to map.
+    assert!(matches!(unsafe { MappedWaveFile::open(format!("{directory}/missing.wav")) }, Err(WaveFileReadError::Io(_))));
+    drop(mapped);
+    remove_dir_all(directory).unwrap();
+}
@@ -0,0 +1,129 @@
+use std::f64::consts::TAU;
+
+use blerp::{
+    processing::{
+        generation::{harmonics, Harmonic},
+        wavetable::{Wavetable, WavetableError, WavetableOscillator},
+    },
+    wavefile::WaveFile,
+    Block,
+};
+use common::{amplitude, render, SAMPLE_RATE};
+
+mod common;
+
+#[test]
+fn matches_harmonics() {
+    // The same harmonics as `harmonics` makes by calling `sin` for every partial of every sample.
+    let partials = [Harmonic::new(1., 0), Harmonic::new(0.5, 1), Harmonic::new(0.8, 4), Harmonic::new(1., 9)];
+    let wavetable = Wavetable::from_harmonics(&partials);
+    assert_eq!(wavetable.frames(), 1);
+    let mut oscillator = WavetableOscillator::new(&wavetable, 261.63, 1., SAMPLE_RATE);
+    let mut reference = harmonics::<f64, 1>(261.63, &partials);
+    for (index, sample) in render(&mut oscillator, 4410).into_iter().enumerate() {
+        let [expected] = <[f64; 1]>::from(reference(index as f64 / f64::from(SAMPLE_RATE)));
+        assert!((sample - expected).abs() < 1e-4, "sample {index}: {sample} != {expected}");
+    }
+}
+
+#[test]
+fn mip_levels() {
+    // Every harmonic of a sawtooth wave, which are all kept at a low frequency...
+    let partials: Vec<Harmonic<f64>> = (0..2000).map(|index| Harmonic::new(1., index)).collect();
+    let wavetable = Wavetable::from_harmonics(&partials);
+    let low = render(&mut WavetableOscillator::new(&wavetable, 20., 1., SAMPLE_RATE), SAMPLE_RATE as usize);
+    for harmonic in [1., 10., 100.] {
+        assert!((amplitude(&low, 20. * harmonic) - 1. / harmonic).abs() < 1e-2 / harmonic, "harmonic {harmonic}");
+    }
+    // Interpolating between the samples of the table softens the harmonics close to its Nyquist frequency a little.
+    assert!((amplitude(&low, 20000.) * 1000. - 1.).abs() < 0.5);
+    // ...but only those below the Nyquist frequency at a high one, so nothing folds back down. The mip levels have 1023, 511, ..., 7, 3 and 1 harmonics, so that is 3 here.
+    let high = render(&mut WavetableOscillator::new(&wavetable, 5000., 1., SAMPLE_RATE), SAMPLE_RATE as usize);
+    for harmonic in 1..=3 {
+        let level = amplitude(&high, 5000. * f64::from(harmonic)) * f64::from(harmonic);
+        assert!((level - 1.).abs() < 0.01, "harmonic {harmonic} at {level}");
+    }
+    for alias in [19100., 14100., 9100., 4100., 900.] {
+        let level = 20. * amplitude(&high, alias).log10();
+        assert!(level < -60., "{alias} Hz at {level} dB");
+    }
+}
+
+#[test]
+fn morphing() {
+    let sine: Vec<f64> = (0..600).map(|index| (TAU * f64::from(index) / 600.).sin()).collect();
+    let inverted: Vec<f64> = sine.iter().map(|sample| -sample).collect();
+    let square: Vec<f64> = (0..600).map(|index| if index < 300 { 0.5 } else { -0.5 }).collect();
+    let wavetable = Wavetable::from_cycles(&[sine, inverted, square]).unwrap();
+    assert_eq!(wavetable.frames(), 3);
+
+    let mut oscillator = WavetableOscillator::new(&wavetable, 441., 1., SAMPLE_RATE);
+    let first = render(&mut oscillator, 100);
+    for (index, sample) in first.iter().enumerate() {
+        let expected = (TAU * 441. * index as f64 / f64::from(SAMPLE_RATE)).sin();
+        assert!((sample - expected).abs() < 1e-4, "{sample} != {expected}");
+    }
+    // Halfway between a sine wave and its inverse is silence.
+    oscillator.set_phase(0.);
+    oscillator.set_position(0.25);
+    assert!(render(&mut oscillator, 100).iter().all(|sample| sample.abs() < 1e-6));
+    oscillator.set_phase(0.);
+    oscillator.set_position(0.5);
+    let second = render(&mut oscillator, 100);
+    assert!(second.iter().zip(&first).all(|(second, first)| (second + first).abs() < 1e-6));
+    // The last frame is a square wave, made of odd harmonics of 2 / πn.
+    oscillator.set_position(2.);
+    let square = render(&mut oscillator, SAMPLE_RATE as usize);
+    assert!((amplitude(&square, 441.) - 2. / std::f64::consts::PI).abs() < 1e-3);
+    assert!(amplitude(&square, 882.) < 1e-3);
+    assert!((amplitude(&square, 1323.) - 2. / (3. * std::f64::consts::PI)).abs() < 1e-3);
+}
+
+#[test]
+fn negative_phases() {
+    let wavetable = Wavetable::from_harmonics(&[Harmonic::new(1., 0)]);
+    let mut oscillator = WavetableOscillator::new(&wavetable, 441., 1., SAMPLE_RATE);
+    // A phase just below zero wraps around to the start of the cycle, not to the end of the table.
+    oscillator.set_phase(-1e-20);
+    assert!(oscillator.next_sample().abs() < 1e-6);
+
+    // A negative frequency plays the cycle backwards, which is the sine wave upside down.
+    oscillator.set_phase(0.);
+    oscillator.set_frequency(-441.);
+    for (index, sample) in render(&mut oscillator, 200).into_iter().enumerate() {
+        let expected = -(TAU * 441. * index as f64 / f64::from(SAMPLE_RATE)).sin();
+        assert!((sample - expected).abs() < 1e-4, "sample {index}: {sample} != {expected}");
+    }
+    // A tiny negative frequency takes the phase from 0 to just below it.
+    oscillator.set_phase(0.);
+    oscillator.set_frequency(-1e-16);
+    assert!(render(&mut oscillator, 2).iter().all(|sample| sample.abs() < 1e-6));
+}
+
+#[test]
+fn wave_files() {
+    // A single-cycle sawtooth wave with a constant offset, which is removed.
+    let cycle: Vec<[f32; 2]> = (0..256).map(|index| [index as f32 / 128. - 0.5, index as f32 / 128. - 0.5]).collect();
+    let file = WaveFile::from_samples(cycle, 48000).unwrap();
+    let wavetable = Wavetable::from_wave_file(&file, file.frame_count()).unwrap();
+    assert_eq!(wavetable.frames(), 1);
+    let mut oscillator = WavetableOscillator::new(&wavetable, 100., 1., SAMPLE_RATE);
+    let saw = render(&mut oscillator, SAMPLE_RATE as usize);
+    assert!(saw.iter().sum::<f64>().abs() / f64::from(SAMPLE_RATE) < 1e-4);
+    for harmonic in [1., 2., 3., 10.] {
+        assert!((amplitude(&saw, 100. * harmonic) - 2. / (std::f64::consts::PI * harmonic)).abs() < 0.01, "harmonic {harmonic}");
+    }
+
+    // A file of several cycles is split into frames, and filling a buffer works with any sample type.
+    let frames: Vec<i16> = (0..3 * 64 + 10).map(|index| if index % 64 < 32 { 10000 } else { -10000 }).collect();
+    let file = WaveFile::from_samples(frames, 48000).unwrap();
+    let wavetable = Wavetable::from_wave_file(&file, 64).unwrap();
+    assert_eq!(wavetable.frames(), 3);
+    let mut blocks = [Block::from([0_i16; 2]); 64];
+    WavetableOscillator::new(&wavetable, 1000., 1., SAMPLE_RATE).fill(&mut blocks);
+    assert!(blocks.iter().any(|&block| <[i16; 2]>::from(block)[0] > 9000));
+
+    let empty = WaveFile::from_samples(Vec::<i16>::new(), 48000).unwrap();
+    assert!(matches!(Wavetable::from_wave_file(&empty, 64), Err(WavetableError::Empty)));
+    assert!(matches!(Wavetable::from_cycles::<Vec<f64>>(&[]), Err(WavetableError::Empty)));
+}
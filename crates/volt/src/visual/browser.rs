@@ -1,13 +1,19 @@
 #![warn(clippy::nursery, clippy::pedantic, clippy::undocumented_unsafe_blocks)]
+use blerp::{
+    aiff,
+    raw::{self, RawOptions},
+    wavefile::Format,
+};
 use itertools::Itertools;
 use open::that_detached;
-use rodio::{Decoder, OutputStream, Sink, Source};
+use rodio::{buffer::SamplesBuffer, Decoder, OutputStream, Sink, Source};
 use std::{
     borrow::Cow,
     fs::{read_dir, DirEntry, File},
     io::BufReader,
     iter::Iterator,
     mem::{transmute_copy, ManuallyDrop, MaybeUninit},
+    num::NonZeroU16,
     ops::BitOr,
     path::{Path, PathBuf},
     str::FromStr,
@@ -78,12 +84,7 @@ impl<P: AsRef<Path>> From<P> for EntryKind {
     fn from(value: P) -> Self {
         if value.as_ref().is_dir() {
             Self::Directory
-        } else if value
-            .as_ref()
-            .extension()
-            .and_then(|extension| extension.to_str())
-            .is_some_and(|extension| AUDIO_EXTENSIONS.into_iter().any(|audio_extension| audio_extension.eq_ignore_ascii_case(extension)))
-        {
+        } else if has_extension(value.as_ref(), &AUDIO_EXTENSIONS) || has_extension(value.as_ref(), &AIFF_EXTENSIONS) || has_extension(value.as_ref(), &RAW_EXTENSIONS) {
             Self::Audio
         } else {
             Self::File
@@ -165,9 +166,13 @@ impl Browser {
                         let Ok(path) = path_rx.recv() else {
                             break;
                         };
-                        let source = Decoder::new(BufReader::new(File::open(&path).unwrap())).unwrap();
                         let empty = sink.empty();
                         sink.stop();
+                        // A file that can't be read or decoded just stops the preview, rather than taking the thread down with it.
+                        let Some(source) = preview_source(&path) else {
+                            last_path = None;
+                            continue;
+                        };
                         if last_path != Some(path.clone()) || empty {
                             file_data_tx
                                 .send(PreviewData {
@@ -416,3 +421,30 @@ impl Widget for &mut Browser {
 }
 
 const AUDIO_EXTENSIONS: [&str; 6] = ["wav", "wave", "mp3", "ogg", "flac", "opus"];
+/// Extensions of AIFF and AIFF-C files, which rodio can't decode, so they are previewed with [`aiff::read`] instead.
+const AIFF_EXTENSIONS: [&str; 3] = ["aif", "aiff", "aifc"];
+/// Extensions of headerless sample dumps, which are previewed with [`raw::read`] instead of rodio.
+const RAW_EXTENSIONS: [&str; 2] = ["raw", "pcm"];
+
+/// Open and decode a file for previewing, returning [`None`] if it can't be read.
+fn preview_source(path: &Path) -> Option<Box<dyn Source<Item = i16> + Send>> {
+    let mut reader = BufReader::new(File::open(path).ok()?);
+    Some(if has_extension(path, &RAW_EXTENSIONS) {
+        // Headerless files don't say how they are laid out, so preview them as the most common layout: 16-bit little-endian stereo at 44.1 kHz.
+        let file = raw::read(&mut reader, Format::PulseCodeModulation, NonZeroU16::new(2).unwrap(), 44100, 2, &RawOptions::default()).ok()?;
+        let samples = file.frames::<i16, 2>().ok()?.flat_map(<[i16; 2]>::from).collect_vec();
+        Box::new(SamplesBuffer::new(2, 44100, samples))
+    } else if has_extension(path, &AIFF_EXTENSIONS) {
+        let file = aiff::read(&mut reader).ok()?;
+        let samples = file.samples::<i16>().ok()?.collect_vec();
+        Box::new(SamplesBuffer::new(file.channels.get(), file.sample_rate, samples))
+    } else {
+        Box::new(Decoder::new(reader).ok()?)
+    })
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| extensions.iter().any(|candidate| candidate.eq_ignore_ascii_case(extension)))
+}